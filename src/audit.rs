@@ -0,0 +1,151 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ObjectID;
+
+/// One mutating operation recorded in the audit log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Seconds since the Unix epoch
+    pub time: u64,
+    pub user: String,
+    pub operation: String,
+
+    /// "HEAD" or the ref name the operation changed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+
+    /// Object ID the operation produced or set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub object_id: Option<String>,
+
+    /// Object ID the reference pointed to before the operation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous: Option<String>,
+
+    /// Number of objects removed or packed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub objects: Option<u64>,
+}
+
+impl AuditEntry {
+    pub fn new<S: Into<String>>(operation: S) -> Self {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Self {
+            time,
+            user: current_user(),
+            operation: operation.into(),
+            reference: None,
+            object_id: None,
+            previous: None,
+            objects: None,
+        }
+    }
+
+    pub fn reference<S: Into<String>>(mut self, reference: S) -> Self {
+        self.reference = Some(reference.into());
+        self
+    }
+
+    pub fn object_id(mut self, object_id: &ObjectID) -> Self {
+        self.object_id = Some(object_id.to_string());
+        self
+    }
+
+    pub fn previous(mut self, object_id: Option<ObjectID>) -> Self {
+        self.previous = object_id.map(|id| id.to_string());
+        self
+    }
+
+    pub fn objects(mut self, objects: u64) -> Self {
+        self.objects = Some(objects);
+        self
+    }
+}
+
+/// Append an entry to the audit log as one JSON line.
+///
+/// The line is written with a single `write` on a file opened in append
+/// mode, so concurrent writers never interleave within a line.
+pub fn append<P: AsRef<Path>>(audit_file: P, entry: &AuditEntry) -> io::Result<()> {
+    let mut line = serde_json::to_vec(entry)?;
+    line.push(b'\n');
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(audit_file)?;
+    file.write_all(&line)
+}
+
+/// Read all entries of the audit log, oldest first.
+pub fn read<P: AsRef<Path>>(audit_file: P) -> io::Result<Vec<AuditEntry>> {
+    let audit_file = audit_file.as_ref();
+    if !audit_file.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut entries = Vec::new();
+    for (i, line) in fs::read_to_string(audit_file)?.lines().enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+        match serde_json::from_str(line) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => log::warn!("{}:{}: ignored: {}", audit_file.display(), i + 1, e),
+        }
+    }
+    Ok(entries)
+}
+
+pub(crate) fn current_user() -> String {
+    if let Some(user) = ["USER", "LOGNAME", "USERNAME"]
+        .iter()
+        .find_map(|key| std::env::var(key).ok().filter(|user| !user.is_empty()))
+    {
+        return user;
+    }
+
+    #[cfg(unix)]
+    return format!("uid:{}", unsafe { libc::getuid() });
+
+    #[cfg(not(unix))]
+    return "unknown".to_string();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{append, read, AuditEntry};
+    use crate::ObjectID;
+
+    #[test]
+    fn test_append_and_read() {
+        let audit_file = std::env::temp_dir().join(format!("mtl-audit-{}.log", std::process::id()));
+        let object_id = ObjectID::from_hex("99f9d6592fc5edec").unwrap();
+
+        let build = AuditEntry::new("build")
+            .reference("HEAD")
+            .object_id(&object_id);
+        let gc = AuditEntry::new("gc").objects(3);
+        append(&audit_file, &build).unwrap();
+        append(&audit_file, &gc).unwrap();
+
+        let contents = std::fs::read_to_string(&audit_file).unwrap();
+        let entries = read(&audit_file).unwrap();
+        std::fs::remove_file(&audit_file).unwrap();
+
+        assert_eq!(entries, vec![build, gc]);
+        assert!(contents
+            .lines()
+            .next()
+            .unwrap()
+            .contains(r#""operation":"build","reference":"HEAD","object_id":"99f9d6592fc5edec"}"#));
+    }
+}
@@ -1,5 +1,8 @@
 mod parallel;
+#[cfg(feature = "sqlite")]
+mod sqlite;
 
+use std::collections::HashSet;
 use std::ffi::{OsStr, OsString};
 use std::fs::File;
 use std::io::{BufRead, Read};
@@ -8,41 +11,77 @@ use std::rc::Rc;
 use std::sync::Arc;
 use std::{fs, io};
 
-use ignore::{WalkBuilder, WalkState};
+use scopeguard::defer;
 
-use crate::filter::Filter;
-use crate::progress::BuildProgressBar;
-use crate::{Context, Object, ObjectType, ReadContentError, RelativePath};
+use ignore::{DirEntry, ParallelVisitor, ParallelVisitorBuilder, WalkBuilder, WalkState};
 
+use crate::filter::{Filter, HiddenPolicy};
+use crate::progress::{Phase, ProgressFormat, ProgressSink};
+use crate::{normalize_listed_path, Context, Object, ObjectType, ReadContentError, RelativePath};
+
+#[cfg(feature = "sqlite")]
+pub use sqlite::{SqliteTargetGenerator, DEFAULT_SQLITE_QUERY};
+
+/// Number of files and directories a build hashed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BuildCounts {
+    pub files: u64,
+    pub dirs: u64,
+    /// Total size of the files
+    pub bytes: u64,
+}
+
+/// Produces the entries a [`Builder`] hashes.
+///
+/// The returned [`TargetEntries`] must contain the root directory and every
+/// directory that has entries below it; entries whose parent directory is
+/// missing are skipped by the build.
 pub trait TargetGenerator {
     fn generate(&self, ctx: &Context) -> anyhow::Result<TargetEntries, ReadContentError>;
 }
 
 pub struct Builder {
     generator: Box<dyn TargetGenerator>,
-    progress: bool,
+    progress: Arc<dyn ProgressSink>,
 }
 
 impl Builder {
+    /// A builder showing progress bars if `progress` is true.
     pub fn new(generator: Box<dyn TargetGenerator>, progress: bool) -> Self {
         Self {
             generator,
-            progress,
+            progress: ProgressFormat::sink(progress.then_some(ProgressFormat::Bar)),
         }
     }
 
+    /// Report the progress to `progress` instead.
+    pub fn with_progress_sink(mut self, progress: Arc<dyn ProgressSink>) -> Self {
+        self.progress = progress;
+        self
+    }
+
     pub fn build(&self, ctx: &Context) -> anyhow::Result<Object, ReadContentError> {
+        Ok(self.build_counted(ctx)?.0)
+    }
+
+    /// Like [`Builder::build`], also returning how many entries were hashed.
+    pub fn build_counted(
+        &self,
+        ctx: &Context,
+    ) -> anyhow::Result<(Object, BuildCounts), ReadContentError> {
+        defer! {
+            self.progress.finish();
+        }
+        self.progress.set_phase(Phase::Scan);
         let target_entries = self.generator.generate(ctx)?;
         if target_entries.max_depth == 0 {
             return Err(ReadContentError::TargetEmpty);
         }
 
-        let pb = BuildProgressBar::new(
-            target_entries.num_files,
-            target_entries.num_dirs,
-            self.progress,
-        );
-        Ok(parallel::build(ctx, &pb, target_entries)?)
+        let (files, dirs) = (target_entries.num_files, target_entries.num_dirs);
+        self.progress.set_phase(Phase::Hash { files, dirs });
+        let (root, bytes) = parallel::build(ctx, self.progress.as_ref(), target_entries)?;
+        Ok((root, BuildCounts { files, dirs, bytes }))
     }
 
     pub fn update<P: AsRef<Path>>(&self, ctx: &Context, path: P) -> anyhow::Result<Object> {
@@ -56,6 +95,7 @@ impl Builder {
         let (object_id, object_ids) = object_ids.split_first().unwrap();
         if *object_id == updated_root_id {
             log::info!("nothing to update");
+            self.progress.message("nothing to update");
             return Ok(updated_object);
         }
 
@@ -102,8 +142,9 @@ impl Builder {
     }
 }
 
+/// A file or directory to be hashed, relative to the root directory.
 #[derive(Debug, Clone, Eq, PartialEq)]
-pub(crate) struct FileEntry {
+pub struct FileEntry {
     pub mode: ObjectType,
     pub path: RelativePath,
     pub depth: usize,
@@ -113,6 +154,17 @@ impl FileEntry {
     pub fn new(mode: ObjectType, path: RelativePath, depth: usize) -> Self {
         Self { mode, path, depth }
     }
+
+    /// Create an entry whose depth is derived from the number of path
+    /// components.
+    pub fn from_path<P: Into<RelativePath>>(mode: ObjectType, path: P) -> Self {
+        let path = path.into();
+        let depth = match &path {
+            RelativePath::Root => 0,
+            RelativePath::Path(path) => path.components().count(),
+        };
+        Self { mode, path, depth }
+    }
 }
 
 impl PartialOrd for FileEntry {
@@ -127,8 +179,9 @@ impl Ord for FileEntry {
     }
 }
 
-#[derive(Debug)]
-pub(crate) struct TargetEntries {
+/// The set of entries produced by a [`TargetGenerator`].
+#[derive(Debug, Default)]
+pub struct TargetEntries {
     max_depth: usize,
     files: Vec<FileEntry>,
     num_files: u64,
@@ -157,6 +210,51 @@ impl TargetEntries {
     pub fn iter(&self) -> impl Iterator<Item = &FileEntry> {
         self.files.iter()
     }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn num_files(&self) -> u64 {
+        self.num_files
+    }
+
+    pub fn num_dirs(&self) -> u64 {
+        self.num_dirs
+    }
+
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+}
+
+impl Extend<FileEntry> for TargetEntries {
+    fn extend<I: IntoIterator<Item = FileEntry>>(&mut self, entries: I) {
+        for entry in entries {
+            self.push_file_entry(entry);
+        }
+    }
+}
+
+impl FromIterator<FileEntry> for TargetEntries {
+    fn from_iter<I: IntoIterator<Item = FileEntry>>(entries: I) -> Self {
+        let mut target_entries = TargetEntries::new();
+        target_entries.extend(entries);
+        target_entries
+    }
+}
+
+impl IntoIterator for TargetEntries {
+    type Item = FileEntry;
+    type IntoIter = std::vec::IntoIter<FileEntry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.files.into_iter()
+    }
 }
 
 fn format_filetype(mode: &fs::FileType) -> &'static str {
@@ -204,111 +302,294 @@ fn windows_format_filetype(mode: &fs::FileType) -> &'static str {
     }
 }
 
+/// Default number of entry batches buffered between the walker threads and
+/// the collector.
+pub const DEFAULT_SCAN_QUEUE_SIZE: usize = 100;
+
+/// Default size from which `--mmap` hashes files through a memory map.
+pub const DEFAULT_MMAP_THRESHOLD: u64 = 1024 * 1024;
+
+// number of entries each walker thread collects before handing them over
+const SCAN_BATCH_SIZE: usize = 1024;
+
+/// Name of the files listing paths for scans to skip, in gitignore syntax.
+///
+/// They are read in every directory like `.gitignore` and `.ignore`, and take
+/// precedence over both.
+pub const IGNORE_FILE_NAME: &str = ".mtlignore";
+
 pub struct ScanTargetGenerator {
     filter: Arc<Box<dyn Filter>>,
-    hidden: bool,
+    hidden: HiddenPolicy,
+    queue_size: usize,
 }
 
 impl ScanTargetGenerator {
+    /// Scan the entries `filter` takes, hidden ones included if `hidden`.
     pub fn new(filter: Box<dyn Filter>, hidden: bool) -> Self {
         Self {
             filter: Arc::new(filter),
-            hidden,
+            hidden: HiddenPolicy::from(hidden),
+            queue_size: DEFAULT_SCAN_QUEUE_SIZE,
         }
     }
+
+    /// Decide which hidden entries to scan by `policy` instead.
+    pub fn with_hidden_policy(mut self, policy: HiddenPolicy) -> Self {
+        self.hidden = policy;
+        self
+    }
+
+    /// Set how many entry batches may be buffered before the walker threads
+    /// block on the collector.
+    pub fn with_queue_size(mut self, queue_size: usize) -> Self {
+        self.queue_size = queue_size.max(1);
+        self
+    }
 }
 
 impl TargetGenerator for ScanTargetGenerator {
     fn generate(&self, ctx: &Context) -> anyhow::Result<TargetEntries, ReadContentError> {
-        let (tx, rx) = crossbeam_channel::bounded::<FileEntry>(100);
+        let (tx, rx) = crossbeam_channel::bounded::<Vec<FileEntry>>(self.queue_size);
 
         let output_thread = std::thread::spawn(move || {
             let mut entries = TargetEntries::new();
             entries.push_file_entry(FileEntry::new(ObjectType::Tree, RelativePath::Root, 0));
-            for entry in rx {
-                entries.push_file_entry(entry);
+            for batch in rx {
+                entries.extend(batch);
             }
             entries
         });
 
         let filter = self.filter.clone();
+        let hidden = self.hidden.clone();
         let root_dir = ctx.root_dir();
+        // hidden entries are left to the policy, which may take some of them
         let walker = WalkBuilder::new(root_dir)
-            .hidden(!self.hidden)
+            .hidden(false)
+            .add_custom_ignore_filename(IGNORE_FILE_NAME)
             .filter_entry(move |entry| {
                 let Ok(path) = entry.path().strip_prefix(filter.root()) else {
                     return false;
                 };
                 let relative_path = RelativePath::from(path);
-                filter.path_matches(&relative_path)
+                let is_dir = entry.file_type().is_some_and(|ft| ft.is_dir());
+                hidden.takes(&relative_path, is_dir) && filter.entry_matches(&relative_path, is_dir)
             })
-            .threads(num_cpus::get())
+            .threads(ctx.jobs())
             .build_parallel();
-        walker.run(|| {
-            let tx = tx.clone();
-            Box::new(move |entry| {
-                // get DirEntry error
-                let Ok(entry) = entry.map_err(|e| log::warn!("ignored: {}", e)) else {
-                    return WalkState::Continue;
-                };
+        walker.visit(&mut ScanVisitorBuilder { root_dir, tx: &tx });
+        drop(tx);
 
-                // strip prefix error
-                let Ok(path) = entry
-                    .path()
-                    .strip_prefix(root_dir)
-                    .map_err(|e| log::error!("strip prefix error: {}", e))
-                else {
-                    return WalkState::Continue;
-                };
-                // root dir
-                if path.as_os_str().is_empty() {
-                    return WalkState::Continue;
-                }
+        Ok(output_thread.join().unwrap())
+    }
+}
 
-                // get file type error
-                let Some(ft) = entry.file_type() else {
-                    return WalkState::Continue;
-                };
+struct ScanVisitorBuilder<'a> {
+    root_dir: &'a Path,
+    tx: &'a crossbeam_channel::Sender<Vec<FileEntry>>,
+}
 
-                // not supported file type
-                if !ft.is_file() && !ft.is_dir() {
-                    log::warn!(
-                        "ignored: not supported file type: {} \"{}\"",
-                        format_filetype(&ft),
-                        path.display()
-                    );
-                    return WalkState::Continue;
-                }
+impl<'s> ParallelVisitorBuilder<'s> for ScanVisitorBuilder<'s> {
+    fn build(&mut self) -> Box<dyn ParallelVisitor + 's> {
+        Box::new(ScanVisitor {
+            root_dir: self.root_dir,
+            tx: self.tx.clone(),
+            batch: Vec::with_capacity(SCAN_BATCH_SIZE),
+        })
+    }
+}
 
-                let object_type = if ft.is_dir() {
-                    ObjectType::Tree
-                } else {
-                    ObjectType::File
-                };
+/// Per-thread shard of the scan.
+///
+/// Entries are collected locally and sent to the collector in batches, so
+/// walker threads rarely contend on the channel.
+struct ScanVisitor<'a> {
+    root_dir: &'a Path,
+    tx: crossbeam_channel::Sender<Vec<FileEntry>>,
+    batch: Vec<FileEntry>,
+}
 
-                tx.send(FileEntry::new(
-                    object_type,
-                    RelativePath::from(path),
-                    entry.depth(),
-                ))
-                .unwrap();
-                WalkState::Continue
-            })
-        });
-        drop(tx);
+impl ScanVisitor<'_> {
+    fn flush(&mut self) {
+        if self.batch.is_empty() {
+            return;
+        }
+        let batch = std::mem::replace(&mut self.batch, Vec::with_capacity(SCAN_BATCH_SIZE));
+        self.tx.send(batch).unwrap();
+    }
 
-        Ok(output_thread.join().unwrap())
+    fn file_entry(&self, entry: Result<DirEntry, ignore::Error>) -> Option<FileEntry> {
+        // get DirEntry error
+        let Ok(entry) = entry.map_err(|e| log::warn!("ignored: {}", e)) else {
+            return None;
+        };
+
+        // strip prefix error
+        let Ok(path) = entry
+            .path()
+            .strip_prefix(self.root_dir)
+            .map_err(|e| log::error!("strip prefix error: {}", e))
+        else {
+            return None;
+        };
+        // root dir
+        if path.as_os_str().is_empty() {
+            return None;
+        }
+
+        // get file type error
+        let ft = entry.file_type()?;
+
+        // not supported file type
+        if !ft.is_file() && !ft.is_dir() {
+            log::warn!(
+                "ignored: not supported file type: {} \"{}\"",
+                format_filetype(&ft),
+                path.display()
+            );
+            return None;
+        }
+
+        let object_type = if ft.is_dir() {
+            ObjectType::Tree
+        } else {
+            ObjectType::File
+        };
+        Some(FileEntry::new(
+            object_type,
+            RelativePath::from(path),
+            entry.depth(),
+        ))
+    }
+}
+
+impl ParallelVisitor for ScanVisitor<'_> {
+    fn visit(&mut self, entry: Result<DirEntry, ignore::Error>) -> WalkState {
+        if let Some(entry) = self.file_entry(entry) {
+            self.batch.push(entry);
+            if self.batch.len() >= SCAN_BATCH_SIZE {
+                self.flush();
+            }
+        }
+        WalkState::Continue
+    }
+}
+
+impl Drop for ScanVisitor<'_> {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// Generates targets from any source of entries, such as a database query
+/// or an object store listing.
+///
+/// `source` is called once per build and its entries are consumed as they
+/// are produced. The root directory is added if the source does not list it.
+///
+/// ```no_run
+/// use mtl::builder::{Builder, FileEntry, IterTargetGenerator};
+/// use mtl::{Context, ObjectType};
+///
+/// let generator = IterTargetGenerator::new(|| {
+///     ["src", "src/lib.rs", "Cargo.toml"].into_iter().map(|path| {
+///         let mode = match path.ends_with(".rs") || path.ends_with(".toml") {
+///             true => ObjectType::File,
+///             false => ObjectType::Tree,
+///         };
+///         Ok(FileEntry::from_path(mode, path))
+///     })
+/// });
+/// let ctx = Context::new(".").unwrap();
+/// let root = Builder::new(Box::new(generator), false).build(&ctx).unwrap();
+/// println!("{}", root.object_id());
+/// ```
+pub struct IterTargetGenerator<F> {
+    source: F,
+}
+
+impl<F, I> IterTargetGenerator<F>
+where
+    F: Fn() -> I,
+    I: IntoIterator<Item = io::Result<FileEntry>>,
+{
+    pub fn new(source: F) -> Self {
+        Self { source }
+    }
+}
+
+impl<F, I> TargetGenerator for IterTargetGenerator<F>
+where
+    F: Fn() -> I,
+    I: IntoIterator<Item = io::Result<FileEntry>>,
+{
+    fn generate(&self, _ctx: &Context) -> anyhow::Result<TargetEntries, ReadContentError> {
+        let mut entries = TargetEntries::new();
+        let mut has_root = false;
+        for entry in (self.source)() {
+            let entry = entry?;
+            has_root |= entry.path.is_root();
+            entries.push_file_entry(entry);
+        }
+        if !has_root {
+            entries.push_file_entry(FileEntry::new(ObjectType::Tree, RelativePath::Root, 0));
+        }
+        Ok(entries)
     }
 }
 
 pub struct FileTargetGenerator {
     filter: Box<dyn Filter>,
     input: OsString,
+    null_separated: bool,
 }
 
 impl FileTargetGenerator {
     pub fn new(filter: Box<dyn Filter>, input: OsString) -> Self {
-        Self { filter, input }
+        Self {
+            filter,
+            input,
+            null_separated: false,
+        }
+    }
+
+    /// Read paths separated by NUL, as written by `find -print0`, instead of
+    /// lines. Paths are taken as is, without trimming whitespace.
+    pub fn with_null_separated(mut self, null_separated: bool) -> Self {
+        self.null_separated = null_separated;
+        self
+    }
+
+    // A line is a listed path, or "kind<TAB>path" where kind is "file",
+    // "dir" or "tree".
+    fn push_line(
+        &self,
+        entries: &mut TargetEntries,
+        dirs: &mut HashSet<String>,
+        line: &str,
+    ) -> Result<(), ReadContentError> {
+        let (is_dir, path) = match line.split_once('\t') {
+            Some(("file", path)) => (false, path),
+            Some(("dir" | "tree", path)) => (true, path),
+            Some((kind, path)) => {
+                log::warn!("ignored: unknown kind \"{}\": {}", kind, path);
+                return Ok(());
+            }
+            None => return self.push_listed(entries, dirs, line),
+        };
+        push_listed_path(self.filter.as_ref(), entries, dirs, path, is_dir)
+    }
+
+    // A path, with a trailing "/" for a directory.
+    fn push_listed(
+        &self,
+        entries: &mut TargetEntries,
+        dirs: &mut HashSet<String>,
+        path: &str,
+    ) -> Result<(), ReadContentError> {
+        let is_dir = path.ends_with('/') || (cfg!(windows) && path.ends_with('\\'));
+        push_listed_path(self.filter.as_ref(), entries, dirs, path, is_dir)
     }
 }
 
@@ -324,36 +605,77 @@ impl TargetGenerator for FileTargetGenerator {
         };
 
         let mut entries = TargetEntries::new();
-        for line in input {
-            let line = line?;
-            let relative_path = line.trim();
-
-            let is_dir = relative_path.ends_with('/');
-            let relative_path = relative_path.trim_start_matches("./").trim_end_matches('/');
-            let depth = relative_path.split('/').count();
-
-            let relative_path = PathBuf::from(relative_path);
-            if relative_path.is_absolute() {
-                return Err(ReadContentError::AbsolutePathNotSupported);
+        let mut dirs = HashSet::new();
+        if self.null_separated {
+            for path in input.split(b'\0') {
+                self.push_listed(&mut entries, &mut dirs, &String::from_utf8(path?)?)?;
             }
-
-            let relative_path = RelativePath::from(relative_path);
-            if !self.filter.path_matches(&relative_path) {
-                continue;
+        } else {
+            for line in input {
+                self.push_line(&mut entries, &mut dirs, line?.trim())?;
             }
-
-            let object_type = if is_dir {
-                ObjectType::Tree
-            } else {
-                ObjectType::File
-            };
-            entries.push_file_entry(FileEntry::new(object_type, relative_path, depth));
         }
         entries.push_file_entry(FileEntry::new(ObjectType::Tree, RelativePath::Root, 0));
         Ok(entries)
     }
 }
 
+// Add a path listed by the user, after its parents that were not listed
+// yet. `dirs` holds the directories added so far.
+fn push_listed_path(
+    filter: &dyn Filter,
+    entries: &mut TargetEntries,
+    dirs: &mut HashSet<String>,
+    path: &str,
+    is_dir: bool,
+) -> Result<(), ReadContentError> {
+    let path = normalize_listed_path(path, cfg!(windows))?;
+
+    // parents that are not listed are added, so that lists of files only,
+    // like the output of "git ls-files", can be built as they are
+    for (i, _) in path.match_indices('/') {
+        let parent = &path[..i];
+        if dirs.insert(parent.to_string()) {
+            if let Some(entry) = listed_entry(filter, parent, true)? {
+                entries.push_file_entry(entry);
+            }
+        }
+    }
+    if is_dir && !dirs.insert(path.clone()) {
+        return Ok(());
+    }
+    if let Some(entry) = listed_entry(filter, &path, is_dir)? {
+        entries.push_file_entry(entry);
+    }
+    Ok(())
+}
+
+// make an entry from a path listed by the user, or None if it is filtered out
+fn listed_entry(
+    filter: &dyn Filter,
+    relative_path: &str,
+    is_dir: bool,
+) -> Result<Option<FileEntry>, ReadContentError> {
+    let relative_path = normalize_listed_path(relative_path, cfg!(windows))?;
+    if relative_path.is_empty() {
+        // the root is always added
+        return Ok(None);
+    }
+    let depth = relative_path.split('/').count();
+
+    let relative_path = RelativePath::from(PathBuf::from(relative_path));
+    if !filter.entry_matches(&relative_path, is_dir) {
+        return Ok(None);
+    }
+
+    let object_type = if is_dir {
+        ObjectType::Tree
+    } else {
+        ObjectType::File
+    };
+    Ok(Some(FileEntry::new(object_type, relative_path, depth)))
+}
+
 pub struct BufReaderWrapper<R: BufRead> {
     reader: R,
     buf: Rc<String>,
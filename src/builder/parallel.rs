@@ -1,122 +1,270 @@
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::{self, Read};
+use std::io;
 use std::path::PathBuf;
+use std::sync::Mutex;
 
-use itertools::Itertools;
+use crossbeam_channel::{Receiver, Sender};
 use rayon::prelude::*;
 
 use crate::builder::{FileEntry, TargetEntries};
-use crate::progress::BuildProgressBar;
-use crate::{filesystem, Context, Object, ObjectID, ObjectType, RelativePath};
+use crate::progress::ProgressSink;
+use crate::{filesystem, tree_object, xattr, Context, Object, ObjectID, ObjectType, RelativePath};
 
+// objects handed to a writer in one put_batch call
+const WRITE_BATCH_SIZE: usize = 1024;
+// serialized trees waiting to be written before serialization blocks
+const WRITE_QUEUE_SIZE: usize = 4 * WRITE_BATCH_SIZE;
+const WRITER_THREADS: usize = 2;
+
+type SerializedObject = (ObjectID, Vec<u8>);
+
+/// Build the tree for `target_entries`, returning its root and the number
+/// of bytes of the files hashed.
+///
+/// Hashing and tree serialization run on the rayon pool, while persisting the
+/// serialized trees runs on dedicated writer threads fed by a bounded channel,
+/// so slow object store writes overlap with the remaining work.
 pub(crate) fn build(
     ctx: &Context,
-    pb: &BuildProgressBar,
+    pb: &dyn ProgressSink,
+    target_entries: TargetEntries,
+) -> io::Result<(Object, u64)> {
+    let (tx, rx) = crossbeam_channel::bounded::<SerializedObject>(WRITE_QUEUE_SIZE);
+
+    std::thread::scope(|s| {
+        let writers = (0..WRITER_THREADS)
+            .map(|_| {
+                let rx = rx.clone();
+                s.spawn(move || write_objects(ctx, rx))
+            })
+            .collect::<Vec<_>>();
+        drop(rx);
+
+        let root = build_trees(ctx, pb, target_entries, &tx);
+        drop(tx);
+
+        // a writer error explains why sending may have failed, so report it first
+        for writer in writers {
+            writer.join().unwrap()?;
+        }
+        root
+    })
+}
+
+fn build_trees(
+    ctx: &Context,
+    pb: &dyn ProgressSink,
     target_entries: TargetEntries,
-) -> io::Result<Object> {
-    let max_depth = target_entries.max_depth;
-    let (files, mut dirs) = target_entries
+    tx: &Sender<SerializedObject>,
+) -> io::Result<(Object, u64)> {
+    let (files, dirs) = target_entries
         .files
         .into_iter()
         .partition::<Vec<_>, _>(|entry| matches!(entry.mode, ObjectType::File));
 
-    let mut objects_per_dir = files
+    let scheduler = Scheduler::new(pb, tx, &files, &dirs);
+    let empty_dirs = dirs
+        .iter()
+        .filter(|entry| scheduler.is_ready(&entry.path))
+        .collect::<Vec<_>>();
+
+    empty_dirs
         .into_par_iter()
-        .fold(
-            HashMap::new,
-            |mut acc: HashMap<RelativePath, Vec<_>>, entry| {
-                let parent = entry.path.parent();
-                let object =
-                    process_file_content(ctx, &entry).expect("failed to process file content");
-                acc.entry(parent).or_default().push(object);
-                pb.inc_file(1);
-                acc
-            },
-        )
-        .reduce(HashMap::new, merge_hashmap);
-
-    for i in (1..max_depth).rev() {
-        let (target, rest) = dirs
-            .into_iter()
-            .partition::<Vec<_>, _>(|entry| entry.depth == i);
-        dirs = rest;
-
-        let tmp = target
-            .into_par_iter()
-            .fold(
-                HashMap::new,
-                |mut acc: HashMap<RelativePath, Vec<_>>, entry| {
-                    let parent = entry.path.parent();
-                    if let Some(object) =
-                        process_tree_content(ctx, &objects_per_dir, &entry).unwrap()
-                    {
-                        acc.entry(parent).or_default().push(object);
-                    }
-                    pb.inc_dir(1);
-
-                    acc
-                },
-            )
-            .reduce(HashMap::new, merge_hashmap);
-        objects_per_dir = merge_hashmap(objects_per_dir, tmp);
-    }
+        .try_for_each(|entry| scheduler.complete_dir(&entry.path))?;
+    let bytes = files
+        .into_par_iter()
+        .map(|entry| -> io::Result<u64> {
+            if !scheduler.has_dir(&entry.path.parent()) {
+                return Ok(0);
+            }
+            let (object, len) = process_file_content(ctx, &entry)?;
+            pb.inc_file(1);
+            scheduler.finish_child(&entry.path.parent(), Some(object))?;
+            Ok(len)
+        })
+        .try_reduce(|| 0, |a, b| Ok(a + b))?;
 
-    let root = RelativePath::Root;
-    let mut objects = objects_per_dir.remove(&root).unwrap();
-    objects.par_sort_unstable();
+    Ok((scheduler.into_root()?, bytes))
+}
 
-    let object_id = ctx.write_tree_contents(&objects)?;
-    Ok(Object::new_tree(object_id, PathBuf::from("")))
+struct DirState {
+    // children that are not finished yet
+    pending: usize,
+    objects: Vec<Object>,
 }
 
-fn merge_hashmap<K: std::hash::Hash + Eq + Clone, V: Clone>(
-    map1: HashMap<K, Vec<V>>,
-    map2: HashMap<K, Vec<V>>,
-) -> HashMap<K, Vec<V>> {
-    map2.into_iter().fold(map1, |mut acc, (k, vs)| {
-        acc.entry(k).or_default().extend(vs);
-        acc
-    })
+/// Schedules each directory as soon as all of its children are done.
+///
+/// Every directory counts its direct children; finishing a child decrements
+/// the counter of its parent and the thread that finishes the last child
+/// serializes the directory, so no worker waits for a whole depth level.
+struct Scheduler<'a> {
+    pb: &'a dyn ProgressSink,
+    tx: &'a Sender<SerializedObject>,
+    dirs: HashMap<RelativePath, Mutex<DirState>>,
+    root: Mutex<Option<Object>>,
 }
 
-fn process_tree_content(
-    ctx: &Context,
-    map: &HashMap<RelativePath, Vec<Object>>,
-    entry: &FileEntry,
-) -> io::Result<Option<Object>> {
-    let objects = match map.get(&entry.path) {
-        Some(objects) => objects.iter().sorted().collect::<Vec<_>>(),
-        None => return Ok(None), // empty dir
-    };
+impl<'a> Scheduler<'a> {
+    fn new(
+        pb: &'a dyn ProgressSink,
+        tx: &'a Sender<SerializedObject>,
+        files: &[FileEntry],
+        dirs: &[FileEntry],
+    ) -> Self {
+        let mut states = dirs
+            .iter()
+            .map(|entry| {
+                let state = DirState {
+                    pending: 0,
+                    objects: Vec::new(),
+                };
+                (entry.path.clone(), state)
+            })
+            .collect::<HashMap<_, _>>();
+
+        for entry in files.iter().chain(dirs.iter()) {
+            if entry.path.is_root() {
+                continue;
+            }
+            match states.get_mut(&entry.path.parent()) {
+                Some(parent) => parent.pending += 1,
+                None => log::warn!("ignored: parent directory not listed: {}", entry.path),
+            }
+        }
+
+        Self {
+            pb,
+            tx,
+            dirs: states
+                .into_iter()
+                .map(|(path, state)| (path, Mutex::new(state)))
+                .collect(),
+            root: Mutex::new(None),
+        }
+    }
+
+    fn has_dir(&self, path: &RelativePath) -> bool {
+        self.dirs.contains_key(path)
+    }
+
+    fn is_ready(&self, path: &RelativePath) -> bool {
+        self.dirs
+            .get(path)
+            .is_some_and(|state| state.lock().unwrap().pending == 0)
+    }
+
+    fn finish_child(&self, parent: &RelativePath, object: Option<Object>) -> io::Result<()> {
+        let Some(state) = self.dirs.get(parent) else {
+            return Ok(());
+        };
+
+        let ready = {
+            let mut state = state.lock().unwrap();
+            if let Some(object) = object {
+                state.objects.push(object);
+            }
+            state.pending -= 1;
+            state.pending == 0
+        };
+        if ready {
+            self.complete_dir(parent)?;
+        }
+        Ok(())
+    }
+
+    fn complete_dir(&self, path: &RelativePath) -> io::Result<()> {
+        let mut objects = std::mem::take(&mut self.dirs[path].lock().unwrap().objects);
 
-    match &entry.path {
-        RelativePath::Path(path) => {
-            let object_id = ctx.write_tree_contents(&objects)?;
-            Ok(Some(Object::new_tree(object_id, path)))
+        if path.is_root() {
+            objects.sort_unstable();
+            let (object_id, contents) = tree_object(&objects)?;
+            self.send(object_id, contents)?;
+            *self.root.lock().unwrap() = Some(Object::new_tree(object_id, PathBuf::from("")));
+            return Ok(());
         }
-        RelativePath::Root => {
-            let object_id = ctx.write_tree_contents(&objects)?;
-            Ok(Some(Object::new_tree(object_id, PathBuf::from(""))))
+
+        self.pb.inc_dir(1);
+        let object = match objects.is_empty() {
+            true => None, // empty dir
+            false => {
+                objects.sort_unstable();
+                let (object_id, contents) = tree_object(&objects)?;
+                self.send(object_id, contents)?;
+                Some(Object::new_tree(object_id, path.as_path()))
+            }
+        };
+        self.finish_child(&path.parent(), object)
+    }
+
+    fn send(&self, object_id: ObjectID, contents: Vec<u8>) -> io::Result<()> {
+        self.tx
+            .send((object_id, contents))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "object writer stopped"))
+    }
+
+    fn into_root(self) -> io::Result<Object> {
+        self.root.into_inner().unwrap().ok_or(io::Error::new(
+            io::ErrorKind::NotFound,
+            "root directory is not listed",
+        ))
+    }
+}
+
+fn write_objects(ctx: &Context, rx: Receiver<SerializedObject>) -> io::Result<()> {
+    let mut batch = Vec::with_capacity(WRITE_BATCH_SIZE);
+    for object in rx {
+        batch.push(object);
+        if batch.len() >= WRITE_BATCH_SIZE {
+            ctx.write_objects(&batch)?;
+            batch.clear();
         }
     }
+    if !batch.is_empty() {
+        ctx.write_objects(&batch)?;
+    }
+    Ok(())
+}
+
+// Hash a whole file through a memory map. The pages are read ahead in order
+// and dropped from the page cache afterwards, so that a large scan does not
+// push out everything else.
+fn hash_mapped(file: &File, len: u64) -> io::Result<ObjectID> {
+    filesystem::fadvise(file, filesystem::Advise::Sequential, None, None)?;
+    let map = unsafe { memmap::MmapOptions::new().len(len as usize).map(file)? };
+    let object_id = ObjectID::from_contents(&map[..]);
+    drop(map);
+    filesystem::fadvise(file, filesystem::Advise::DontNeed, None, None)?;
+    Ok(object_id)
 }
 
-fn process_file_content(ctx: &Context, entry: &FileEntry) -> io::Result<Object> {
+fn process_file_content(ctx: &Context, entry: &FileEntry) -> io::Result<(Object, u64)> {
     let path = ctx.root_dir().join(entry.path.as_path());
 
-    let mut file = File::open(path)?;
-    let mut contents = Vec::new();
-    file.read_to_end(&mut contents)?;
-    if ctx.drop_cache {
-        filesystem::fadvise(&file, filesystem::Advise::DontNeed, None, None)?;
-    }
+    let mut file = File::open(&path)?;
+    let len = file.metadata()?.len();
+    let object_id = match ctx.mmap_threshold {
+        // an empty file cannot be mapped
+        Some(threshold) if len >= threshold && len > 0 => hash_mapped(&file, len)?,
+        _ => {
+            let object_id = ObjectID::from_reader(&mut file)?;
+            if ctx.drop_cache {
+                filesystem::fadvise(&file, filesystem::Advise::DontNeed, None, None)?;
+            }
+            object_id
+        }
+    };
+
+    let object_id = match &ctx.xattrs {
+        Some(selector) => xattr::fingerprint(object_id, &selector.read(&path)?),
+        None => object_id,
+    };
 
-    let object_id = ObjectID::from_contents(&contents);
     let file_name = entry.path.file_name().ok_or(io::Error::new(
         io::ErrorKind::NotFound,
         "failed to get file_name",
     ))?;
 
-    Ok(Object::new_file(object_id, file_name))
+    Ok((Object::new_file(object_id, file_name), len))
 }
@@ -0,0 +1,146 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use rusqlite::{Connection, OpenFlags};
+
+use crate::builder::{push_listed_path, FileEntry, TargetEntries, TargetGenerator};
+use crate::filter::Filter;
+use crate::{Context, ObjectType, ReadContentError, RelativePath};
+
+/// Query used when none is given: every row of the `files` table.
+pub const DEFAULT_SQLITE_QUERY: &str = "SELECT path FROM files";
+
+/// Generates targets from the rows of a query against a SQLite database,
+/// for file catalogs that are kept in a database instead of being walked.
+///
+/// The first column is the path relative to the root directory. An optional
+/// second column gives the kind ("file", "tree" or "dir"); without it, paths
+/// ending with "/" are directories, as in an input file. Parents that are
+/// not listed are added, as they are for an input file.
+///
+/// The database is a path or a `file:` URI, so that e.g.
+/// `file:catalog.db?immutable=1` can be given.
+pub struct SqliteTargetGenerator {
+    filter: Box<dyn Filter>,
+    database: PathBuf,
+    query: String,
+}
+
+impl SqliteTargetGenerator {
+    pub fn new<P: Into<PathBuf>, S: Into<String>>(
+        filter: Box<dyn Filter>,
+        database: P,
+        query: S,
+    ) -> Self {
+        Self {
+            filter,
+            database: database.into(),
+            query: query.into(),
+        }
+    }
+}
+
+impl TargetGenerator for SqliteTargetGenerator {
+    fn generate(&self, _ctx: &Context) -> anyhow::Result<TargetEntries, ReadContentError> {
+        let flags = OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI;
+        let conn = Connection::open_with_flags(&self.database, flags)?;
+        let mut stmt = conn.prepare(&self.query)?;
+        let has_kind = stmt.column_count() > 1;
+
+        let mut entries = TargetEntries::new();
+        let mut dirs = HashSet::new();
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let path: String = row.get(0)?;
+            let is_dir = match has_kind {
+                true => match row.get::<_, String>(1)?.as_str() {
+                    "file" => false,
+                    "tree" | "dir" => true,
+                    kind => {
+                        log::warn!("ignored: unknown kind \"{}\": {}", kind, path);
+                        continue;
+                    }
+                },
+                false => path.ends_with('/'),
+            };
+
+            push_listed_path(self.filter.as_ref(), &mut entries, &mut dirs, &path, is_dir)?;
+        }
+        entries.push_file_entry(FileEntry::new(ObjectType::Tree, RelativePath::Root, 0));
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::MatchAllFilter;
+
+    #[test]
+    fn test_sqlite_target_generator() {
+        let database = std::env::temp_dir().join(format!("mtl-sqlite-{}.db", std::process::id()));
+        let conn = Connection::open(&database).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE files (path TEXT, kind TEXT);
+             INSERT INTO files VALUES ('dir1', 'tree'), ('dir1/file1', 'file'), ('file2', 'file');",
+        )
+        .unwrap();
+        drop(conn);
+
+        let ctx = Context::in_memory();
+        let filter = Box::new(MatchAllFilter::new(PathBuf::new()));
+        let generator =
+            SqliteTargetGenerator::new(filter, &database, "SELECT path, kind FROM files");
+        let entries = generator.generate(&ctx).unwrap();
+        std::fs::remove_file(&database).unwrap();
+
+        let entries = entries
+            .iter()
+            .map(|entry| (entry.mode.clone(), entry.path.to_string(), entry.depth))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            vec![
+                (ObjectType::Tree, "dir1".to_string(), 1),
+                (ObjectType::File, "dir1/file1".to_string(), 2),
+                (ObjectType::File, "file2".to_string(), 1),
+                (ObjectType::Tree, "".to_string(), 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sqlite_unlisted_parents() {
+        let database =
+            std::env::temp_dir().join(format!("mtl-sqlite-parents-{}.db", std::process::id()));
+        let conn = Connection::open(&database).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE files (path TEXT);
+             INSERT INTO files VALUES ('a/b/f'), ('top'), ('a/g'), ('a/b/');",
+        )
+        .unwrap();
+        drop(conn);
+
+        let ctx = Context::in_memory();
+        let filter = Box::new(MatchAllFilter::new(PathBuf::new()));
+        let generator = SqliteTargetGenerator::new(filter, &database, DEFAULT_SQLITE_QUERY);
+        let entries = generator.generate(&ctx).unwrap();
+        std::fs::remove_file(&database).unwrap();
+
+        let entries = entries
+            .iter()
+            .map(|entry| (entry.mode.clone(), entry.path.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            vec![
+                (ObjectType::Tree, "a".to_string()),
+                (ObjectType::Tree, "a/b".to_string()),
+                (ObjectType::File, "a/b/f".to_string()),
+                (ObjectType::File, "top".to_string()),
+                (ObjectType::File, "a/g".to_string()),
+                (ObjectType::Tree, "".to_string()),
+            ]
+        );
+    }
+}
@@ -1,21 +1,48 @@
+mod audit;
+mod bench;
+mod bundle;
+mod config;
+mod git;
 pub mod local;
+mod plumbing;
 mod r#ref;
+mod remote;
+mod selftest;
+mod stats;
 mod tool;
 
-use std::collections::HashMap;
-use std::io::BufWriter;
+pub use git::{ExportGitCommand, ImportGitCommand};
+pub use selftest::SelftestCommand;
+pub use stats::StatsCommand;
+
+use std::collections::{hash_map, HashMap, HashSet};
+use std::ffi::OsString;
+use std::io::{BufRead, BufWriter, Write};
 use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::{fs, io};
 
+use clap::builder::NonEmptyStringValueParser;
 use clap::{Args, Subcommand};
 use console::{style, Style};
+use globset::{Glob, GlobMatcher};
 use itertools::Itertools;
-use redb::Database;
+use redb::{Database, ReadableTable};
+use regex::Regex;
 use scopeguard::defer;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
 use similar::{self, Algorithm, ChangeTag, DiffOp};
 
+use crate::audit::AuditEntry;
+use crate::hooks::Hook;
+use crate::output::{self, OutputFormat};
+use crate::patch::{self, PatchEntry};
+use crate::retention::RetentionPolicy;
+use crate::tree::{self, TreeBuilder};
 use crate::{
-    file_size, Context, Object, ObjectExpr, ObjectID, ObjectType, ReadContentError, RelativePath,
+    datetime, delta, file_size, header, store, Context, Object, ObjectExpr, ObjectID, ObjectType,
+    ReadContentError, RelativePath, MIN_OBJECT_ID_PREFIX, OBJECT_ID_LEN, PACKED_DELTAS_TABLE,
     PACKED_OBJECTS_TABLE,
 };
 
@@ -29,6 +56,9 @@ pub enum LocalCommand {
 
     /// List target files
     List(local::List),
+
+    /// Show the files changed since HEAD was built
+    Status(local::Status),
 }
 
 impl LocalCommand {
@@ -37,6 +67,108 @@ impl LocalCommand {
             LocalCommand::Build(cmd) => cmd.run(ctx),
             LocalCommand::Update(cmd) => cmd.run(ctx),
             LocalCommand::List(cmd) => cmd.run(ctx),
+            LocalCommand::Status(cmd) => cmd.run(ctx),
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum BundleCommand {
+    /// Write the objects reachable from some roots, and their refs, to a file
+    Create(bundle::Create),
+
+    /// Import the objects and refs of a bundle
+    Unbundle(bundle::Unbundle),
+}
+
+impl BundleCommand {
+    pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+        match self {
+            BundleCommand::Create(cmd) => cmd.run(ctx),
+            BundleCommand::Unbundle(cmd) => cmd.run(ctx),
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum AuditCommand {
+    /// Show the audit log
+    Show(audit::Show),
+}
+
+impl AuditCommand {
+    pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+        match self {
+            AuditCommand::Show(cmd) => cmd.run(ctx),
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Print the value of a key, from the repository's config file or else
+    /// the user's
+    #[clap(verbatim_doc_comment)]
+    Get(config::Get),
+
+    /// Set a key in the repository's config file, or the user's with --user
+    Set(config::Set),
+}
+
+impl ConfigCommand {
+    pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+        match self {
+            ConfigCommand::Get(cmd) => cmd.run(ctx),
+            ConfigCommand::Set(cmd) => cmd.run(ctx),
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum RemoteCommand {
+    /// Save the URL of a remote under a name, in the repository's config file
+    Add(remote::Add),
+
+    /// Print the remotes, as "name<TAB>url" lines
+    List(remote::List),
+
+    /// Forget a remote of the repository's config file
+    Remove(remote::Remove),
+}
+
+impl RemoteCommand {
+    pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+        match self {
+            RemoteCommand::Add(cmd) => cmd.run(ctx),
+            RemoteCommand::List(cmd) => cmd.run(ctx),
+            RemoteCommand::Remove(cmd) => cmd.run(ctx),
+        }
+    }
+}
+
+/// Low-level commands with fixed formats, for scripts
+#[derive(Subcommand)]
+pub enum PlumbingCommand {
+    /// Set or delete a ref, optionally only if it has an expected value
+    UpdateRef(plumbing::UpdateRef),
+
+    /// Print the object ID of file contents, optionally storing them
+    HashObject(plumbing::HashObject),
+
+    /// Build a tree from a list of entries on standard input
+    WriteTree(plumbing::WriteTree),
+
+    /// Print the files of a tree in the format write-tree reads
+    ReadTree(plumbing::ReadTree),
+}
+
+impl PlumbingCommand {
+    pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+        match self {
+            PlumbingCommand::UpdateRef(cmd) => cmd.run(ctx),
+            PlumbingCommand::HashObject(cmd) => cmd.run(ctx),
+            PlumbingCommand::WriteTree(cmd) => cmd.run(ctx),
+            PlumbingCommand::ReadTree(cmd) => cmd.run(ctx),
         }
     }
 }
@@ -68,13 +200,25 @@ pub struct CatObjectCommand {
     /// Object ID to print
     #[clap(value_name = "object-id")]
     object_id: ObjectExpr,
+
+    /// Print the kind of the object instead of its contents: tree, file, or
+    /// unknown for objects written before kinds were recorded
+    #[clap(short = 't', long = "type", default_value_t = false)]
+    show_type: bool,
 }
 
 impl CatObjectCommand {
     pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
         let object_id = self.object_id.resolve(&ctx)?;
 
-        let contents = ctx.read_object(&object_id)?;
+        let (kind, contents) = ctx.store().get_typed(&object_id)?;
+        if self.show_type {
+            match kind {
+                Some(kind) => println!("{}", kind),
+                None => println!("unknown"),
+            }
+            return Ok(());
+        }
         let contents = String::from_utf8_lossy(&contents);
         print!("{}", contents);
 
@@ -108,57 +252,133 @@ pub struct DiffCommand {
     /// Maximum depth to print
     #[clap(long, value_name = "max-depth")]
     max_depth: Option<usize>,
+
+    /// Print nothing, only report through the exit status
+    #[clap(short, long)]
+    quiet: bool,
+
+    /// Print a patch that `mtl apply` can replay on another tree
+    #[clap(long, conflicts_with_all = ["max_depth", "quiet"])]
+    emit_patch: bool,
+
+    /// Only report directories whose IDs differ, leaving files out
+    #[clap(long, conflicts_with = "emit_patch")]
+    dirs_only: bool,
+
+    /// Print at most this many lines
+    #[clap(long, value_name = "N", conflicts_with = "emit_patch")]
+    limit: Option<usize>,
+
+    /// Leave out this many lines before printing
+    #[clap(
+        long,
+        value_name = "M",
+        default_value_t = 0,
+        conflicts_with = "emit_patch"
+    )]
+    skip: usize,
+}
+
+/// Window of diff lines to print, for paging through large diffs.
+struct Page {
+    skip: usize,
+    limit: Option<usize>,
+}
+
+impl Page {
+    /// Returns whether the next line falls inside the page.
+    fn next(&mut self) -> bool {
+        if self.skip > 0 {
+            self.skip -= 1;
+            return false;
+        }
+        match &mut self.limit {
+            Some(0) => false,
+            Some(limit) => {
+                *limit -= 1;
+                true
+            }
+            None => true,
+        }
+    }
+
+    /// Whether the page is full, so the rest of the trees needn't be read.
+    fn is_full(&self) -> bool {
+        self.limit == Some(0)
+    }
 }
 
 impl DiffCommand {
-    pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+    /// Returns whether the two trees differ.
+    pub fn run(&self, ctx: Context) -> anyhow::Result<bool> {
         let object_a = self.object_a.resolve(&ctx)?;
         let object_b = self.object_b.resolve(&ctx)?;
 
-        Self::print_diff(&ctx, &object_a, &object_b, self.max_depth)?;
+        // trees are content addressed, so equal IDs mean equal contents
+        let differs = object_a != object_b;
+        if self.emit_patch {
+            let stdout = io::stdout();
+            let mut stdout = BufWriter::new(stdout.lock());
+            for entry in patch::diff(&ctx, &object_a, &object_b)? {
+                writeln!(stdout, "{}", entry)?;
+            }
+            stdout.flush()?;
+        } else if !self.quiet {
+            let mut page = Page {
+                skip: self.skip,
+                limit: self.limit,
+            };
+            self.print_diff(&ctx, &object_a, &object_b, &mut page)?;
+        }
 
-        Ok(())
+        Ok(differs)
     }
 
     fn print_diff(
+        &self,
         ctx: &Context,
         object_a_id: &ObjectID,
         object_b_id: &ObjectID,
-        max_depth: Option<usize>,
+        page: &mut Page,
     ) -> anyhow::Result<()> {
         let object_a = Object::new_tree(*object_a_id, ".");
         let object_b = Object::new_tree(*object_b_id, ".");
-        Self::print_difference(&RelativePath::Root, Some(&object_a), Some(&object_b))?;
-        Self::inner_print_diff(
+        Self::print_difference(
             ctx,
+            page,
             &RelativePath::Root,
-            object_a_id,
-            object_b_id,
-            max_depth,
-            0,
-        )
+            Some(&object_a),
+            Some(&object_b),
+        )?;
+        self.inner_print_diff(ctx, &RelativePath::Root, object_a_id, object_b_id, page, 0)
     }
 
     fn inner_print_diff<P: AsRef<Path>>(
+        &self,
         ctx: &Context,
         parent: P,
         object_a: &ObjectID,
         object_b: &ObjectID,
-        max_depth: Option<usize>,
+        page: &mut Page,
         depth: usize,
     ) -> anyhow::Result<()> {
-        if object_a == object_b {
+        if object_a == object_b || page.is_full() {
             return Ok(());
         }
-        if let Some(max_depth) = max_depth {
+        if let Some(max_depth) = self.max_depth {
             if depth >= max_depth {
                 return Ok(());
             }
         }
 
         let parent = parent.as_ref();
-        let tree_a = ctx.read_tree_contents(object_a)?;
-        let tree_b = ctx.read_tree_contents(object_b)?;
+        let mut tree_a = ctx.read_tree_contents(object_a)?;
+        let mut tree_b = ctx.read_tree_contents(object_b)?;
+        if self.dirs_only {
+            // files never get compared, only the trees to descend into
+            tree_a.retain(|object| object.is_tree());
+            tree_b.retain(|object| object.is_tree());
+        }
 
         let diff = similar::capture_diff_slices(Algorithm::Myers, &tree_a, &tree_b);
         for op in diff {
@@ -166,12 +386,12 @@ impl DiffCommand {
                 DiffOp::Equal { .. } => continue,
                 DiffOp::Delete { .. } => {
                     for change in op.iter_changes(&tree_a, &tree_b) {
-                        Self::print_difference(parent, Some(change.value_ref()), None)?;
+                        Self::print_difference(ctx, page, parent, Some(change.value_ref()), None)?;
                     }
                 }
                 DiffOp::Insert { .. } => {
                     for change in op.iter_changes(&tree_a, &tree_b) {
-                        Self::print_difference(parent, None, Some(change.value_ref()))?;
+                        Self::print_difference(ctx, page, parent, None, Some(change.value_ref()))?;
                     }
                 }
 
@@ -205,17 +425,23 @@ impl DiffCommand {
                             }
                         }
 
-                        Self::print_difference(parent, object_a.as_ref(), object_b.as_ref())?;
+                        Self::print_difference(
+                            ctx,
+                            page,
+                            parent,
+                            object_a.as_ref(),
+                            object_b.as_ref(),
+                        )?;
                         match (object_a, object_b) {
                             (Some(object_a), Some(object_b))
                                 if object_a.is_tree() && object_b.is_tree() =>
                             {
-                                Self::inner_print_diff(
+                                self.inner_print_diff(
                                     ctx,
                                     parent.join(&file_name),
                                     &object_a.object_id,
                                     &object_b.object_id,
-                                    max_depth,
+                                    page,
                                     depth + 1,
                                 )?;
                             }
@@ -230,11 +456,34 @@ impl DiffCommand {
     }
 
     fn print_difference<P: AsRef<Path>>(
+        ctx: &Context,
+        page: &mut Page,
         path: P,
         object_a: Option<&Object>,
         object_b: Option<&Object>,
     ) -> io::Result<()> {
+        if !page.next() {
+            return Ok(());
+        }
         let path = path.as_ref();
+        if ctx.output_format() == OutputFormat::Json {
+            let Some(object) = object_a.or(object_b) else {
+                return Ok(());
+            };
+            let side = |object: Option<&Object>| {
+                object.map(|object| {
+                    json!({
+                        "type": object.object_type.to_string(),
+                        "id": object.object_id.to_string(),
+                    })
+                })
+            };
+            return output::print_json(&json!({
+                "path": ctx.render_path(&path.join(&object.file_path)).to_string_lossy(),
+                "a": side(object_a),
+                "b": side(object_b),
+            }));
+        }
         match (object_a, object_b) {
             (Some(object_a), Some(object_b)) => {
                 let (object_type_style_a, object_type_style_b) =
@@ -250,38 +499,41 @@ impl DiffCommand {
                         (Style::new().red(), Style::new().green())
                     };
                 let path = path.join(&object_a.file_path);
+                let path = ctx.render_path(&path);
                 println!(
                     "{}/{} {}/{}\t{}/{}\t{}",
                     style("-").red(),
                     style("+").green(),
                     object_type_style_a.apply_to(&object_a.object_type),
                     object_type_style_b.apply_to(&object_b.object_type),
-                    object_id_style_a.apply_to(&object_a.object_id),
-                    object_id_style_b.apply_to(&object_b.object_id),
+                    object_id_style_a.apply_to(ctx.abbrev(&object_a.object_id)),
+                    object_id_style_b.apply_to(ctx.abbrev(&object_b.object_id)),
                     path.display(),
                 );
             }
             (Some(object_a), None) => {
                 let path = path.join(&object_a.file_path);
+                let path = ctx.render_path(&path);
                 println!(
                     "{}/  {}/{}\t{}/{}\t{}",
                     style("-").red(),
                     style(&object_a.object_type).red(),
                     " ".repeat(4),
-                    style(&object_a.object_id).red(),
-                    " ".repeat(16),
+                    style(ctx.abbrev(&object_a.object_id)).red(),
+                    " ".repeat(ctx.id_width()),
                     style(path.display()).red(),
                 );
             }
             (None, Some(object_b)) => {
                 let path = path.join(&object_b.file_path);
+                let path = ctx.render_path(&path);
                 println!(
                     " /{} {}/{}\t{}/{}\t{}",
                     style("+").green(),
                     " ".repeat(4),
                     style(&object_b.object_type).green(),
-                    " ".repeat(16),
-                    style(&object_b.object_id).green(),
+                    " ".repeat(ctx.id_width()),
+                    style(ctx.abbrev(&object_b.object_id)).green(),
                     style(path.display()).green(),
                 );
             }
@@ -292,10 +544,81 @@ impl DiffCommand {
 }
 
 #[derive(Args, Debug)]
-pub struct PackCommand {}
+pub struct ApplyCommand {
+    /// Patch written by `mtl diff --emit-patch`, or "-" for standard input
+    #[clap(value_name = "patch")]
+    patch: OsString,
+
+    /// Tree to apply the patch to
+    #[clap(value_name = "object")]
+    base: ObjectExpr,
+}
+
+impl ApplyCommand {
+    pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+        let _lock = ctx.lock()?;
+        let base = self.base.resolve(&ctx)?;
+
+        let input: Box<dyn BufRead> = match self.patch.to_str() {
+            Some("-") => Box::new(io::stdin().lock()),
+            _ => Box::new(io::BufReader::new(fs::File::open(&self.patch)?)),
+        };
+        let mut entries = Vec::new();
+        for (n, line) in input.lines().enumerate() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            let entry: PatchEntry = line
+                .parse()
+                .map_err(|e| anyhow::anyhow!("line {}: {}", n + 1, e))?;
+            entries.push(entry);
+        }
+        println!("{}", patch::apply(&ctx, &base, &entries)?);
+
+        Ok(())
+    }
+}
+
+#[derive(Args, Debug)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct PackCommand {
+    /// Add loose objects to the existing pack instead of rewriting it
+    #[clap(long, default_value_t = false)]
+    incremental: bool,
+
+    /// Store trees as deltas against an earlier tree at the same path
+    /// when that is much smaller. [config: pack.delta]
+    #[clap(long, default_value_t = false, conflicts_with = "incremental")]
+    delta: bool,
+
+    #[command(subcommand)]
+    action: Option<PackAction>,
+}
+
+#[derive(Subcommand, Debug)]
+enum PackAction {
+    /// Replace an unreadable pack with one made from the loose objects.
+    /// The old pack is kept next to the new one.
+    #[clap(verbatim_doc_comment)]
+    Rebuild,
+}
 
 impl PackCommand {
     pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+        let _lock = ctx.lock()?;
+        ctx.hooks().run(Hook::PrePack, &[], &[])?;
+        let rebuild = matches!(self.action, Some(PackAction::Rebuild));
+        if rebuild {
+            Self::set_aside_pack(&ctx)?;
+        } else {
+            // packing now would drop the objects that are only in the pack
+            ctx.check_pack()?;
+        }
+        if self.incremental {
+            return Self::run_incremental(ctx);
+        }
+
         let pack_dir = ctx.pack_dir();
         fs::create_dir_all(&pack_dir)?;
 
@@ -306,14 +629,43 @@ impl PackCommand {
             }
         }
 
+        let delta = self.delta || ctx.config().delta.unwrap_or(false);
+        let bases = if delta {
+            Self::delta_bases(&ctx)?
+        } else {
+            HashMap::new()
+        };
+
         let db = Database::create(tmp_file.clone())?;
         let write_txn = db.begin_write()?;
+        let mut packed = 0;
+        let mut deltas = 0;
         {
             let mut table = write_txn.open_table(PACKED_OBJECTS_TABLE)?;
+            let mut delta_table = write_txn.open_table(PACKED_DELTAS_TABLE)?;
 
             for object_id in ctx.list_object_ids()? {
-                let content = ctx.read_object(&object_id)?;
-                table.insert(object_id, content)?;
+                let (kind, content) = ctx.store().get_typed(&object_id)?;
+                packed += 1;
+                match bases.get(&object_id) {
+                    Some(base_id) => {
+                        // the base's loose file is gone once it has been packed
+                        let base = match table.get(base_id)? {
+                            Some(base) => header::decode_vec(base.value())?.1,
+                            None => ctx.read_object(base_id)?,
+                        };
+                        let delta = delta::encode(&base, &content);
+                        if delta.len() < content.len() / 2 {
+                            delta_table.insert(object_id, (*base_id, delta))?;
+                            deltas += 1;
+                        } else {
+                            table.insert(object_id, header::restore(kind, content))?;
+                        }
+                    }
+                    None => {
+                        table.insert(object_id, header::restore(kind, content))?;
+                    }
+                }
 
                 let object_path = ctx.object_file(&object_id);
                 if object_path.exists() {
@@ -322,9 +674,165 @@ impl PackCommand {
             }
         }
         write_txn.commit()?;
+        drop(db);
 
-        let dirs = fs::read_dir(ctx.objects_dir())?;
-        for dir in dirs {
+        Self::remove_object_dirs(&ctx.objects_dir())?;
+        let pack_file = ctx.pack_file();
+        let audit_file = ctx.audit_file();
+        // the new pack is read with the same settings once it is in place
+        let settings = ctx.for_store(Box::new(store::NullObjectStore));
+        drop(ctx);
+
+        fs::rename(&tmp_file, pack_file)?;
+
+        if delta {
+            println!("Packed {} objects, {} as deltas", packed, deltas);
+        }
+        let operation = if rebuild { "pack-rebuild" } else { "pack" };
+        crate::audit::append(audit_file, &AuditEntry::new(operation).objects(packed))?;
+
+        if rebuild {
+            println!("Packed {} objects", packed);
+            let missing = Self::missing_trees(&settings.reopen()?)?;
+            println!(
+                "Missing trees reachable from HEAD and the refs: {}",
+                missing.len()
+            );
+            for object_id in missing {
+                println!("  {}", object_id);
+            }
+        }
+        Ok(())
+    }
+
+    // rename an unreadable pack out of the way, keeping it for inspection
+    fn set_aside_pack(ctx: &Context) -> anyhow::Result<()> {
+        let Some(reason) = ctx.pack_error() else {
+            return Ok(());
+        };
+        let secs = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let kept = ctx.pack_dir().join(format!("packed.redb.broken-{}", secs));
+        fs::rename(ctx.pack_file(), &kept)?;
+        println!(
+            "Moved the unreadable pack ({}) to {}",
+            reason,
+            kept.display()
+        );
+        Ok(())
+    }
+
+    // trees below HEAD and the refs that can't be read, in the order found
+    fn missing_trees(ctx: &Context) -> anyhow::Result<Vec<ObjectID>> {
+        let mut roots = ctx.read_head().into_iter().collect::<Vec<_>>();
+        for object_ref in ctx.list_object_refs()? {
+            roots.push(ctx.deref_object_ref(&object_ref)?);
+        }
+
+        let mut seen = HashSet::new();
+        let mut missing = Vec::new();
+        let mut stack = roots;
+        while let Some(object_id) = stack.pop() {
+            if !seen.insert(object_id) {
+                continue;
+            }
+            match ctx.read_tree_contents(&object_id) {
+                Ok(objects) => stack.extend(
+                    objects
+                        .into_iter()
+                        .filter(|object| object.is_tree())
+                        .map(|object| object.object_id),
+                ),
+                Err(ReadContentError::ObjectNotFound) => missing.push(object_id),
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(missing)
+    }
+
+    // Pick a base for trees reachable from HEAD and the refs: the first tree
+    // seen at a path is the base of the later ones seen there. A tree is
+    // visited once, so a base is never a delta itself.
+    fn delta_bases(ctx: &Context) -> anyhow::Result<HashMap<ObjectID, ObjectID>> {
+        let mut roots = ctx.read_head().into_iter().collect::<Vec<_>>();
+        for object_ref in ctx.list_object_refs()? {
+            roots.push(ctx.deref_object_ref(&object_ref)?);
+        }
+
+        let mut first_at_path = HashMap::new();
+        let mut seen = HashSet::new();
+        let mut bases = HashMap::new();
+        for root in roots {
+            let mut stack = vec![(PathBuf::new(), root)];
+            while let Some((path, object_id)) = stack.pop() {
+                if !seen.insert(object_id) {
+                    continue;
+                }
+                match first_at_path.entry(path.clone()) {
+                    hash_map::Entry::Vacant(entry) => {
+                        entry.insert(object_id);
+                    }
+                    hash_map::Entry::Occupied(entry) => {
+                        bases.insert(object_id, *entry.get());
+                    }
+                }
+
+                for object in ctx.read_tree_contents(&object_id)? {
+                    if object.is_tree() {
+                        stack.push((path.join(&object.file_path), object.object_id));
+                    }
+                }
+            }
+        }
+        Ok(bases)
+    }
+
+    fn run_incremental(ctx: Context) -> anyhow::Result<()> {
+        fs::create_dir_all(ctx.pack_dir())?;
+
+        let objects_dir = ctx.objects_dir();
+        let pack_file = ctx.pack_file();
+        let object_files = ctx.object_files()?;
+        let audit_file = ctx.audit_file();
+        // the context holds the pack open
+        drop(ctx);
+
+        let db = if pack_file.exists() {
+            Database::open(&pack_file)?
+        } else {
+            Database::create(&pack_file)?
+        };
+        let write_txn = db.begin_write()?;
+        let mut packed = 0;
+        {
+            let mut table = write_txn.open_table(PACKED_OBJECTS_TABLE)?;
+            for object_file in &object_files {
+                let object_id = store::loose_object_id(object_file)?;
+                if table.get(&object_id)?.is_none() {
+                    table.insert(object_id, fs::read(object_file)?)?;
+                    packed += 1;
+                }
+            }
+        }
+        write_txn.commit()?;
+
+        for object_file in &object_files {
+            fs::remove_file(object_file)?;
+        }
+        Self::remove_object_dirs(&objects_dir)?;
+
+        crate::audit::append(
+            audit_file,
+            &AuditEntry::new("pack-incremental").objects(packed),
+        )?;
+        Ok(())
+    }
+
+    fn remove_object_dirs(objects_dir: &Path) -> io::Result<()> {
+        if !objects_dir.exists() {
+            return Ok(());
+        }
+
+        for dir in fs::read_dir(objects_dir)? {
             let dir = dir?;
             if !dir.file_type()?.is_dir() {
                 continue;
@@ -332,15 +840,344 @@ impl PackCommand {
 
             fs::remove_dir(dir.path())?;
         }
-        let pack_file = ctx.pack_file();
-        drop(ctx);
+        Ok(())
+    }
+}
 
-        fs::rename(&tmp_file, pack_file)?;
+#[derive(Args, Debug)]
+pub struct LsCommand {
+    /// Tree to list. Defaults to HEAD.
+    #[clap(value_name = "object")]
+    object: Option<ObjectExpr>,
+
+    /// Show the kind, object ID and stored size of each entry.
+    /// Only trees are stored, so files have no size.
+    #[clap(long, short, verbatim_doc_comment)]
+    long: bool,
+}
+
+impl LsCommand {
+    pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+        let object_id = match self.object {
+            Some(ref object) => object.resolve(&ctx)?,
+            None => ctx.read_head()?,
+        };
+
+        let stdout = io::stdout();
+        let mut stdout = BufWriter::new(stdout.lock());
+        for object in ctx.read_tree_contents(&object_id)? {
+            let suffix = if object.is_tree() { "/" } else { "" };
+            if !self.long {
+                writeln!(stdout, "{}{}", object.file_path.display(), suffix)?;
+                continue;
+            }
+
+            let size = match object.object_type {
+                ObjectType::Tree => ctx.read_object(&object.object_id)?.len().to_string(),
+                ObjectType::File => "-".to_string(),
+            };
+            writeln!(
+                stdout,
+                "{} {} {:>8}\t{}{}",
+                object.object_type,
+                object.object_id,
+                size,
+                object.file_path.display(),
+                suffix
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct FindCommand {
+    /// Tree to search. Defaults to HEAD.
+    #[clap(value_name = "object")]
+    object: Option<ObjectExpr>,
+
+    /// Glob matched against the entry name, e.g. "*.parquet"
+    #[clap(long, value_name = "pattern")]
+    name: Option<Glob>,
+
+    /// Regular expression matched against the whole path
+    #[clap(long, value_name = "pattern")]
+    regex: Option<Regex>,
+
+    /// Object ID of the entries to find, or a prefix of at least 4 digits.
+    /// Finds every path a tree or file hash appears at, e.g. duplicate files.
+    #[clap(long, value_name = "object-id", value_parser = parse_object_id_prefix, verbatim_doc_comment)]
+    id: Option<String>,
+
+    /// Type of objects to print
+    #[clap(long, short, value_name = "type")]
+    r#type: Option<ObjectType>,
+
+    /// Minimum depth of printed entries; the entries of the tree are at depth 1
+    #[clap(long, value_name = "min-depth")]
+    min_depth: Option<usize>,
+
+    /// Maximum depth to descend
+    #[clap(long, value_name = "max-depth")]
+    max_depth: Option<usize>,
+
+    /// End each path with NUL instead of newline, for `xargs -0`
+    #[clap(short = 'z', long, default_value_t = false)]
+    null: bool,
+}
+
+impl FindCommand {
+    pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+        let object_id = match self.object {
+            Some(ref object) => object.resolve(&ctx)?,
+            None => ctx.read_head()?,
+        };
+        let name = self.name.as_ref().map(|glob| glob.compile_matcher());
+
+        let stdout = io::stdout();
+        let mut stdout = BufWriter::new(stdout.lock());
+        let mut entries = ctx.tree_iter(&object_id);
+        while let Some(entry) = entries.next() {
+            let entry = entry?;
+            let depth = entries.depth();
+            let file_name = entry.path.file_name().unwrap_or_default();
+            let matched = self.min_depth.is_none_or(|min| depth >= min)
+                && self.r#type.as_ref().is_none_or(|t| *t == entry.object_type)
+                && name.as_ref().is_none_or(|name| name.is_match(file_name))
+                && self
+                    .regex
+                    .as_ref()
+                    .is_none_or(|regex| regex.is_match(&entry.path.to_string_lossy()))
+                && self
+                    .id
+                    .as_ref()
+                    .is_none_or(|id| entry.object_id.to_string().starts_with(id));
+            let is_tree = entry.object_type == ObjectType::Tree;
+            if matched {
+                let suffix = if is_tree { "/" } else { "" };
+                let end = if self.null { '\0' } else { '\n' };
+                let path = ctx.render_path(&entry.path);
+                write!(stdout, "{}{}{}", path.display(), suffix, end)?;
+            }
+
+            if is_tree && self.max_depth.is_some_and(|max| depth >= max) {
+                entries.skip_children();
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_object_id_prefix(s: &str) -> Result<String, String> {
+    let hex = s.bytes().all(|b| b.is_ascii_hexdigit());
+    if !(MIN_OBJECT_ID_PREFIX..=OBJECT_ID_LEN).contains(&s.len()) || !hex {
+        return Err(format!(
+            "expected {} to {} hex digits",
+            MIN_OBJECT_ID_PREFIX, OBJECT_ID_LEN
+        ));
+    }
+    Ok(s.to_ascii_lowercase())
+}
+
+#[derive(Args, Debug)]
+pub struct RewriteCommand {
+    /// Tree to rewrite
+    #[clap(value_name = "object")]
+    object: ObjectExpr,
+
+    /// Drop entries whose path matches, e.g. "cache/**". Can be repeated.
+    #[clap(long, value_name = "pattern")]
+    exclude: Vec<Glob>,
+
+    /// Move entries from one path to another, e.g. "old/:new/".
+    /// Can be repeated; the first matching rule applies.
+    /// Excludes are matched against the paths before renaming.
+    #[clap(long, value_name = "old:new", value_parser = parse_rename, verbatim_doc_comment)]
+    rename: Vec<Rename>,
+}
+
+#[derive(Clone, Debug)]
+struct Rename {
+    from: PathBuf,
+    to: PathBuf,
+}
+
+fn parse_rename(s: &str) -> Result<Rename, String> {
+    let (from, to) = s
+        .split_once(':')
+        .ok_or_else(|| "expected \"old:new\"".to_string())?;
+    let from = from.trim_end_matches('/');
+    let to = to.trim_end_matches('/');
+    if from.is_empty() || to.is_empty() {
+        return Err("the root cannot be renamed".to_string());
+    }
+    Ok(Rename {
+        from: PathBuf::from(from),
+        to: PathBuf::from(to),
+    })
+}
+
+impl RewriteCommand {
+    pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+        let _lock = ctx.lock()?;
+        let object_id = self.object.resolve(&ctx)?;
+
+        let excludes = self
+            .exclude
+            .iter()
+            .map(|glob| (glob.compile_matcher(), literal_prefix(glob.glob())))
+            .collect::<Vec<_>>();
+        let mut builder = TreeBuilder::strict();
+        self.rewrite(&ctx, &mut builder, &excludes, Path::new(""), &object_id)?;
+        println!("{}", builder.write(&ctx)?);
+
+        Ok(())
+    }
+
+    // subtrees that no rule can touch are kept as they are, without reading them
+    fn rewrite(
+        &self,
+        ctx: &Context,
+        builder: &mut TreeBuilder,
+        excludes: &[(GlobMatcher, String)],
+        parent: &Path,
+        object_id: &ObjectID,
+    ) -> anyhow::Result<()> {
+        let objects = ctx.read_tree_contents(object_id)?;
+        if objects.is_empty() && !parent.as_os_str().is_empty() {
+            builder.insert(ObjectType::Tree, *object_id, self.renamed(parent))?;
+        }
+
+        for object in objects {
+            let path = parent.join(&object.file_path);
+            if excludes.iter().any(|(matcher, _)| matcher.is_match(&path)) {
+                continue;
+            }
+
+            let descend = object.is_tree()
+                && (excludes
+                    .iter()
+                    .any(|(_, prefix)| may_match_below(prefix, &path))
+                    || self.rename.iter().any(|rename| {
+                        (rename.from.starts_with(&path) && rename.from != path)
+                            || rename.to.starts_with(&path)
+                    }));
+            match descend {
+                true => self.rewrite(ctx, builder, excludes, &path, &object.object_id)?,
+                false => builder.insert(
+                    object.object_type.clone(),
+                    object.object_id,
+                    self.renamed(&path),
+                )?,
+            }
+        }
+        Ok(())
+    }
+
+    fn renamed(&self, path: &Path) -> PathBuf {
+        for rename in &self.rename {
+            if let Ok(rest) = path.strip_prefix(&rename.from) {
+                return rename.to.join(rest);
+            }
+        }
+        path.to_path_buf()
+    }
+}
+
+// the part of a glob before its first special character
+fn literal_prefix(glob: &str) -> String {
+    glob.chars()
+        .take_while(|c| !matches!(c, '*' | '?' | '[' | '{' | '\\'))
+        .collect()
+}
+
+// whether a glob starting with `prefix` can match something below `dir`
+fn may_match_below(prefix: &str, dir: &Path) -> bool {
+    let dir = format!("{}/", dir.display());
+    dir.starts_with(prefix) || prefix.starts_with(&dir)
+}
+
+#[derive(Args, Debug)]
+pub struct RerootCommand {
+    /// Subtree to use as a root, e.g. "HEAD:path/to/dir"
+    #[clap(value_name = "object")]
+    object: ObjectExpr,
+
+    /// Save the result as a ref
+    #[clap(long, value_name = "ref-name")]
+    save: Option<String>,
+}
+
+impl RerootCommand {
+    pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+        let _lock = match self.save {
+            Some(_) => Some(ctx.lock()?),
+            None => None,
+        };
+
+        let object_id = self.object.resolve(&ctx)?;
+        if ctx.read_tree_contents(&object_id).is_err() {
+            anyhow::bail!("{} is not a tree", object_id);
+        }
+        if let Some(ref_name) = &self.save {
+            save_ref(&ctx, "reroot", ref_name, &object_id)?;
+        }
+        println!("{}", object_id);
+
+        Ok(())
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct WrapCommand {
+    /// Tree to nest
+    #[clap(value_name = "object")]
+    object: ObjectExpr,
+
+    /// Directories to nest the tree under, e.g. "new/prefix/"
+    #[clap(long, value_name = "path")]
+    under: PathBuf,
+
+    /// Save the result as a ref
+    #[clap(long, value_name = "ref-name")]
+    save: Option<String>,
+}
+
+impl WrapCommand {
+    pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+        let _lock = ctx.lock()?;
+
+        let object_id = self.object.resolve(&ctx)?;
+        let mut builder = TreeBuilder::new();
+        builder.insert(ObjectType::Tree, object_id, &self.under)?;
+        let object_id = builder.write(&ctx)?;
+        if let Some(ref_name) = &self.save {
+            save_ref(&ctx, "wrap", ref_name, &object_id)?;
+        }
+        println!("{}", object_id);
 
         Ok(())
     }
 }
 
+// point a ref at a new tree, as `ref save` does
+fn save_ref(
+    ctx: &Context,
+    operation: &str,
+    ref_name: &str,
+    object_id: &ObjectID,
+) -> anyhow::Result<()> {
+    let previous = ctx.deref_object_ref(&ref_name.into()).ok();
+    ctx.write_object_ref(ref_name, *object_id)?;
+    ctx.audit(
+        &AuditEntry::new(operation)
+            .reference(ref_name)
+            .object_id(object_id)
+            .previous(previous),
+    )?;
+    Ok(())
+}
+
 #[derive(Args, Debug)]
 pub struct PrintTreeCommand {
     /// Root object ID where to start printing the tree
@@ -354,6 +1191,12 @@ pub struct PrintTreeCommand {
     /// Maximum depth to print
     #[clap(long, value_name = "max-depth")]
     max_depth: Option<usize>,
+
+    /// End each entry with NUL instead of newline, and print paths as they
+    /// are. Otherwise backslashes, tabs and newlines in paths are escaped as
+    /// "\\", "\t" and "\n", as plumbing write-tree reads them.
+    #[clap(short = 'z', long, default_value_t = false, verbatim_doc_comment)]
+    null: bool,
 }
 
 impl PrintTreeCommand {
@@ -362,41 +1205,84 @@ impl PrintTreeCommand {
             Some(ref object_id) => object_id.resolve(&ctx)?,
             None => ctx.read_head()?,
         };
-        let object_type = self.r#type.as_ref();
 
-        println!("tree {}\t.", object_id);
-        Self::print_tree(&ctx, &object_id, object_type, self.max_depth)?;
+        let stdout = io::stdout();
+        let mut stdout = BufWriter::new(stdout.lock());
+        self.write_entry(
+            &ctx,
+            &mut stdout,
+            ObjectType::Tree,
+            &object_id,
+            Path::new("."),
+        )?;
+        self.print_tree(&ctx, &mut stdout, Path::new(""), &object_id, 0)?;
+        stdout.flush()?;
 
         Ok(())
     }
 
-    fn print_tree(
+    fn end(&self) -> char {
+        if self.null {
+            '\0'
+        } else {
+            '\n'
+        }
+    }
+
+    // "tree <id>\t<path>/" or "file <id>\t<path>", or a JSON object
+    fn write_entry<W: io::Write>(
+        &self,
         ctx: &Context,
+        stdout: &mut W,
+        object_type: ObjectType,
         object_id: &ObjectID,
-        object_type: Option<&ObjectType>,
-        max_depth: Option<usize>,
-    ) -> anyhow::Result<()> {
-        let stdout = io::stdout();
-        let mut stdout = BufWriter::new(stdout.lock());
-        Self::inner_print_tree(ctx, &mut stdout, "", object_id, object_type, max_depth, 0)
+        path: &Path,
+    ) -> io::Result<()> {
+        let shown = ctx.render_path(path);
+        if ctx.output_format() == OutputFormat::Json {
+            return output::write_json(
+                stdout,
+                &json!({
+                    "type": object_type.to_string(),
+                    "id": object_id.to_string(),
+                    "path": shown.to_string_lossy(),
+                }),
+            );
+        }
+        let slash = match object_type {
+            ObjectType::Tree if path != Path::new(".") => "/",
+            _ => "",
+        };
+        let shown = match self.null {
+            true => shown.to_string_lossy(),
+            false => tree::escape_path(&shown.to_string_lossy()).into(),
+        };
+        write!(
+            stdout,
+            "{} {}\t{}{}{}",
+            object_type,
+            ctx.abbrev(object_id),
+            shown,
+            slash,
+            self.end()
+        )
     }
 
-    fn inner_print_tree<W: io::Write, P: Into<PathBuf>>(
+    fn print_tree<W: io::Write>(
+        &self,
         ctx: &Context,
         stdout: &mut W,
-        parent: P,
+        parent: &Path,
         object_id: &ObjectID,
-        object_type: Option<&ObjectType>,
-        max_depth: Option<usize>,
         depth: usize,
     ) -> anyhow::Result<()> {
-        if let Some(max_depth) = max_depth {
+        if let Some(max_depth) = self.max_depth {
             if depth >= max_depth {
                 return Ok(());
             }
         }
 
-        let parent = parent.into();
+        let object_type = self.r#type.as_ref();
         let objects = ctx.read_tree_contents(object_id)?;
         for object in &objects {
             let file_name = parent.join(&object.file_path);
@@ -404,26 +1290,25 @@ impl PrintTreeCommand {
             match object.object_type {
                 ObjectType::Tree => {
                     if object_type.is_none() || object_type == Some(&ObjectType::Tree) {
-                        writeln!(
+                        self.write_entry(
+                            ctx,
                             stdout,
-                            "tree {}\t{}/",
-                            object.object_id,
-                            file_name.display(),
+                            ObjectType::Tree,
+                            &object.object_id,
+                            &file_name,
                         )?;
                     }
-                    Self::inner_print_tree(
-                        ctx,
-                        stdout,
-                        &file_name,
-                        &object.object_id,
-                        object_type,
-                        max_depth,
-                        depth + 1,
-                    )?;
+                    self.print_tree(ctx, stdout, &file_name, &object.object_id, depth + 1)?;
                 }
                 ObjectType::File => {
                     if object_type.is_none() || object_type == Some(&ObjectType::File) {
-                        writeln!(stdout, "file {}\t{}", object.object_id, file_name.display())?;
+                        self.write_entry(
+                            ctx,
+                            stdout,
+                            ObjectType::File,
+                            &object.object_id,
+                            &file_name,
+                        )?;
                     }
                 }
             }
@@ -437,10 +1322,74 @@ pub struct GCCommand {
     /// Dry run
     #[clap(long = "dry", short = 'n', default_value_t = false)]
     dry_run: bool,
+
+    /// Keep unreachable objects modified within this duration (e.g. 30m, 12h, 7d).
+    /// Files left in .mtl/tmp by killed writers are removed once older than
+    /// this, or than an hour without it. [config: gc.grace]
+    #[clap(long, value_name = "duration", value_parser = datetime::parse_duration, verbatim_doc_comment)]
+    grace: Option<Duration>,
+
+    /// Write the objects that would be deleted to this file as JSON, with
+    /// their loose file and size, and delete nothing.
+    #[clap(long, value_name = "path", verbatim_doc_comment)]
+    plan: Option<PathBuf>,
+
+    /// Delete the objects listed in a file written by --plan, instead of all
+    /// the unreachable ones. Objects that became reachable since are kept.
+    #[clap(long, value_name = "path", conflicts_with_all = ["grace", "plan"], verbatim_doc_comment)]
+    from_plan: Option<PathBuf>,
+
+    /// Move the deleted objects to .mtl/trash/<time>/ instead, laid out like
+    /// .mtl/objects, so that they can be copied back until the trash is
+    /// emptied. Packed objects are written there as loose ones.
+    #[clap(long, default_value_t = false, verbatim_doc_comment)]
+    trash: bool,
+
+    /// Delete everything in .mtl/trash, and nothing else
+    #[clap(long, default_value_t = false, conflicts_with_all = ["dry_run", "grace", "plan", "from_plan", "trash"])]
+    empty_trash: bool,
+}
+
+/// The objects a `gc` deletes, as written by `gc --plan`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct GcPlan {
+    objects: Vec<PlannedObject>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PlannedObject {
+    id: String,
+    /// The loose object file, if there is one. The object may be packed too.
+    path: Option<PathBuf>,
+    bytes: u64,
+}
+
+impl PlannedObject {
+    fn new(ctx: &Context, object_id: &ObjectID) -> anyhow::Result<Self> {
+        let path = ctx.object_file(object_id);
+        let (path, bytes) = match fs::metadata(&path) {
+            Ok(metadata) => (Some(path), file_size(&metadata)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                (None, ctx.read_object(object_id)?.len() as u64)
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Ok(PlannedObject {
+            id: object_id.to_string(),
+            path,
+            bytes,
+        })
+    }
 }
 
 impl GCCommand {
     pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+        let _lock = ctx.lock()?;
+        if self.empty_trash {
+            return Self::empty_trash(&ctx);
+        }
+        // objects only in the pack would look unreachable
+        ctx.check_pack()?;
         let head_object = ctx.read_head()?;
 
         let mut objects = ctx
@@ -456,30 +1405,82 @@ impl GCCommand {
             Self::mark_used_object(&ctx, &object_id, &mut objects)?;
         }
 
+        for object_id in Self::read_keep_file(&ctx)? {
+            Self::mark_used_object(&ctx, &object_id, &mut objects)?;
+        }
+
+        let plan = match &self.from_plan {
+            Some(plan_file) => Self::read_plan(&ctx, plan_file, &objects)?,
+            None => self.make_plan(&ctx, &objects)?,
+        };
+        if let Some(plan_file) = &self.plan {
+            let mut file = BufWriter::new(fs::File::create(plan_file)?);
+            serde_json::to_writer_pretty(&mut file, &plan)?;
+            writeln!(file)?;
+            file.flush()?;
+        }
+        let dry_run = self.dry_run || self.plan.is_some();
+        let trash_dir = self.trash.then(|| {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs());
+            ctx.trash_dir().join(datetime::format_compact(now))
+        });
+
         let mut deleted_objects = 0u64;
         let mut deleted_bytes = 0u64;
-        for (object_id, used) in objects {
-            if !used {
-                let path = ctx.object_file(&object_id);
-                let path_exists = path.exists();
-                // Note: not remove from packed db
-                if path_exists {
-                    let metadata = fs::metadata(&path)?;
-                    deleted_bytes += file_size(&metadata);
+        let mut packed_garbage = Vec::new();
+        for object in plan.objects {
+            let object_id: ObjectID = object.id.parse()?;
+            deleted_objects += 1;
+            deleted_bytes += object.bytes;
+            // the object may be in the pack even if it also exists as a loose file
+            packed_garbage.push(object_id);
+
+            if ctx.output_format() == OutputFormat::Json {
+                output::print_json(&json!({
+                    "action": if trash_dir.is_some() { "trash" } else { "remove" },
+                    "id": object.id,
+                    "path": object.path,
+                    "bytes": object.bytes,
+                }))?;
+            }
+            match (&object.path, &trash_dir) {
+                _ if dry_run && ctx.output_format() == OutputFormat::Json => {}
+                (Some(path), _) if dry_run => println!("[dry-run] Removing {}", path.display()),
+                (_, Some(trash_dir)) if !dry_run => {
+                    let dest = Self::move_to_trash(&ctx, trash_dir, &object_id, object.path)?;
+                    if ctx.output_format() == OutputFormat::Text {
+                        println!("Moving {} to {}", object_id, dest.display());
+                    }
                 }
-                deleted_objects += 1;
-
-                if path_exists {
-                    if self.dry_run {
-                        println!("[dry-run] Removing {}", path.display());
-                    } else {
+                (Some(path), None) => {
+                    if ctx.output_format() == OutputFormat::Text {
                         println!("Removing {}", path.display());
-                        fs::remove_file(path)?;
                     }
+                    fs::remove_file(path)?;
                 }
+                _ => {}
             }
         }
-        if self.dry_run {
+        self.remove_stale_tmp(&ctx, dry_run)?;
+
+        let pack_file = ctx.pack_file();
+        let audit_file = ctx.audit_file();
+        let format = ctx.output_format();
+        let hooks = ctx.hooks();
+        drop(ctx);
+        if pack_file.exists() && !packed_garbage.is_empty() {
+            Self::prune_pack(&pack_file, &packed_garbage, dry_run)?;
+        }
+
+        if format == OutputFormat::Json {
+            output::print_json(&json!({
+                "dry_run": dry_run,
+                "deleted_objects": deleted_objects,
+                "deleted_bytes": deleted_bytes,
+            }))?;
+        } else if dry_run {
             println!(
                 "[dry-run] Deleted {} objects ({} bytes)",
                 deleted_objects, deleted_bytes
@@ -490,7 +1491,256 @@ impl GCCommand {
                 deleted_objects, deleted_bytes
             );
         }
+        if !dry_run {
+            crate::audit::append(audit_file, &AuditEntry::new("gc").objects(deleted_objects))?;
+            let deleted = deleted_objects.to_string();
+            hooks.notify(
+                Hook::PostGc,
+                &[&deleted],
+                &[("MTL_DELETED_OBJECTS", &deleted)],
+            );
+        }
+
+        Ok(())
+    }
+
+    // Files in .mtl/tmp are renamed into place once written, so those older
+    // than the grace period, or an hour without one, are left by killed
+    // writers. Nobody else writes while gc holds the lock, but commands that
+    // do not take it may.
+    fn remove_stale_tmp(&self, ctx: &Context, dry_run: bool) -> anyhow::Result<()> {
+        let age = self
+            .grace
+            .or(ctx.config().grace)
+            .unwrap_or(Duration::from_secs(60 * 60));
+        let cutoff = SystemTime::now().checked_sub(age).unwrap_or(UNIX_EPOCH);
+        let entries = match fs::read_dir(ctx.tmp_dir()) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        for entry in entries {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if !metadata.is_file() || metadata.modified()? > cutoff {
+                continue;
+            }
+            let path = entry.path();
+            match (dry_run, ctx.output_format()) {
+                (_, OutputFormat::Json) => {}
+                (true, _) => println!("[dry-run] Removing {}", path.display()),
+                (false, _) => println!("Removing {}", path.display()),
+            }
+            if !dry_run {
+                fs::remove_file(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    // the unreachable objects, but for those within the grace period
+    fn make_plan(
+        &self,
+        ctx: &Context,
+        objects: &HashMap<ObjectID, bool>,
+    ) -> anyhow::Result<GcPlan> {
+        let cutoff = self
+            .grace
+            .or(ctx.config().grace)
+            .map(|grace| SystemTime::now().checked_sub(grace).unwrap_or(UNIX_EPOCH));
+        // packed objects carry no timestamp of their own, so the pack's is used
+        let pack_mtime = fs::metadata(ctx.pack_file())
+            .and_then(|m| m.modified())
+            .ok();
+
+        let mut plan = GcPlan::default();
+        let mut kept_objects = 0u64;
+        let unreachable = objects
+            .iter()
+            .filter(|(_, used)| !**used)
+            .map(|(object_id, _)| object_id)
+            .sorted();
+        for object_id in unreachable {
+            let object = PlannedObject::new(ctx, object_id)?;
+            if let Some(cutoff) = cutoff {
+                let mtime = match &object.path {
+                    Some(path) => fs::metadata(path)?.modified().ok(),
+                    None => pack_mtime,
+                };
+                if mtime.is_some_and(|mtime| mtime > cutoff) {
+                    kept_objects += 1;
+                    continue;
+                }
+            }
+            plan.objects.push(object);
+        }
+
+        if kept_objects > 0 {
+            println!(
+                "Kept {} unreachable objects within the grace period",
+                kept_objects
+            );
+        }
+        Ok(plan)
+    }
+
+    // the objects of a plan that are still there and still unreachable
+    fn read_plan(
+        ctx: &Context,
+        plan_file: &Path,
+        objects: &HashMap<ObjectID, bool>,
+    ) -> anyhow::Result<GcPlan> {
+        let read: GcPlan = serde_json::from_reader(io::BufReader::new(fs::File::open(plan_file)?))
+            .map_err(|e| anyhow::anyhow!("{}: {}", plan_file.display(), e))?;
+        let mut plan = GcPlan::default();
+        for object in read.objects {
+            let object_id: ObjectID = object.id.parse()?;
+            match objects.get(&object_id) {
+                Some(false) => plan.objects.push(PlannedObject::new(ctx, &object_id)?),
+                Some(true) => Self::print_left(ctx, "Keeping", &object_id, "reachable again")?,
+                None => Self::print_left(ctx, "Skipping", &object_id, "already deleted")?,
+            }
+        }
+        Ok(plan)
+    }
+
+    // an object of a plan that is not deleted after all
+    fn print_left(
+        ctx: &Context,
+        action: &str,
+        object_id: &ObjectID,
+        reason: &str,
+    ) -> io::Result<()> {
+        match ctx.output_format() {
+            OutputFormat::Text => {
+                println!("{} {}: {}", action, object_id, reason);
+                Ok(())
+            }
+            OutputFormat::Json => output::print_json(&json!({
+                "action": action.to_lowercase(),
+                "id": object_id.to_string(),
+                "reason": reason,
+            })),
+        }
+    }
+
+    // keep a copy of an object in the trash, moving its loose file if it has
+    // one, and return where it went
+    fn move_to_trash(
+        ctx: &Context,
+        trash_dir: &Path,
+        object_id: &ObjectID,
+        loose_file: Option<PathBuf>,
+    ) -> anyhow::Result<PathBuf> {
+        let object_file = ctx.object_file(object_id);
+        let dest = trash_dir.join(object_file.strip_prefix(ctx.objects_dir())?);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        match loose_file {
+            Some(path) => fs::rename(path, &dest)?,
+            None => {
+                let (kind, contents) = ctx.store().get_typed(object_id)?;
+                fs::write(&dest, header::restore(kind, contents))?;
+            }
+        }
+        Ok(dest)
+    }
 
+    fn empty_trash(ctx: &Context) -> anyhow::Result<()> {
+        let trash_dir = ctx.trash_dir();
+        if !trash_dir.exists() {
+            println!("Trash is empty");
+            return Ok(());
+        }
+        // laid out as <time>/xx/rest
+        let mut objects = 0usize;
+        for run in fs::read_dir(&trash_dir)? {
+            for prefix in fs::read_dir(run?.path())? {
+                objects += fs::read_dir(prefix?.path())?.count();
+            }
+        }
+        fs::remove_dir_all(&trash_dir)?;
+        println!("Emptied trash ({} objects)", objects);
+        ctx.audit(&AuditEntry::new("gc-empty-trash").objects(objects as u64))?;
+        Ok(())
+    }
+
+    fn read_keep_file(ctx: &Context) -> anyhow::Result<Vec<ObjectID>> {
+        let keep_file = ctx.keep_file();
+        if !keep_file.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut object_ids = Vec::new();
+        for line in fs::read_to_string(&keep_file)?.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let expr: ObjectExpr = line
+                .parse()
+                .map_err(|e| anyhow::anyhow!("{}: {}", keep_file.display(), e))?;
+            match expr.resolve(ctx) {
+                Ok(object_id) => object_ids.push(object_id),
+                Err(e) => log::warn!("{}: ignored \"{}\": {}", keep_file.display(), line, e),
+            }
+        }
+        Ok(object_ids)
+    }
+
+    fn prune_pack(pack_file: &Path, object_ids: &[ObjectID], dry_run: bool) -> anyhow::Result<()> {
+        let mut db = Database::open(pack_file)?;
+        let write_txn = db.begin_write()?;
+        let mut removed = 0u64;
+        {
+            let mut table = write_txn.open_table(PACKED_OBJECTS_TABLE)?;
+            let mut delta_table = write_txn.open_table(PACKED_DELTAS_TABLE)?;
+
+            // deltas that outlive their base are stored whole again
+            let garbage: HashSet<_> = object_ids.iter().collect();
+            let mut orphans = Vec::new();
+            for range in delta_table.iter()? {
+                let (object_id, value) = range?;
+                let (object_id, (base_id, delta)) = (object_id.value(), value.value());
+                if garbage.contains(&base_id) && !garbage.contains(&object_id) {
+                    orphans.push((object_id, base_id, delta));
+                }
+            }
+            for (object_id, base_id, delta) in orphans {
+                let base = table
+                    .get(&base_id)?
+                    .ok_or_else(|| anyhow::anyhow!("base {} of {} not found", base_id, object_id))?
+                    .value();
+                let (kind, base) = header::decode_vec(base)?;
+                table.insert(
+                    object_id,
+                    header::restore(kind, delta::apply(&base, &delta)?),
+                )?;
+                delta_table.remove(&object_id)?;
+            }
+
+            for object_id in object_ids {
+                if table.remove(object_id)?.is_none() && delta_table.remove(object_id)?.is_none() {
+                    continue;
+                }
+                removed += 1;
+                if dry_run {
+                    println!("[dry-run] Removing {} from pack", object_id);
+                } else {
+                    println!("Removing {} from pack", object_id);
+                }
+            }
+        }
+        if dry_run {
+            write_txn.abort()?;
+            return Ok(());
+        }
+        write_txn.commit()?;
+
+        if removed > 0 {
+            db.compact()?;
+        }
         Ok(())
     }
 
@@ -506,6 +1756,9 @@ impl GCCommand {
             if object.is_tree() {
                 objects.insert(object.object_id, true);
                 Self::mark_used_object(ctx, &object.object_id, objects)?;
+            } else if let Some(used) = objects.get_mut(&object.object_id) {
+                // file contents stored as a blob by "plumbing hash-object -w"
+                *used = true;
             }
         }
 
@@ -513,6 +1766,121 @@ impl GCCommand {
     }
 }
 
+#[derive(Args, Debug)]
+pub struct PruneSnapshotsCommand {
+    /// Only treat refs whose name starts with this prefix as snapshots
+    #[clap(long, value_name = "prefix", default_value = "snapshot-", value_parser = NonEmptyStringValueParser::new())]
+    prefix: String,
+
+    /// Keep the N most recent snapshots
+    #[clap(long, value_name = "n", default_value_t = 0)]
+    keep_last: usize,
+
+    /// Keep the newest snapshot of each of the last N days
+    #[clap(long, value_name = "n", default_value_t = 0)]
+    keep_daily: usize,
+
+    /// Keep the newest snapshot of each of the last N weeks
+    #[clap(long, value_name = "n", default_value_t = 0)]
+    keep_weekly: usize,
+
+    /// Keep the newest snapshot of each of the last N months
+    #[clap(long, value_name = "n", default_value_t = 0)]
+    keep_monthly: usize,
+
+    /// Dry run
+    #[clap(long = "dry", short = 'n', default_value_t = false)]
+    dry_run: bool,
+
+    /// Run garbage collection after deleting refs
+    #[clap(long, default_value_t = false)]
+    gc: bool,
+
+    /// Repack the objects after garbage collection
+    #[clap(long, default_value_t = false, requires = "gc")]
+    pack: bool,
+}
+
+impl PruneSnapshotsCommand {
+    pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+        let policy = RetentionPolicy {
+            keep_last: self.keep_last,
+            keep_daily: self.keep_daily,
+            keep_weekly: self.keep_weekly,
+            keep_monthly: self.keep_monthly,
+        };
+        if policy.is_empty() {
+            anyhow::bail!("no retention policy given; pass at least one --keep-* option");
+        }
+
+        {
+            let _lock = ctx.lock()?;
+
+            // a snapshot is as old as the last time its ref was written, as
+            // the reflog tells; the mtime of the ref file changes with any
+            // copy or touch
+            let mut snapshots = Vec::new();
+            for object_ref in ctx.list_object_refs()? {
+                let name = object_ref.to_string();
+                if !name.starts_with(&self.prefix) {
+                    continue;
+                }
+                let time = match ctx.read_reflog_time(&name)? {
+                    Some(time) => time,
+                    // written before reflogs existed
+                    None => fs::metadata(ctx.reference_file(&name))?.modified()?,
+                };
+                snapshots.push((name, time));
+            }
+            snapshots.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+            let times = snapshots.iter().map(|(_, time)| *time).collect_vec();
+            for ((name, _), keep) in snapshots.iter().zip(policy.retain(&times)) {
+                if keep {
+                    continue;
+                }
+                if self.dry_run {
+                    println!("[dry-run] Deleting ref \"{}\"", name);
+                } else {
+                    println!("Deleting ref \"{}\"", name);
+                    let previous = ctx.deref_object_ref(&name.as_str().into()).ok();
+                    ctx.delete_object_ref(name)?;
+                    ctx.audit(
+                        &AuditEntry::new("prune-snapshots")
+                            .reference(name)
+                            .previous(previous),
+                    )?;
+                }
+            }
+        }
+
+        if self.gc {
+            // gc and pack open the pack through handles of their own, so the
+            // store is opened again for pack, with the same settings
+            let settings = ctx.for_store(Box::new(store::NullObjectStore));
+            let gc = GCCommand {
+                dry_run: self.dry_run,
+                grace: None,
+                plan: None,
+                from_plan: None,
+                trash: false,
+                empty_trash: false,
+            };
+            gc.run(ctx)?;
+
+            if self.pack && !self.dry_run {
+                PackCommand {
+                    incremental: false,
+                    delta: false,
+                    action: None,
+                }
+                .run(settings.reopen()?)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Subcommand)]
 pub enum ToolCommands {
     /// generate test data
@@ -521,6 +1889,9 @@ pub enum ToolCommands {
     /// calculate xxHash
     Hash(tool::Hash),
 
+    /// merge two "hash path" manifests
+    ManifestMerge(tool::ManifestMerge),
+
     #[cfg(not(windows))]
     /// fincore
     Fincore(tool::Fincore),
@@ -530,6 +1901,9 @@ pub enum ToolCommands {
 
     /// redb commands
     Redb(tool::ReDB),
+
+    /// time a standard workload with another mtl binary and this one
+    BenchCompare(bench::BenchCompare),
 }
 
 impl ToolCommands {
@@ -537,10 +1911,12 @@ impl ToolCommands {
         match self {
             ToolCommands::Generate(cmd) => cmd.run(),
             ToolCommands::Hash(cmd) => cmd.run(),
+            ToolCommands::ManifestMerge(cmd) => cmd.run(),
             #[cfg(not(windows))]
             ToolCommands::Fincore(cmd) => cmd.run(),
             ToolCommands::Fadvise(cmd) => cmd.run(),
             ToolCommands::Redb(cmd) => cmd.run(ctx),
+            ToolCommands::BenchCompare(cmd) => cmd.run(),
         }
     }
 }
@@ -0,0 +1,71 @@
+use std::io::{self, BufWriter, Write};
+
+use clap::Args;
+
+use crate::audit::{self, AuditEntry};
+use crate::datetime::DateFormat;
+use crate::output::OutputFormat;
+use crate::Context;
+
+#[derive(Args, Debug)]
+pub struct Show {
+    /// Print only the last N entries
+    #[clap(short = 'n', long, value_name = "n")]
+    limit: Option<usize>,
+
+    /// Print the entries as JSON lines, as they are stored
+    #[clap(long, default_value_t = false)]
+    json: bool,
+
+    /// Format of the timestamps
+    #[clap(long, value_name = "format", value_enum, default_value_t = DateFormat::Iso)]
+    date: DateFormat,
+}
+
+impl Show {
+    pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+        let entries = audit::read(ctx.audit_file())?;
+        let skip = match self.limit {
+            Some(limit) => entries.len().saturating_sub(limit),
+            None => 0,
+        };
+
+        let stdout = io::stdout();
+        let mut stdout = BufWriter::new(stdout.lock());
+        for entry in entries.iter().skip(skip) {
+            if self.json || ctx.output_format() == OutputFormat::Json {
+                serde_json::to_writer(&mut stdout, entry)?;
+                writeln!(stdout)?;
+            } else {
+                writeln!(stdout, "{}", self.format_entry(&ctx, entry))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn format_entry(&self, ctx: &Context, entry: &AuditEntry) -> String {
+        let mut line = format!(
+            "{} {} {}",
+            self.date.format(entry.time),
+            entry.user,
+            entry.operation
+        );
+        if let Some(reference) = &entry.reference {
+            line.push_str(&format!(" {}", reference));
+        }
+        let previous = entry.previous.as_deref().map(|id| ctx.abbrev_str(id));
+        let object_id = entry.object_id.as_deref().map(|id| ctx.abbrev_str(id));
+        match (previous, object_id) {
+            (Some(previous), Some(object_id)) => {
+                line.push_str(&format!(" {} -> {}", previous, object_id))
+            }
+            (Some(previous), None) => line.push_str(&format!(" {} -> (deleted)", previous)),
+            (None, Some(object_id)) => line.push_str(&format!(" {}", object_id)),
+            (None, None) => {}
+        }
+        if let Some(objects) = entry.objects {
+            line.push_str(&format!(" ({} objects)", objects));
+        }
+        line
+    }
+}
@@ -0,0 +1,215 @@
+//! Timing of a standard workload with two mtl binaries, to check an upgrade
+//! on the hardware it will run on.
+
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+use std::time::{Duration, Instant};
+use std::{env, fs};
+
+use clap::Args;
+
+use crate::Context;
+
+// what each run of the workload times, in order
+const STEPS: [&str; 4] = ["build", "rebuild", "diff", "pack"];
+
+#[derive(Args, Debug)]
+pub struct BenchCompare {
+    /// The mtl binary to compare against, like the release in use
+    #[clap(long, value_name = "path")]
+    baseline: PathBuf,
+
+    /// The mtl binary to measure. Defaults to this one.
+    #[clap(long, value_name = "path")]
+    candidate: Option<PathBuf>,
+
+    /// Directory to generate the workload in, on the filesystem to measure.
+    /// Defaults to the system temporary directory.
+    #[clap(long, value_name = "path", verbatim_doc_comment)]
+    dir: Option<PathBuf>,
+
+    /// Number of files to generate
+    #[clap(long, value_name = "n", default_value_t = 10000)]
+    files: usize,
+
+    /// Times to run the workload with each binary. The median is reported.
+    #[clap(long, value_name = "n", default_value_t = 3, value_parser = clap::value_parser!(u32).range(1..))]
+    runs: u32,
+
+    /// Keep the workload for inspection
+    #[clap(long, default_value_t = false)]
+    keep: bool,
+}
+
+impl BenchCompare {
+    pub fn run(&self) -> anyhow::Result<()> {
+        // the binaries run from inside the workload
+        let baseline = self.baseline.canonicalize()?;
+        let candidate = match &self.candidate {
+            Some(candidate) => candidate.canonicalize()?,
+            None => env::current_exe()?,
+        };
+
+        let parent = self.dir.clone().unwrap_or_else(env::temp_dir);
+        let repo = parent.join(format!("mtl-bench-{}", process::id()));
+        fs::create_dir(&repo)?;
+        let workload = Workload {
+            repo: repo.canonicalize()?,
+        };
+        let result = self.measure(&workload, &baseline, &candidate);
+
+        if self.keep {
+            println!("Kept {}", repo.display());
+        } else {
+            fs::remove_dir_all(&repo)?;
+        }
+        let (baseline_times, candidate_times) = result?;
+
+        println!(
+            "{:<10}{:>12}{:>12}{:>10}",
+            "step", "baseline", "candidate", "change"
+        );
+        for (i, step) in STEPS.iter().enumerate() {
+            let (before, after) = (baseline_times[i], candidate_times[i]);
+            println!(
+                "{:<10}{:>11.3}s{:>11.3}s{:>+9.1}%",
+                step,
+                before.as_secs_f64(),
+                after.as_secs_f64(),
+                change(before, after)
+            );
+        }
+        Ok(())
+    }
+
+    // median times of the steps with each binary, taking turns so that
+    // neither gets all the warm runs
+    fn measure(
+        &self,
+        workload: &Workload,
+        baseline: &Path,
+        candidate: &Path,
+    ) -> anyhow::Result<([Duration; 4], [Duration; 4])> {
+        // the same seed every time, so that results can be compared
+        let files = self.files.to_string();
+        workload.mtl(
+            &env::current_exe()?,
+            [
+                "tool",
+                "generate",
+                "data",
+                files.as_str(),
+                "--num-kilobytes",
+                "4",
+            ]
+            .into_iter()
+            .chain(["--prefix-bytes", "1", "--seed", "0"]),
+        )?;
+
+        let mut baseline_runs = Vec::new();
+        let mut candidate_runs = Vec::new();
+        for _ in 0..self.runs {
+            baseline_runs.push(workload.run(baseline)?);
+            candidate_runs.push(workload.run(candidate)?);
+        }
+        Ok((median(&baseline_runs), median(&candidate_runs)))
+    }
+}
+
+struct Workload {
+    repo: PathBuf,
+}
+
+impl Workload {
+    // one run from an empty repository
+    fn run(&self, exe: &Path) -> anyhow::Result<[Duration; 4]> {
+        let mtl_dir = Context::new(&self.repo)?.mtl_dir().to_path_buf();
+        if mtl_dir.exists() {
+            fs::remove_dir_all(&mtl_dir)?;
+        }
+        let changed = self.repo.join("data/changed");
+        if changed.exists() {
+            fs::remove_file(&changed)?;
+        }
+
+        let build = self.time(exe, ["local", "build"])?;
+        let first = Context::new(&self.repo)?.read_head()?.to_string();
+        fs::write(&changed, "changed\n")?;
+        let rebuild = self.time(exe, ["local", "build"])?;
+        let diff = self.time(exe, ["diff", first.as_str(), "HEAD"])?;
+        let pack = self.time(exe, ["pack"])?;
+        Ok([build, rebuild, diff, pack])
+    }
+
+    fn time<I, S>(&self, exe: &Path, args: I) -> anyhow::Result<Duration>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let start = Instant::now();
+        self.mtl(exe, args)?;
+        Ok(start.elapsed())
+    }
+
+    fn mtl<I, S>(&self, exe: &Path, args: I) -> anyhow::Result<()>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let output = Command::new(exe)
+            .args(args)
+            .current_dir(&self.repo)
+            .stdout(Stdio::null())
+            .output()?;
+        // diff exits with 1 when the trees differ
+        if !matches!(output.status.code(), Some(0 | 1)) {
+            anyhow::bail!(
+                "{}: {}: {}",
+                exe.display(),
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            );
+        }
+        Ok(())
+    }
+}
+
+// the median of each step over the runs
+fn median(runs: &[[Duration; 4]]) -> [Duration; 4] {
+    let mut medians = [Duration::ZERO; 4];
+    for (i, median) in medians.iter_mut().enumerate() {
+        let mut times = runs.iter().map(|run| run[i]).collect::<Vec<_>>();
+        times.sort();
+        *median = times[times.len() / 2];
+    }
+    medians
+}
+
+// percentage of the baseline time gained (+) or saved (-)
+fn change(baseline: Duration, candidate: Duration) -> f64 {
+    if baseline.is_zero() {
+        return 0.0;
+    }
+    (candidate.as_secs_f64() / baseline.as_secs_f64() - 1.0) * 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{change, median};
+
+    #[test]
+    fn test_median() {
+        let ms = Duration::from_millis;
+        let runs = [
+            [ms(3), ms(1), ms(5), ms(1)],
+            [ms(1), ms(2), ms(4), ms(1)],
+            [ms(2), ms(9), ms(6), ms(1)],
+        ];
+        assert_eq!(median(&runs), [ms(2), ms(2), ms(5), ms(1)]);
+        assert!((change(ms(200), ms(150)) + 25.0).abs() < 1e-9);
+        assert_eq!(change(ms(0), ms(150)), 0.0);
+    }
+}
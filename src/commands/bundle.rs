@@ -0,0 +1,171 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+use anyhow::Context as _;
+use clap::Args;
+use redb::{Database, ReadableTable, TableDefinition};
+
+use crate::audit::AuditEntry;
+use crate::store::check_redb_magic;
+use crate::{
+    check_ref_name, header, Context, ObjectExpr, ObjectID, ObjectRef, ObjectType, ReadContentError,
+    PACKED_OBJECTS_TABLE,
+};
+
+// a bundle is a redb database holding objects in the same table as the pack,
+// with their header as in the pack, plus the refs it was created from
+const BUNDLE_REFS_TABLE: TableDefinition<&str, ObjectID> = TableDefinition::new("bundle-refs");
+
+#[derive(Args, Debug)]
+pub struct Create {
+    /// Bundle file to create
+    #[clap(value_name = "file")]
+    file: PathBuf,
+
+    /// Roots whose reachable objects are bundled.
+    /// Refs given by name are recorded in the bundle too.
+    #[clap(value_name = "object", required = true, verbatim_doc_comment)]
+    objects: Vec<ObjectExpr>,
+}
+
+impl Create {
+    pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+        if self.file.exists() {
+            anyhow::bail!("{} already exists", self.file.display());
+        }
+
+        let mut refs = BTreeMap::new();
+        let mut objects = HashMap::new();
+        for object in &self.objects {
+            let object_id = object.resolve(&ctx)?;
+            if let (ObjectRef::Reference(name), None) = (&object.object_ref, &object.path) {
+                if name != "HEAD" && !name.contains("@{") {
+                    refs.insert(name.clone(), object_id);
+                }
+            }
+            reachable_objects(&ctx, &object_id, &mut objects)?;
+        }
+
+        let db = Database::create(&self.file)?;
+        let write_txn = db.begin_write()?;
+        {
+            let mut table = write_txn.open_table(PACKED_OBJECTS_TABLE)?;
+            for (object_id, kind) in &objects {
+                let contents = ctx.read_object(object_id)?;
+                table.insert(object_id, header::encode(kind.clone(), &contents))?;
+            }
+
+            let mut table = write_txn.open_table(BUNDLE_REFS_TABLE)?;
+            for (name, object_id) in &refs {
+                table.insert(name.as_str(), object_id)?;
+            }
+        }
+        write_txn.commit()?;
+
+        println!(
+            "Bundled {} objects and {} refs into {}",
+            objects.len(),
+            refs.len(),
+            self.file.display()
+        );
+        Ok(())
+    }
+}
+
+// trees below the root, and file contents that are stored as blobs, with
+// the kind they are reached as
+fn reachable_objects(
+    ctx: &Context,
+    object_id: &ObjectID,
+    objects: &mut HashMap<ObjectID, ObjectType>,
+) -> Result<(), ReadContentError> {
+    if objects.insert(*object_id, ObjectType::Tree).is_some() {
+        return Ok(());
+    }
+
+    for object in ctx.read_tree_contents(object_id)? {
+        if object.is_tree() {
+            reachable_objects(ctx, &object.object_id, objects)?;
+        } else if ctx.store().contains(&object.object_id)? {
+            objects.insert(object.object_id, ObjectType::File);
+        }
+    }
+    Ok(())
+}
+
+#[derive(Args, Debug)]
+pub struct Unbundle {
+    /// Bundle file to import
+    #[clap(value_name = "file")]
+    file: PathBuf,
+
+    /// Overwrite refs that already point elsewhere
+    #[clap(short, long, default_value_t = false)]
+    force: bool,
+}
+
+impl Unbundle {
+    pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+        let _lock = ctx.lock()?;
+
+        // redb would replace any other file with an empty database
+        let magic = check_redb_magic(&self.file)
+            .with_context(|| format!("cannot read {}", self.file.display()))?;
+        if let Err(reason) = magic {
+            anyhow::bail!("{} is not a bundle: {}", self.file.display(), reason);
+        }
+        let db = Database::open(&self.file)?;
+        let read_txn = db.begin_read()?;
+
+        let mut refs = Vec::new();
+        for range in read_txn.open_table(BUNDLE_REFS_TABLE)?.iter()? {
+            let (name, object_id) = range?;
+            let (name, object_id) = (name.value().to_string(), object_id.value());
+            check_ref_name(&name)?;
+            let current = ctx.deref_object_ref(&name.as_str().into()).ok();
+            if current.is_some_and(|current| current != object_id) && !self.force {
+                anyhow::bail!(
+                    "ref \"{}\" already exists with a different value, use --force to overwrite",
+                    name
+                );
+            }
+            refs.push((name, object_id, current));
+        }
+
+        let (mut trees, mut files) = (Vec::new(), Vec::new());
+        for range in read_txn.open_table(PACKED_OBJECTS_TABLE)?.iter()? {
+            let (object_id, stored) = range?;
+            let object_id = object_id.value();
+            let (kind, contents) = header::decode_vec(stored.value())
+                .with_context(|| format!("object {} in the bundle is corrupt", object_id))?;
+            if ObjectID::from_contents(&contents) != object_id {
+                anyhow::bail!("object {} in the bundle is corrupt", object_id);
+            }
+            match kind {
+                Some(ObjectType::File) => files.push((object_id, contents)),
+                // bundles made before kinds were kept hold trees and blobs
+                // alike without a header
+                Some(ObjectType::Tree) | None => trees.push((object_id, contents)),
+            }
+        }
+        ctx.write_objects(&trees)?;
+        ctx.write_objects_of(ObjectType::File, &files)?;
+        let count = trees.len() + files.len();
+        ctx.audit(&AuditEntry::new("unbundle").objects(count as u64))?;
+
+        for (name, object_id, current) in &refs {
+            if *current == Some(*object_id) {
+                continue;
+            }
+            ctx.write_object_ref(name, *object_id)?;
+            ctx.audit(
+                &AuditEntry::new("unbundle")
+                    .reference(name)
+                    .object_id(object_id)
+                    .previous(*current),
+            )?;
+            println!("{}\t{}", name, object_id);
+        }
+        Ok(())
+    }
+}
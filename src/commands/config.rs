@@ -0,0 +1,56 @@
+use std::path::PathBuf;
+
+use anyhow::Context as _;
+use clap::Args;
+
+use crate::config::{self, ConfigFile};
+use crate::Context;
+
+#[derive(Args, Debug)]
+pub struct Get {
+    /// The key, as "section.key", e.g. "core.jobs"
+    key: String,
+}
+
+impl Get {
+    pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+        config::check_key(&self.key)?;
+        let mut file = match config::user_config_path() {
+            Some(path) => ConfigFile::read(&path)?,
+            None => ConfigFile::default(),
+        };
+        file.merge(&ConfigFile::read(&ctx.config_file())?);
+        let value = file
+            .get(&self.key)
+            .with_context(|| format!("config key \"{}\" is not set", self.key))?;
+        println!("{}", value);
+        Ok(())
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct Set {
+    /// The key, as "section.key", e.g. "core.jobs"
+    key: String,
+
+    value: String,
+
+    /// Write the user's config file instead of the repository's
+    #[clap(long, default_value_t = false)]
+    user: bool,
+}
+
+impl Set {
+    pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+        let path: PathBuf = match self.user {
+            true => {
+                config::user_config_path().context("neither XDG_CONFIG_HOME nor HOME is set")?
+            }
+            false => ctx.config_file(),
+        };
+        let mut file = ConfigFile::read(&path)?;
+        file.set(&self.key, &self.value)?;
+        file.write(&path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,329 @@
+//! Conversion between mtl trees and git trees, through the git command.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::thread;
+
+use clap::Args;
+
+use crate::audit::AuditEntry;
+use crate::tree::TreeBuilder;
+use crate::{Context, ObjectExpr, ObjectID, ObjectType};
+
+// mtl records no permissions, so every file is exported as non-executable
+const GIT_FILE_MODE: &str = "100644";
+const GIT_TREE_MODE: &str = "040000";
+
+#[derive(Args, Debug)]
+pub struct ExportGitCommand {
+    /// Tree to export. File contents are read from the worktree, at the path
+    /// of the expression for a subtree like "HEAD:dir".
+    #[clap(value_name = "object")]
+    object: ObjectExpr,
+
+    /// Git repository to write the objects to
+    #[clap(long, value_name = "path")]
+    git_dir: PathBuf,
+}
+
+impl ExportGitCommand {
+    pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+        let object_id = self.object.resolve(&ctx)?;
+        // "HEAD:dir" is read from dir in the worktree
+        let worktree = match &self.object.path {
+            Some(path) => ctx.root_dir().join(path),
+            None => ctx.root_dir().to_path_buf(),
+        };
+
+        // one worktree path per distinct file, checked against the snapshot
+        let mut files = HashMap::new();
+        collect_files(&ctx, &worktree, &object_id, &mut files)?;
+        let mut files: Vec<_> = files.into_iter().collect();
+        files.sort_by(|a, b| a.1.cmp(&b.1));
+        for (file_id, path) in &files {
+            // the IDs of files in trees built with --xattr or --acl are not
+            // those of their contents, so such trees fail here too
+            if ObjectID::from_reader(fs::File::open(path)?)? != *file_id {
+                anyhow::bail!(
+                    "{} has changed since the snapshot, or the snapshot was built with --xattr or --acl",
+                    path.display()
+                );
+            }
+        }
+
+        let paths: Vec<_> = files.iter().map(|(_, path)| path.clone()).collect();
+        let shas = self.hash_objects(paths)?;
+        if shas.len() != files.len() {
+            anyhow::bail!(
+                "git hash-object returned {} of {} blobs",
+                shas.len(),
+                files.len()
+            );
+        }
+        let blobs = files.iter().map(|(id, _)| *id).zip(shas).collect();
+
+        let mut mktree = MkTree::spawn(&self.git_dir)?;
+        let mut trees = HashMap::new();
+        let sha = export_tree(&ctx, &object_id, &blobs, &mut trees, &mut mktree)?;
+        mktree.finish()?;
+
+        println!("{}", sha);
+        Ok(())
+    }
+
+    // write files as git blobs, returning their SHAs in the same order
+    fn hash_objects(&self, paths: Vec<PathBuf>) -> anyhow::Result<Vec<String>> {
+        let mut child = git(&self.git_dir)
+            .args(["hash-object", "-w", "--stdin-paths"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let writer = thread::spawn(move || -> io::Result<()> {
+            let mut stdin = BufWriter::new(stdin);
+            for path in paths {
+                writeln!(stdin, "{}", path.display())?;
+            }
+            stdin.flush()
+        });
+
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let shas = BufReader::new(stdout)
+            .lines()
+            .collect::<io::Result<Vec<_>>>()?;
+        writer.join().expect("writer thread panicked")?;
+        wait(child, "hash-object")?;
+        Ok(shas)
+    }
+}
+
+fn collect_files(
+    ctx: &Context,
+    parent: &Path,
+    object_id: &ObjectID,
+    files: &mut HashMap<ObjectID, PathBuf>,
+) -> anyhow::Result<()> {
+    for object in ctx.read_tree_contents(object_id)? {
+        let path = parent.join(&object.file_path);
+        match object.object_type {
+            ObjectType::Tree => collect_files(ctx, &path, &object.object_id, files)?,
+            ObjectType::File => {
+                files.entry(object.object_id).or_insert(path);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn export_tree(
+    ctx: &Context,
+    object_id: &ObjectID,
+    blobs: &HashMap<ObjectID, String>,
+    trees: &mut HashMap<ObjectID, String>,
+    mktree: &mut MkTree,
+) -> anyhow::Result<String> {
+    if let Some(sha) = trees.get(object_id) {
+        return Ok(sha.clone());
+    }
+
+    let mut entries = Vec::new();
+    for object in ctx.read_tree_contents(object_id)? {
+        let (mode, kind, sha) = match object.object_type {
+            ObjectType::Tree => {
+                let sha = export_tree(ctx, &object.object_id, blobs, trees, mktree)?;
+                (GIT_TREE_MODE, "tree", sha)
+            }
+            ObjectType::File => (GIT_FILE_MODE, "blob", blobs[&object.object_id].clone()),
+        };
+        entries.push(format!(
+            "{} {} {}\t{}",
+            mode,
+            kind,
+            sha,
+            object.file_path.display()
+        ));
+    }
+
+    let sha = mktree.write(&entries)?;
+    trees.insert(*object_id, sha.clone());
+    Ok(sha)
+}
+
+#[derive(Args, Debug)]
+pub struct ImportGitCommand {
+    /// Git revision whose tree is imported, e.g. "HEAD" or "v1.0:src"
+    #[clap(value_name = "git-rev")]
+    rev: String,
+
+    /// Git repository to read the objects from
+    #[clap(long, value_name = "path")]
+    git_dir: PathBuf,
+
+    /// Ref to save the imported tree as
+    #[clap(long, value_name = "ref-name", default_value = "git")]
+    save: String,
+}
+
+impl ImportGitCommand {
+    pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+        let _lock = ctx.lock()?;
+
+        let entries = self.list_tree()?;
+        let mut shas: Vec<_> = entries.iter().map(|(sha, _)| sha.clone()).collect();
+        shas.sort();
+        shas.dedup();
+        let blobs = self.hash_blobs(shas)?;
+
+        // only trees are written, as for a build; file IDs come from git's blobs
+        let mut builder = TreeBuilder::new();
+        for (sha, path) in &entries {
+            builder.insert(ObjectType::File, blobs[sha], path)?;
+        }
+        let object_id = builder.write(&ctx)?;
+
+        let previous = ctx.deref_object_ref(&self.save.as_str().into()).ok();
+        ctx.write_object_ref(&self.save, object_id)?;
+        ctx.audit(
+            &AuditEntry::new("import-git")
+                .reference(&self.save)
+                .object_id(&object_id)
+                .previous(previous),
+        )?;
+
+        println!("{}", object_id);
+        Ok(())
+    }
+
+    // blob SHAs and paths of the files below the revision's tree
+    fn list_tree(&self) -> anyhow::Result<Vec<(String, String)>> {
+        let output = git(&self.git_dir)
+            .args(["ls-tree", "-r", "-z", "--full-tree", &self.rev])
+            .stderr(Stdio::inherit())
+            .output()?;
+        if !output.status.success() {
+            anyhow::bail!("git ls-tree failed: {}", output.status);
+        }
+
+        let mut entries = Vec::new();
+        for line in output.stdout.split(|&b| b == 0).filter(|l| !l.is_empty()) {
+            let line = std::str::from_utf8(line)
+                .map_err(|_| anyhow::anyhow!("path is not UTF-8: {:?}", line))?;
+            // "<mode> <type> <sha>\t<path>"
+            let (info, path) = line
+                .split_once('\t')
+                .ok_or_else(|| anyhow::anyhow!("unexpected ls-tree output: {}", line))?;
+            let mut info = info.split(' ');
+            let (Some(mode), Some(kind), Some(sha)) = (info.next(), info.next(), info.next())
+            else {
+                anyhow::bail!("unexpected ls-tree output: {}", line);
+            };
+            match (mode, kind) {
+                ("100644" | "100755", "blob") => entries.push((sha.to_string(), path.to_string())),
+                // links and submodules have no contents of their own to hash
+                _ => log::warn!("ignored {} {}: {}", mode, kind, path),
+            }
+        }
+        Ok(entries)
+    }
+
+    // read blobs and hash their contents as mtl does
+    fn hash_blobs(&self, shas: Vec<String>) -> anyhow::Result<HashMap<String, ObjectID>> {
+        let mut child = git(&self.git_dir)
+            .args(["cat-file", "--batch"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let requests = shas.clone();
+        let writer = thread::spawn(move || -> io::Result<()> {
+            let mut stdin = BufWriter::new(stdin);
+            for sha in requests {
+                writeln!(stdin, "{}", sha)?;
+            }
+            stdin.flush()
+        });
+
+        let mut stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        let mut blobs = HashMap::new();
+        for sha in shas {
+            // "<sha> blob <size>\n<contents>\n"
+            let mut header = String::new();
+            stdout.read_line(&mut header)?;
+            let size = match header.trim_end().split(' ').collect::<Vec<_>>()[..] {
+                [_, "blob", size] => size.parse::<usize>()?,
+                _ => anyhow::bail!("cannot read blob {}: {}", sha, header.trim_end()),
+            };
+            let mut contents = vec![0; size + 1];
+            stdout.read_exact(&mut contents)?;
+            contents.truncate(size);
+            blobs.insert(sha, ObjectID::from_contents(&contents));
+        }
+        drop(stdout);
+        writer.join().expect("writer thread panicked")?;
+        wait(child, "cat-file")?;
+        Ok(blobs)
+    }
+}
+
+/// A `git mktree --batch` process, writing one tree per request.
+struct MkTree {
+    child: Child,
+    stdin: BufWriter<ChildStdin>,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl MkTree {
+    fn spawn(git_dir: &Path) -> io::Result<Self> {
+        let mut child = git(git_dir)
+            .args(["mktree", "--batch"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = BufWriter::new(child.stdin.take().expect("stdin is piped"));
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+        Ok(MkTree {
+            child,
+            stdin,
+            stdout,
+        })
+    }
+
+    fn write(&mut self, entries: &[String]) -> anyhow::Result<String> {
+        for entry in entries {
+            writeln!(self.stdin, "{}", entry)?;
+        }
+        // a blank line ends the tree
+        writeln!(self.stdin)?;
+        self.stdin.flush()?;
+
+        let mut sha = String::new();
+        if self.stdout.read_line(&mut sha)? == 0 {
+            anyhow::bail!("git mktree exited early");
+        }
+        Ok(sha.trim_end().to_string())
+    }
+
+    fn finish(self) -> anyhow::Result<()> {
+        drop(self.stdin);
+        wait(self.child, "mktree")
+    }
+}
+
+fn git(git_dir: &Path) -> Command {
+    let mut command = Command::new("git");
+    command.arg("--git-dir").arg(git_dir);
+    command
+}
+
+fn wait(mut child: Child, name: &str) -> anyhow::Result<()> {
+    let status = child.wait()?;
+    if !status.success() {
+        anyhow::bail!("git {} failed: {}", name, status);
+    }
+    Ok(())
+}
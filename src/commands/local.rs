@@ -1,55 +1,364 @@
+use std::collections::HashSet;
 use std::ffi::OsString;
-use std::path::PathBuf;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
 
 use clap::Args;
+use globset::Glob;
+use serde_json::json;
 
-use crate::builder::{Builder, FileTargetGenerator, ScanTargetGenerator, TargetGenerator};
-use crate::filter::{Filter, MatchAllFilter, PathFilter};
-use crate::Context;
+use crate::audit::AuditEntry;
+use crate::builder::{
+    Builder, FileTargetGenerator, ScanTargetGenerator, TargetGenerator, DEFAULT_MMAP_THRESHOLD,
+    DEFAULT_SCAN_QUEUE_SIZE,
+};
+#[cfg(feature = "sqlite")]
+use crate::builder::{SqliteTargetGenerator, DEFAULT_SQLITE_QUERY};
+use crate::config::Config;
+use crate::filter::{Filter, FilterBuilder, HiddenPolicy, SkipReport};
+use crate::hooks::Hook;
+use crate::output::{self, OutputFormat};
+use crate::patch;
+use crate::progress::{ProgressFormat, ProgressSink};
+use crate::repository::Change;
+use crate::summary::{BuildReport, BuildSummary};
+use crate::tree;
+use crate::xattr::{XattrSelector, ACL_XATTR};
+use crate::{check_ref_name, Context, FilterError, ObjectID};
 
 #[derive(Args, Debug)]
 pub struct Build {
     /// The input file containing a list of files to be scanned.
-    /// By default, it scans all files in the current directory.
+    /// By default, it scans all files in the current directory except those
+    /// ignored by .gitignore, .ignore or .mtlignore files.
     /// If you want to receive from standard input, specify "-".
-    #[clap(short, long, value_name = "input-file", verbatim_doc_comment)]
+    /// Paths are relative to the root directory; on Windows "\" separators
+    /// are accepted too.
+    /// Directories end with "/", or a line is "file<TAB>path" or "dir<TAB>path".
+    /// Parent directories that are not listed are added.
+    #[clap(
+        short,
+        long,
+        visible_alias = "files-from",
+        value_name = "input-file",
+        verbatim_doc_comment
+    )]
     input: Option<OsString>,
 
+    /// Read --input paths separated by NUL instead of newline, as written by
+    /// `find -print0`. Paths are taken as is, without kind annotations.
+    #[clap(
+        short = 'z',
+        long,
+        default_value_t = false,
+        requires = "input",
+        verbatim_doc_comment
+    )]
+    null: bool,
+
+    /// The SQLite database listing the files to be hashed, instead of scanning.
+    /// Paths are read with --sqlite-query. A `file:` URI is accepted too.
+    /// Defaults to the build.sqlite config, unless --input is given.
+    #[cfg(feature = "sqlite")]
+    #[clap(
+        long,
+        value_name = "database",
+        conflicts_with_all = ["input", "roots"],
+        verbatim_doc_comment
+    )]
+    sqlite: Option<String>,
+
+    /// The query returning the paths to hash, and optionally their kind
+    /// ("file" or "tree") as a second column. Defaults to the
+    /// build.sqlite-query config, then to "SELECT path FROM files".
+    #[cfg(feature = "sqlite")]
+    #[clap(long, value_name = "sql", verbatim_doc_comment)]
+    sqlite_query: Option<String>,
+
     /// If true, don't write the object ID of the root tree to HEAD.
     #[clap(short, long, default_value_t = false, verbatim_doc_comment)]
     no_write_head: bool,
 
-    /// If true, scan hidden files.
-    #[clap(long, default_value_t = false, verbatim_doc_comment)]
-    hidden: bool,
+    #[clap(flatten)]
+    hidden: HiddenArgs,
 
-    /// If true, show progress bar.
-    #[clap(long, default_value_t = false, verbatim_doc_comment)]
-    progress: bool,
+    #[clap(flatten)]
+    filters: FilterArgs,
+
+    /// Show the progress on stderr, as bars or with "--progress=json" as
+    /// JSON lines for other programs. [env: MTL_PROGRESS=1]
+    #[clap(
+        long,
+        value_name = "format",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "bar",
+        verbatim_doc_comment
+    )]
+    progress: Option<ProgressFormat>,
 
     /// If true, drop cache after reading files.
     #[clap(long, default_value_t = false, verbatim_doc_comment)]
     drop_cache: bool,
+
+    /// If true, hash files of at least --mmap-threshold bytes through a
+    /// memory map and drop them from the page cache afterwards.
+    #[clap(long, default_value_t = false, verbatim_doc_comment)]
+    mmap: bool,
+
+    /// Size in bytes from which --mmap maps files.
+    #[clap(long, value_name = "bytes", default_value_t = DEFAULT_MMAP_THRESHOLD, requires = "mmap", verbatim_doc_comment)]
+    mmap_threshold: u64,
+
+    #[clap(flatten)]
+    xattrs: XattrArgs,
+
+    /// If true, sync written objects to disk once per write batch.
+    #[clap(long, default_value_t = false, verbatim_doc_comment)]
+    fsync: bool,
+
+    /// Number of scanned entry batches buffered before the walker threads
+    /// wait for the collector.
+    #[clap(long, value_name = "batches", default_value_t = DEFAULT_SCAN_QUEUE_SIZE, verbatim_doc_comment)]
+    scan_queue_size: usize,
+
+    /// Build each of these directories instead of the repository root, all
+    /// at once, and write each root tree to its own ref rather than HEAD.
+    /// "dir=ref" names the ref, "dir" alone names it after the directory.
+    #[clap(
+        long = "root",
+        value_name = "dir[=ref]",
+        conflicts_with_all = ["input", "summary"],
+        verbatim_doc_comment
+    )]
+    roots: Vec<BuildRoot>,
+
+    /// If true, also record who built the root, where, and how long it took,
+    /// under a "build-<time>" ref. "build-<time>:root" is the built root and
+    /// "build-<time>:build.json" the metadata. Builds finishing within the
+    /// same second get "build-<time>-2", "build-<time>-3" and so on.
+    #[clap(long, default_value_t = false, verbatim_doc_comment)]
+    summary: bool,
+
+    /// Write a JSON report of the build to this file: the root, the IDs of
+    /// its entries, counts, bytes hashed, duration, and the entries skipped
+    /// by the filters with the reason.
+    #[clap(
+        long,
+        value_name = "path",
+        conflicts_with = "roots",
+        verbatim_doc_comment
+    )]
+    report: Option<PathBuf>,
 }
 
 impl Build {
     pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+        let start = Instant::now();
         let mut ctx = ctx;
         ctx.set_drop_cache(self.drop_cache);
+        ctx.set_mmap_threshold(self.mmap.then_some(self.mmap_threshold));
+        ctx.set_xattrs(self.xattrs.selector()?);
+        ctx.set_fsync(self.fsync);
+        let _lock = ctx.lock_shared()?;
+        if !self.roots.is_empty() {
+            return self.build_roots(&ctx);
+        }
 
-        let root_dir = ctx.root_dir().to_path_buf();
-        let generator = get_generator(root_dir, None, self.input.as_ref(), self.hidden);
-        let builder = Builder::new(generator, self.progress);
-        let object = builder.build(&ctx)?;
+        let filters = FilterArgs {
+            explain: self.report.is_some(),
+            ..self.filters.clone()
+        };
+        let generator = get_generator(
+            &ctx,
+            None,
+            &filters,
+            self.input.as_ref(),
+            self.null,
+            &self.hidden,
+            self.scan_queue_size,
+        )?;
+        #[cfg(feature = "sqlite")]
+        let generator = match self.sqlite_database(&ctx) {
+            Some(database) => {
+                let query = (self.sqlite_query.as_deref())
+                    .or(ctx.config().sqlite_query.as_deref())
+                    .unwrap_or(DEFAULT_SQLITE_QUERY);
+                Box::new(SqliteTargetGenerator::new(
+                    filters.filter(&ctx, None)?,
+                    database,
+                    query,
+                ))
+            }
+            None if self.sqlite_query.is_some() => {
+                anyhow::bail!("--sqlite-query needs --sqlite or the build.sqlite config")
+            }
+            None => generator,
+        };
+        let builder =
+            Builder::new(generator, false).with_progress_sink(progress_sink(self.progress, &ctx));
+        let (object, counts) = builder.build_counted(&ctx)?;
+        filters.write_report()?;
+        let entry = AuditEntry::new("build").object_id(&object.object_id);
+        let new = object.object_id.to_string();
         match self.no_write_head {
-            true => println!("HEAD: {}", object.object_id),
+            true => {
+                ctx.audit(&entry)?;
+                println!("HEAD: {}", object.object_id);
+                ctx.hooks()
+                    .notify_update(Hook::PostBuild, "", None, Some(&new));
+            }
             false => {
+                let previous = ctx.read_head().ok();
                 ctx.write_head(&object.object_id)?;
+                ctx.audit(&entry.reference("HEAD").previous(previous))?;
                 println!("Written HEAD: {}", object.object_id);
+                let old = previous.map(|id| id.to_string());
+                ctx.hooks()
+                    .notify_update(Hook::PostBuild, "HEAD", old.as_deref(), Some(&new));
             }
         }
+
+        if self.summary {
+            let summary =
+                BuildSummary::new(&object.object_id, counts, start.elapsed().as_secs_f64());
+            let tree_id = summary.write(&ctx)?;
+            let ref_name = summary.write_ref(&ctx, tree_id)?;
+            ctx.audit(
+                &AuditEntry::new("build-summary")
+                    .reference(&ref_name)
+                    .object_id(&tree_id),
+            )?;
+            println!("Written {}: {}", ref_name, tree_id);
+        }
+
+        if let Some(path) = &self.report {
+            let duration_secs = start.elapsed().as_secs_f64();
+            let report = BuildReport::new(
+                &ctx,
+                &object.object_id,
+                counts,
+                duration_secs,
+                &filters.report,
+            )?;
+            let mut file = BufWriter::new(File::create(path)?);
+            serde_json::to_writer_pretty(&mut file, &report)?;
+            writeln!(file)?;
+            file.flush()?;
+        }
         Ok(())
     }
+
+    // --sqlite, or the configured database unless the paths are given by
+    // --input
+    #[cfg(feature = "sqlite")]
+    fn sqlite_database<'a>(&'a self, ctx: &'a Context) -> Option<&'a str> {
+        match (&self.sqlite, &self.input) {
+            (Some(database), _) => Some(database),
+            (None, None) => ctx.config().sqlite.as_deref(),
+            (None, Some(_)) => None,
+        }
+    }
+
+    // Each root is built on its own thread, and they all hash on the one
+    // rayon pool, so the walk of one root overlaps with hashing another.
+    fn build_roots(&self, ctx: &Context) -> anyhow::Result<()> {
+        let mut ref_names = HashSet::new();
+        for root in &self.roots {
+            if !ref_names.insert(&root.ref_name) {
+                anyhow::bail!("ref \"{}\" is given to more than one --root", root.ref_name);
+            }
+        }
+
+        let results = thread::scope(|s| {
+            let handles = self
+                .roots
+                .iter()
+                .map(|root| s.spawn(move || self.build_root(ctx, root)))
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        // no ref is written unless every root built
+        let mut object_ids = Vec::new();
+        for (root, result) in self.roots.iter().zip(results) {
+            match result {
+                Ok(object_id) => object_ids.push(object_id),
+                Err(e) => anyhow::bail!("{}: {}", root.dir.display(), e),
+            }
+        }
+        self.filters.write_report()?;
+
+        for (root, object_id) in self.roots.iter().zip(object_ids) {
+            let previous = ctx.deref_object_ref(&root.ref_name.as_str().into()).ok();
+            ctx.write_object_ref(&root.ref_name, object_id)?;
+            ctx.audit(
+                &AuditEntry::new("build")
+                    .reference(&root.ref_name)
+                    .object_id(&object_id)
+                    .previous(previous),
+            )?;
+            println!("Written {}: {}", root.ref_name, object_id);
+            ctx.hooks().notify_update(
+                Hook::PostBuild,
+                &root.ref_name,
+                previous.map(|id| id.to_string()).as_deref(),
+                Some(&object_id.to_string()),
+            );
+        }
+        Ok(())
+    }
+
+    fn build_root(&self, ctx: &Context, root: &BuildRoot) -> anyhow::Result<ObjectID> {
+        let ctx = ctx.for_root(root.dir.canonicalize()?);
+        let generator = get_generator(
+            &ctx,
+            None,
+            &self.filters,
+            None,
+            false,
+            &self.hidden,
+            self.scan_queue_size,
+        )?;
+        // progress bars of roots built at once would overwrite each other
+        let builder = Builder::new(generator, false);
+        Ok(builder.build(&ctx)?.object_id)
+    }
+}
+
+/// A directory for `local build --root`, and the ref its tree is written to.
+#[derive(Debug, Clone)]
+pub struct BuildRoot {
+    dir: PathBuf,
+    ref_name: String,
+}
+
+impl FromStr for BuildRoot {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (dir, ref_name) = match s.rsplit_once('=') {
+            Some((dir, ref_name)) => (PathBuf::from(dir), ref_name.to_string()),
+            None => {
+                let dir = PathBuf::from(s);
+                let ref_name = dir
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .ok_or_else(|| format!("cannot name a ref after \"{}\", use {}=<ref>", s, s))?
+                    .to_string();
+                (dir, ref_name)
+            }
+        };
+        check_ref_name(&ref_name).map_err(|e| e.to_string())?;
+        Ok(BuildRoot { dir, ref_name })
+    }
 }
 
 #[derive(Args, Debug)]
@@ -58,18 +367,46 @@ pub struct Update {
     #[clap(short, long, default_value_t = false, verbatim_doc_comment)]
     no_write_head: bool,
 
-    /// If true, scan hidden files.
-    #[clap(long, default_value_t = false, verbatim_doc_comment)]
-    hidden: bool,
+    #[clap(flatten)]
+    hidden: HiddenArgs,
 
-    /// If true, show progress bar.
-    #[clap(long, default_value_t = false, verbatim_doc_comment)]
-    progress: bool,
+    /// Show the progress on stderr, as bars or with "--progress=json" as
+    /// JSON lines for other programs. [env: MTL_PROGRESS=1]
+    #[clap(
+        long,
+        value_name = "format",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "bar",
+        verbatim_doc_comment
+    )]
+    progress: Option<ProgressFormat>,
 
     /// If true, drop cache after reading files.
     #[clap(long, default_value_t = false, verbatim_doc_comment)]
     drop_cache: bool,
 
+    /// If true, hash files of at least --mmap-threshold bytes through a
+    /// memory map and drop them from the page cache afterwards.
+    #[clap(long, default_value_t = false, verbatim_doc_comment)]
+    mmap: bool,
+
+    /// Size in bytes from which --mmap maps files.
+    #[clap(long, value_name = "bytes", default_value_t = DEFAULT_MMAP_THRESHOLD, requires = "mmap", verbatim_doc_comment)]
+    mmap_threshold: u64,
+
+    #[clap(flatten)]
+    xattrs: XattrArgs,
+
+    /// If true, sync written objects to disk once per write batch.
+    #[clap(long, default_value_t = false, verbatim_doc_comment)]
+    fsync: bool,
+
+    /// Number of scanned entry batches buffered before the walker threads
+    /// wait for the collector.
+    #[clap(long, value_name = "batches", default_value_t = DEFAULT_SCAN_QUEUE_SIZE, verbatim_doc_comment)]
+    scan_queue_size: usize,
+
     path: PathBuf,
 }
 
@@ -77,15 +414,33 @@ impl Update {
     pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
         let mut ctx = ctx;
         ctx.set_drop_cache(self.drop_cache);
+        ctx.set_mmap_threshold(self.mmap.then_some(self.mmap_threshold));
+        ctx.set_xattrs(self.xattrs.selector()?);
+        ctx.set_fsync(self.fsync);
+        let _lock = ctx.lock()?;
 
-        let root_dir = ctx.root_dir().to_path_buf();
-        let generator = get_generator(root_dir, Some(&self.path), None, self.hidden);
-        let builder = Builder::new(generator, self.progress);
+        let generator = get_generator(
+            &ctx,
+            Some(&self.path),
+            &FilterArgs::default(),
+            None,
+            false,
+            &self.hidden,
+            self.scan_queue_size,
+        )?;
+        let builder =
+            Builder::new(generator, false).with_progress_sink(progress_sink(self.progress, &ctx));
         let root = builder.update(&ctx, &self.path)?;
+        let entry = AuditEntry::new("update").object_id(&root.object_id);
         match self.no_write_head {
-            true => println!("HEAD: {}", root.object_id),
+            true => {
+                ctx.audit(&entry)?;
+                println!("HEAD: {}", root.object_id);
+            }
             false => {
+                let previous = ctx.read_head().ok();
                 ctx.write_head(&root.object_id)?;
+                ctx.audit(&entry.reference("HEAD").previous(previous))?;
                 println!("Written HEAD: {}", root.object_id);
             }
         }
@@ -97,51 +452,344 @@ impl Update {
 #[derive(Args, Debug)]
 pub struct List {
     /// The input file containing a list of files to be scanned.
-    /// By default, it scans all files in the current directory.
+    /// By default, it scans all files in the current directory except those
+    /// ignored by .gitignore, .ignore or .mtlignore files.
     /// If you want to receive from standard input, specify "-".
-    #[clap(short, long, value_name = "input-file", verbatim_doc_comment)]
+    #[clap(
+        short,
+        long,
+        visible_alias = "files-from",
+        value_name = "input-file",
+        verbatim_doc_comment
+    )]
     input: Option<OsString>,
 
-    /// If true, scan hidden files.
+    /// Separate paths with NUL instead of newline, both in --input and in
+    /// the output.
+    #[clap(short = 'z', long, default_value_t = false, verbatim_doc_comment)]
+    null: bool,
+
+    #[clap(flatten)]
+    hidden: HiddenArgs,
+
+    #[clap(flatten)]
+    filters: FilterArgs,
+
+    /// If true, print to stderr the directories skipped by the filters, as
+    /// "pruned<TAB>reason<TAB>path/" lines, and then the number of entries
+    /// skipped for each reason, as "skipped<TAB>reason<TAB>count" lines.
+    /// Paths hidden by .gitignore and friends are not included.
     #[clap(long, default_value_t = false, verbatim_doc_comment)]
-    hidden: bool,
+    explain: bool,
+
+    /// Number of scanned entry batches buffered before the walker threads
+    /// wait for the collector.
+    #[clap(long, value_name = "batches", default_value_t = DEFAULT_SCAN_QUEUE_SIZE, verbatim_doc_comment)]
+    scan_queue_size: usize,
 
     path: Option<PathBuf>,
 }
 
 impl List {
     pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
-        let root_dir = ctx.root_dir().to_path_buf();
+        let filters = FilterArgs {
+            explain: self.explain,
+            ..self.filters.clone()
+        };
         let generator = get_generator(
-            root_dir,
+            &ctx,
             self.path.as_ref(),
+            &filters,
             self.input.as_ref(),
-            self.hidden,
-        );
+            self.null,
+            &self.hidden,
+            self.scan_queue_size,
+        )?;
         let target_entries = generator.generate(&ctx)?;
+        filters.write_report()?;
+        let end = if self.null { '\0' } else { '\n' };
+        let mut stdout = BufWriter::new(io::stdout().lock());
         for file in target_entries.iter() {
-            if file.path.is_root() {
-                println!("{} .", file.mode);
-                continue;
+            let path = match file.path.is_root() {
+                true => Path::new("."),
+                false => file.path.as_path(),
+            };
+            write!(
+                stdout,
+                "{} {}{}",
+                file.mode,
+                ctx.render_path(path).display(),
+                end
+            )?;
+        }
+        stdout.flush()?;
+
+        if self.explain {
+            let mut stderr = io::stderr().lock();
+            for entry in filters.report.entries() {
+                if entry.is_dir {
+                    writeln!(stderr, "pruned\t{}\t{}/", entry.reason, entry.path)?;
+                }
+            }
+            for (reason, count) in filters.report.counts() {
+                writeln!(stderr, "skipped\t{}\t{}", reason, count)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct Status {
+    /// Print "X path" entries separated by NUL, with paths unescaped, where X
+    /// is A (added), M (modified) or D (deleted). The format will stay stable
+    /// for scripts and editor plugins to parse.
+    #[clap(long, default_value_t = false, verbatim_doc_comment)]
+    porcelain: bool,
+
+    #[clap(flatten)]
+    hidden: HiddenArgs,
+
+    #[clap(flatten)]
+    filters: FilterArgs,
+
+    #[clap(flatten)]
+    xattrs: XattrArgs,
+
+    /// Number of scanned entry batches buffered before the walker threads
+    /// wait for the collector.
+    #[clap(long, value_name = "batches", default_value_t = DEFAULT_SCAN_QUEUE_SIZE, verbatim_doc_comment)]
+    scan_queue_size: usize,
+}
+
+impl Status {
+    pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+        let mut ctx = ctx;
+        ctx.set_xattrs(self.xattrs.selector()?);
+        let _lock = ctx.lock_shared()?;
+        let head = ctx.read_head()?;
+
+        let generator = get_generator(
+            &ctx,
+            None,
+            &self.filters,
+            None,
+            false,
+            &self.hidden,
+            self.scan_queue_size,
+        )?;
+        // the new trees are kept in memory, and the unchanged subtrees have
+        // the IDs of those under HEAD, so they are skipped without being read
+        let scratch = ctx.scratch();
+        let root = Builder::new(generator, false).build(&scratch)?;
+        self.filters.write_report()?;
+
+        let mut stdout = BufWriter::new(io::stdout().lock());
+        for entry in patch::diff(&scratch, &head, &root.object_id)? {
+            let status = Change::from(entry.op).code();
+            let path = ctx.render_path(&entry.path);
+            match (ctx.output_format(), self.porcelain) {
+                (OutputFormat::Json, _) => output::write_json(
+                    &mut stdout,
+                    &json!({
+                        "change": status.to_string(),
+                        "type": entry.object_type.to_string(),
+                        "path": path.to_string_lossy(),
+                    }),
+                )?,
+                (_, true) => write!(stdout, "{} {}\0", status, path.display())?,
+                (_, false) => writeln!(
+                    stdout,
+                    "{} {}",
+                    status,
+                    tree::escape_path(&path.to_string_lossy())
+                )?,
+            }
+        }
+        stdout.flush()?;
+        Ok(())
+    }
+}
+
+#[derive(Args, Debug, Default, Clone)]
+struct FilterArgs {
+    /// Only take files matching this glob, e.g. "**/*.bin". Repeatable.
+    #[clap(long, value_name = "glob")]
+    include: Vec<Glob>,
+
+    /// Skip files and directories matching this glob, e.g. "**/tmp/**". Repeatable.
+    #[clap(long, value_name = "glob")]
+    exclude: Vec<Glob>,
+
+    /// Skip paths matched by this file in gitignore syntax, also for --input.
+    /// Repeatable.
+    #[clap(long, value_name = "path", verbatim_doc_comment)]
+    ignore_file: Vec<PathBuf>,
+
+    /// Only take files of at least this many bytes
+    #[clap(long, alias = "min-size", value_name = "bytes")]
+    min_file_size: Option<u64>,
+
+    /// Only take files of at most this many bytes
+    #[clap(long, alias = "max-size", value_name = "bytes")]
+    max_file_size: Option<u64>,
+
+    /// Skip FIFOs, sockets, devices and symlinks, also for --input
+    #[clap(long, default_value_t = false)]
+    skip_special: bool,
+
+    /// Write the files skipped by size or type to this file, as
+    /// "reason<TAB>path" lines
+    #[clap(long, value_name = "path", verbatim_doc_comment)]
+    skip_report: Option<PathBuf>,
+
+    #[clap(skip)]
+    report: Arc<SkipReport>,
+
+    // whether the report also takes the entries skipped by globs and
+    // ignore files
+    #[clap(skip)]
+    explain: bool,
+}
+
+impl FilterArgs {
+    fn filter(&self, ctx: &Context, path: Option<&PathBuf>) -> anyhow::Result<Box<dyn Filter>> {
+        let root_dir = ctx.root_dir();
+        let mut builder = FilterBuilder::new(root_dir)
+            .with_globs(&self.include, &self.exclude)
+            .with_size_range(self.min_file_size, self.max_file_size)
+            .with_skip_special(self.skip_special);
+        if let Some(path) = path {
+            builder = builder.with_path(path);
+        }
+        for file in &self.ignore_file {
+            builder = builder.with_ignore_file(file);
+        }
+        // like .mtlignore, the scanned root may or may not have one
+        if let Some(file) = &ctx.config().ignore_file {
+            let file = root_dir.join(file);
+            if file.is_file() {
+                builder = builder.with_ignore_file(file);
             }
-            println!("{} {}", file.mode, file.path);
+        }
+        if self.explain {
+            builder = builder.with_full_report(self.report.clone());
+        } else if self.skip_report.is_some() {
+            builder = builder.with_report(self.report.clone());
+        }
+        Ok(builder.build()?)
+    }
+
+    fn write_report(&self) -> anyhow::Result<()> {
+        if let Some(skip_report) = &self.skip_report {
+            let mut file = BufWriter::new(File::create(skip_report)?);
+            self.report.write(&mut file)?;
+            file.flush()?;
         }
         Ok(())
     }
 }
 
+/// The attributes counted towards file IDs. local status must be given the
+/// ones HEAD was built with, or it reports every file having them as
+/// modified.
+#[derive(Args, Debug, Default)]
+struct XattrArgs {
+    /// Extended attributes of files to count towards their IDs, as glob
+    /// patterns like "user.*" or "security.selinux". Can be given multiple times.
+    /// Directories are not fingerprinted, and export-git cannot export the
+    /// resulting trees.
+    #[clap(long, value_name = "pattern", verbatim_doc_comment)]
+    xattr: Vec<String>,
+
+    /// If true, count the POSIX access ACLs of files towards their IDs.
+    #[clap(long, default_value_t = false, verbatim_doc_comment)]
+    acl: bool,
+}
+
+impl XattrArgs {
+    // None unless any attributes are asked for, so that plain builds skip them
+    fn selector(&self) -> Result<Option<XattrSelector>, FilterError> {
+        let mut patterns = self.xattr.clone();
+        if self.acl {
+            patterns.push(ACL_XATTR.to_string());
+        }
+        match patterns.is_empty() {
+            true => Ok(None),
+            false => Ok(Some(XattrSelector::new(&patterns)?)),
+        }
+    }
+}
+
+#[derive(Args, Debug, Default)]
+struct HiddenArgs {
+    /// If true, scan hidden files. [config: build.hidden]
+    #[clap(
+        long,
+        default_value_t = false,
+        overrides_with = "no_hidden",
+        verbatim_doc_comment
+    )]
+    hidden: bool,
+
+    /// Skip hidden files, whatever the config files say
+    #[clap(long, default_value_t = false, overrides_with = "hidden")]
+    no_hidden: bool,
+
+    /// Always scan the hidden files and directories matching this glob, e.g.
+    /// "**/.well-known". Repeatable.
+    #[clap(long, value_name = "glob", verbatim_doc_comment)]
+    include_hidden: Vec<Glob>,
+
+    /// Never scan the hidden files and directories matching this glob, even
+    /// with --hidden, e.g. "**/.cache". Repeatable.
+    #[clap(long, value_name = "glob", verbatim_doc_comment)]
+    exclude_hidden: Vec<Glob>,
+}
+
+impl HiddenArgs {
+    // the command line over the config files
+    fn hidden(&self, config: &Config) -> bool {
+        match (self.hidden, self.no_hidden) {
+            (true, _) => true,
+            (_, true) => false,
+            _ => config.hidden.unwrap_or(false),
+        }
+    }
+
+    fn policy(&self, config: &Config) -> Result<HiddenPolicy, FilterError> {
+        Ok(HiddenPolicy::new(
+            self.hidden(config),
+            &self.include_hidden,
+            &self.exclude_hidden,
+        )?)
+    }
+}
+
+// the option, or bars if MTL_PROGRESS asks for them
+fn progress_sink(format: Option<ProgressFormat>, ctx: &Context) -> Arc<dyn ProgressSink> {
+    ProgressFormat::sink(format.or(ctx.progress().then_some(ProgressFormat::Bar)))
+}
+
 fn get_generator(
-    root_dir: PathBuf,
+    ctx: &Context,
     path: Option<&PathBuf>,
+    filters: &FilterArgs,
     input: Option<&OsString>,
-    hidden: bool,
-) -> Box<dyn TargetGenerator> {
-    let filter: Box<dyn Filter> = match path {
-        Some(path) => Box::new(PathFilter::new(root_dir, path)),
-        None => Box::new(MatchAllFilter::new(root_dir)),
-    };
-    match input {
-        Some(input) => Box::new(FileTargetGenerator::new(filter, input.to_os_string())),
-        None => Box::new(ScanTargetGenerator::new(filter, hidden)),
-    }
+    null_separated: bool,
+    hidden: &HiddenArgs,
+    scan_queue_size: usize,
+) -> anyhow::Result<Box<dyn TargetGenerator>> {
+    let filter = filters.filter(ctx, path)?;
+    Ok(match input {
+        Some(input) => Box::new(
+            FileTargetGenerator::new(filter, input.to_os_string())
+                .with_null_separated(null_separated),
+        ),
+        None => Box::new(
+            ScanTargetGenerator::new(filter, hidden.hidden(ctx.config()))
+                .with_hidden_policy(hidden.policy(ctx.config())?)
+                .with_queue_size(scan_queue_size),
+        ),
+    })
 }
@@ -0,0 +1,188 @@
+//! Low-level commands for scripts.
+//!
+//! Their input and output formats are fixed: no colors, no progress and no
+//! messages on success, so that they can be composed safely.
+
+use std::fs;
+use std::io::{self, BufRead, BufWriter, Read, Write};
+use std::path::PathBuf;
+
+use clap::Args;
+
+use crate::audit::AuditEntry;
+use crate::tree::{self, TreeBuilder};
+use crate::{Context, ObjectExpr, ObjectID, ObjectRef, ObjectType, NULL_OBJECT_ID};
+
+#[derive(Args, Debug)]
+pub struct UpdateRef {
+    /// "HEAD" or a ref name
+    #[clap(value_name = "ref")]
+    reference: String,
+
+    /// Object ID to set. Omit with --delete.
+    #[clap(value_name = "new-object-id", required_unless_present = "delete")]
+    new: Option<ObjectID>,
+
+    /// Only update if the ref currently has this value.
+    /// 0000000000000000 requires that the ref does not exist.
+    #[clap(long, value_name = "old-object-id", verbatim_doc_comment)]
+    old: Option<String>,
+
+    /// Delete the ref instead of updating it
+    #[clap(short, long, default_value_t = false, conflicts_with = "new")]
+    delete: bool,
+}
+
+impl UpdateRef {
+    pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+        let _lock = ctx.lock()?;
+
+        let current = ctx
+            .deref_object_ref(&ObjectRef::new_reference(&self.reference))
+            .ok();
+        if let Some(old) = &self.old {
+            let expected = match old.as_str() {
+                NULL_OBJECT_ID => None,
+                old => Some(old.parse::<ObjectID>()?),
+            };
+            if current != expected {
+                anyhow::bail!(
+                    "{}: expected {} but it is {}",
+                    self.reference,
+                    expected.map_or("missing".to_string(), |id| id.to_string()),
+                    current.map_or("missing".to_string(), |id| id.to_string()),
+                );
+            }
+        }
+
+        let entry = AuditEntry::new("update-ref")
+            .reference(&self.reference)
+            .previous(current);
+        match (self.delete, &self.new) {
+            (true, _) => {
+                if self.reference == "HEAD" {
+                    anyhow::bail!("HEAD cannot be deleted");
+                }
+                ctx.delete_object_ref(&self.reference)?;
+                ctx.audit(&entry)?;
+            }
+            (false, Some(new)) => {
+                match self.reference.as_str() {
+                    "HEAD" => ctx.write_head(new)?,
+                    reference => ctx.write_object_ref(reference, *new)?,
+                }
+                ctx.audit(&entry.object_id(new))?;
+            }
+            (false, None) => unreachable!("clap requires the new object ID"),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct HashObject {
+    /// Files to hash. Reads standard input if none are given.
+    #[clap(value_name = "file")]
+    files: Vec<PathBuf>,
+
+    /// Also store the contents in the object store as a blob
+    #[clap(short, default_value_t = false)]
+    write: bool,
+}
+
+impl HashObject {
+    pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+        let _lock = match self.write {
+            true => Some(ctx.lock()?),
+            false => None,
+        };
+
+        if self.files.is_empty() {
+            let mut contents = Vec::new();
+            io::stdin().read_to_end(&mut contents)?;
+            println!("{}", self.hash_object(&ctx, &contents)?);
+            return Ok(());
+        }
+        for file in &self.files {
+            let contents = fs::read(file)?;
+            println!("{}", self.hash_object(&ctx, &contents)?);
+        }
+        Ok(())
+    }
+
+    fn hash_object(&self, ctx: &Context, contents: &[u8]) -> io::Result<ObjectID> {
+        let object_id = ObjectID::from_contents(contents);
+        if self.write {
+            ctx.store().put(&object_id, ObjectType::File, contents)?;
+        }
+        Ok(object_id)
+    }
+}
+
+/// Reads `kind<TAB>id<TAB>path` lines from standard input and prints the ID
+/// of the resulting root tree. Intermediate trees are created as needed.
+/// Paths are escaped as read-tree prints them. The `kind id<TAB>path` lines
+/// printed by print-tree are accepted as well.
+#[derive(Args, Debug)]
+pub struct WriteTree {}
+
+impl WriteTree {
+    pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+        let _lock = ctx.lock()?;
+
+        let mut builder = TreeBuilder::new();
+        for (n, line) in io::stdin().lock().lines().enumerate() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            parse_entry(&line)
+                .and_then(|(object_type, object_id, path)| {
+                    Ok(builder.insert(object_type, object_id, path)?)
+                })
+                .map_err(|e| anyhow::anyhow!("line {}: {}", n + 1, e))?;
+        }
+        println!("{}", builder.write(&ctx)?);
+        Ok(())
+    }
+}
+
+fn parse_entry(line: &str) -> anyhow::Result<(ObjectType, ObjectID, String)> {
+    let invalid = || anyhow::anyhow!("invalid entry: {:?}", line);
+    let (kind, rest) = line.split_once(['\t', ' ']).ok_or_else(invalid)?;
+    let (object_id, path) = rest.split_once('\t').ok_or_else(invalid)?;
+    Ok((
+        kind.parse()?,
+        object_id.parse()?,
+        tree::unescape_path(path)?,
+    ))
+}
+
+/// Prints every file and empty tree below a tree as `kind<TAB>id<TAB>path`
+/// lines, the format write-tree reads. Backslashes, tabs and newlines in
+/// paths are escaped as `\\`, `\t` and `\n`.
+#[derive(Args, Debug)]
+pub struct ReadTree {
+    #[clap(value_name = "object")]
+    object: ObjectExpr,
+}
+
+impl ReadTree {
+    pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+        let object_id = self.object.resolve(&ctx)?;
+
+        let stdout = io::stdout();
+        let mut stdout = BufWriter::new(stdout.lock());
+        for entry in tree::flatten(&ctx, &object_id)? {
+            writeln!(
+                stdout,
+                "{}\t{}\t{}",
+                entry.object_type,
+                entry.object_id,
+                tree::escape_path(&entry.path.to_string_lossy()),
+            )?;
+        }
+        stdout.flush()?;
+        Ok(())
+    }
+}
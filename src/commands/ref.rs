@@ -1,5 +1,8 @@
 use clap::Args;
+use serde_json::json;
 
+use crate::audit::AuditEntry;
+use crate::output::{self, OutputFormat};
 use crate::{Context, ObjectExpr};
 
 #[derive(Args, Debug)]
@@ -10,7 +13,13 @@ impl List {
         let refs = ctx.list_object_refs()?;
         for object_ref in refs {
             let object_id = ctx.deref_object_ref(&object_ref)?;
-            println!("{}\t{}", object_ref, object_id);
+            match ctx.output_format() {
+                OutputFormat::Text => println!("{}\t{}", object_ref, object_id),
+                OutputFormat::Json => output::print_json(&json!({
+                    "name": object_ref.to_string(),
+                    "id": object_id.to_string(),
+                }))?,
+            }
         }
         Ok(())
     }
@@ -27,12 +36,20 @@ pub struct Save {
 
 impl Save {
     pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+        let _lock = ctx.lock()?;
         let object_id = match self.object_id {
             Some(ref object_id) => object_id.resolve(&ctx)?,
             None => ctx.read_head()?,
         };
 
+        let previous = ctx.deref_object_ref(&self.ref_name.as_str().into()).ok();
         ctx.write_object_ref(&self.ref_name, object_id)?;
+        ctx.audit(
+            &AuditEntry::new("ref-save")
+                .reference(&self.ref_name)
+                .object_id(&object_id)
+                .previous(previous),
+        )?;
         println!("Save \"{}\" to \"{}\"", object_id, self.ref_name);
         Ok(())
     }
@@ -46,7 +63,14 @@ pub struct Delete {
 
 impl Delete {
     pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+        let _lock = ctx.lock()?;
+        let previous = ctx.deref_object_ref(&self.ref_name.as_str().into()).ok();
         ctx.delete_object_ref(&self.ref_name)?;
+        ctx.audit(
+            &AuditEntry::new("ref-delete")
+                .reference(&self.ref_name)
+                .previous(previous),
+        )?;
         println!("\"{}\" deleted", self.ref_name);
         Ok(())
     }
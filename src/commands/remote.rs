@@ -0,0 +1,65 @@
+use anyhow::bail;
+use clap::Args;
+use serde_json::json;
+
+use crate::config::{self, ConfigFile};
+use crate::output::{self, OutputFormat};
+use crate::Context;
+
+#[derive(Args, Debug)]
+pub struct Add {
+    /// Name to refer to the remote by, e.g. "origin"
+    name: String,
+
+    /// ssh://, http://, https:// or s3:// URL of the remote
+    url: String,
+}
+
+impl Add {
+    pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+        if !config::is_remote_name(&self.name) {
+            bail!("invalid remote name \"{}\"", self.name);
+        }
+        let path = ctx.config_file();
+        let mut file = ConfigFile::read(&path)?;
+        let key = config::remote_key(&self.name);
+        if file.get(&key).is_some() {
+            bail!("remote {} already exists", self.name);
+        }
+        file.set(&key, &self.url)?;
+        file.write(&path)?;
+        Ok(())
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct List {}
+
+impl List {
+    pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+        for (name, url) in &ctx.config().remotes {
+            match ctx.output_format() {
+                OutputFormat::Text => println!("{}\t{}", name, url),
+                OutputFormat::Json => output::print_json(&json!({"name": name, "url": url}))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct Remove {
+    name: String,
+}
+
+impl Remove {
+    pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
+        let path = ctx.config_file();
+        let mut file = ConfigFile::read(&path)?;
+        if !file.remove(&config::remote_key(&self.name)) {
+            bail!("no remote {} in {}", self.name, path.display());
+        }
+        file.write(&path)?;
+        Ok(())
+    }
+}
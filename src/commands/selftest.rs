@@ -0,0 +1,204 @@
+//! End-to-end check of an installation against a scratch repository.
+
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::{env, fs};
+
+use clap::Args;
+
+use crate::{Context, ObjectID, ObjectType};
+
+#[derive(Args, Debug)]
+pub struct SelftestCommand {
+    /// Directory to create the scratch repository in, on the filesystem to
+    /// validate. Defaults to the system temporary directory.
+    #[clap(long, value_name = "path", verbatim_doc_comment)]
+    dir: Option<PathBuf>,
+
+    /// Number of files to generate
+    #[clap(long, value_name = "n", default_value_t = 100)]
+    files: usize,
+
+    /// Keep the scratch repository for inspection
+    #[clap(long, default_value_t = false)]
+    keep: bool,
+}
+
+impl SelftestCommand {
+    pub fn run(&self) -> anyhow::Result<()> {
+        let parent = self.dir.clone().unwrap_or_else(env::temp_dir);
+        let repo = parent.join(format!("mtl-selftest-{}", process::id()));
+        fs::create_dir(&repo)?;
+        // the commands run from inside the repository
+        let repo = repo.canonicalize()?;
+
+        // the commands run as separate processes of this binary, as a user would
+        let selftest = Selftest {
+            exe: env::current_exe()?,
+            repo: repo.clone(),
+        };
+        let result = selftest.run(self.files);
+
+        if self.keep {
+            println!("Kept {}", repo.display());
+        } else {
+            fs::remove_dir_all(&repo)?;
+        }
+        match result {
+            Ok(()) => {
+                println!("PASS");
+                Ok(())
+            }
+            Err(_) => anyhow::bail!("selftest failed"),
+        }
+    }
+}
+
+struct Selftest {
+    exe: PathBuf,
+    repo: PathBuf,
+}
+
+impl Selftest {
+    fn run(&self, files: usize) -> anyhow::Result<()> {
+        step("generate", || {
+            let files = files.to_string();
+            let args = ["tool", "generate", "data", files.as_str()];
+            self.mtl(
+                args.iter()
+                    .chain(&["--num-kilobytes", "4", "--prefix-bytes", "1"]),
+            )?;
+            Ok(())
+        })?;
+
+        let (first, first_tree) = step("build", || {
+            let first = self.build()?;
+            self.mtl(["ref", "save", "first"])?;
+            // a second build of the same files gives the same tree
+            if self.build()? != first {
+                anyhow::bail!("rebuilding unchanged files gave another tree");
+            }
+            Ok((first, self.mtl(["print-tree", "-r", "first"])?))
+        })?;
+
+        step("diff", || {
+            fs::write(self.repo.join("data/changed"), "changed\n")?;
+            let second = self.build()?;
+            if second == first {
+                anyhow::bail!("adding a file did not change the tree");
+            }
+            let (differs, output) = self.diff("first", "HEAD")?;
+            if !differs || !output.contains("data/changed") {
+                anyhow::bail!("diff did not report the added file:\n{}", output);
+            }
+            if self.diff("first", "first")?.0 {
+                anyhow::bail!("a tree differs from itself");
+            }
+            Ok(())
+        })?;
+
+        step("pack", || {
+            self.mtl(["pack"])?;
+            self.check_tree("first", &first_tree)
+        })?;
+
+        step("gc", || {
+            // back to the first tree, which leaves the second unreachable
+            fs::remove_file(self.repo.join("data/changed"))?;
+            if self.build()? != first {
+                anyhow::bail!("removing the added file did not restore the tree");
+            }
+            let output = self.mtl(["gc"])?;
+            if output.contains("Deleted 0 objects") {
+                anyhow::bail!("nothing was collected:\n{}", output);
+            }
+            self.check_tree("first", &first_tree)
+        })?;
+
+        step("fsck", || {
+            let ctx = Context::new(&self.repo)?;
+            verify(&ctx, &self.repo, &ctx.read_head()?)
+        })?;
+        Ok(())
+    }
+
+    fn mtl<I, S>(&self, args: I) -> anyhow::Result<String>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let output = self.command().args(args).output()?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "{}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            );
+        }
+        Ok(String::from_utf8(output.stdout)?)
+    }
+
+    fn command(&self) -> Command {
+        let mut command = Command::new(&self.exe);
+        command.arg("--dir").arg(&self.repo).current_dir(&self.repo);
+        command
+    }
+
+    fn build(&self) -> anyhow::Result<ObjectID> {
+        self.mtl(["local", "build"])?;
+        Ok(self.mtl(["rev-parse", "HEAD"])?.trim().parse()?)
+    }
+
+    // whether the trees differ, by the exit status, and the printed diff
+    fn diff(&self, a: &str, b: &str) -> anyhow::Result<(bool, String)> {
+        let output = self.command().args(["diff", a, b]).output()?;
+        match output.status.code() {
+            Some(0) => Ok((false, String::from_utf8(output.stdout)?)),
+            Some(1) => Ok((true, String::from_utf8(output.stdout)?)),
+            _ => anyhow::bail!(
+                "{}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            ),
+        }
+    }
+
+    fn check_tree(&self, object: &str, expected: &str) -> anyhow::Result<()> {
+        if self.mtl(["print-tree", "-r", object])? != expected {
+            anyhow::bail!("{} reads back differently", object);
+        }
+        Ok(())
+    }
+}
+
+fn step<T>(name: &str, f: impl FnOnce() -> anyhow::Result<T>) -> anyhow::Result<T> {
+    let result = f();
+    match &result {
+        Ok(_) => println!("{:<10}ok", name),
+        Err(e) => println!("{:<10}FAILED: {:#}", name, e),
+    }
+    result
+}
+
+// every tree matches its ID, and every file in the worktree still hashes to
+// the ID recorded for it
+fn verify(ctx: &Context, parent: &Path, object_id: &ObjectID) -> anyhow::Result<()> {
+    let contents = ctx.read_object(object_id)?;
+    if ObjectID::from_contents(&contents) != *object_id {
+        anyhow::bail!("tree {} is corrupt", object_id);
+    }
+
+    for object in ctx.read_tree_contents(object_id)? {
+        let path = parent.join(&object.file_path);
+        match object.object_type {
+            ObjectType::Tree => verify(ctx, &path, &object.object_id)?,
+            ObjectType::File => {
+                if ObjectID::from_reader(fs::File::open(&path)?)? != object.object_id {
+                    anyhow::bail!("{} does not match {}", path.display(), object.object_id);
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,388 @@
+use std::collections::{BTreeMap, HashSet};
+use std::io::{self, BufWriter, Write};
+
+use clap::Args;
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+use serde::Serialize;
+
+use crate::output::OutputFormat;
+use crate::{Context, ObjectExpr, ObjectID, ReadContentError};
+
+#[derive(Args, Debug)]
+pub struct StatsCommand {
+    /// Tree to measure. Defaults to HEAD.
+    #[clap(value_name = "object")]
+    object: Option<ObjectExpr>,
+
+    /// Add histograms of tree object sizes, entries per directory and
+    /// entry depths
+    #[clap(long, default_value_t = false, verbatim_doc_comment)]
+    objects: bool,
+
+    /// Also read N objects of the store picked at random, loose and packed,
+    /// check them against their IDs and estimate how many are corrupt
+    #[clap(long, value_name = "N", verbatim_doc_comment)]
+    sample: Option<usize>,
+
+    /// Print the statistics as JSON
+    #[clap(long, default_value_t = false)]
+    json: bool,
+}
+
+impl StatsCommand {
+    /// Returns whether the sample found corrupt objects.
+    pub fn run(&self, ctx: Context) -> anyhow::Result<bool> {
+        let object_id = match self.object {
+            Some(ref object) => object.resolve(&ctx)?,
+            None => ctx.read_head()?,
+        };
+
+        // one walk for everything, the histograms are cheap to keep
+        let mut stats = Stats::default();
+        let mut histograms = Histograms::default();
+        stats.walk(&ctx, &object_id, 0, &mut histograms)?;
+        let sample = match self.sample {
+            Some(n) => Some(Sample::take(&ctx, n)?),
+            None => None,
+        };
+        let report = Report {
+            stats: &stats,
+            histograms: self.objects.then_some(&histograms),
+            sample: sample.as_ref(),
+        };
+
+        let stdout = io::stdout();
+        let mut stdout = BufWriter::new(stdout.lock());
+        if self.json || ctx.output_format() == OutputFormat::Json {
+            serde_json::to_writer(&mut stdout, &report)?;
+            writeln!(stdout)?;
+        } else {
+            report.print(&mut stdout)?;
+        }
+        Ok(sample.is_some_and(|sample| !sample.corrupt.is_empty()))
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+struct Stats {
+    /// Directories, counted once per path, the root included
+    trees: u64,
+    /// Files, counted once per path
+    files: u64,
+    distinct_trees: u64,
+    distinct_files: u64,
+    /// Total size of the distinct tree objects
+    tree_bytes: u64,
+    max_depth: u64,
+
+    #[serde(skip)]
+    seen_trees: HashSet<ObjectID>,
+    #[serde(skip)]
+    seen_files: HashSet<ObjectID>,
+}
+
+#[derive(Debug, Serialize)]
+struct Report<'a> {
+    #[serde(flatten)]
+    stats: &'a Stats,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    histograms: Option<&'a Histograms>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sample: Option<&'a Sample>,
+}
+
+/// Objects read back at random to check the health of the store.
+#[derive(Debug, Serialize)]
+struct Sample {
+    sampled: u64,
+    loose: u64,
+    packed: u64,
+    /// Objects that could not be read or did not match their ID
+    corrupt: Vec<String>,
+    /// Estimated fraction of corrupt objects in the store, with its 95%
+    /// confidence interval
+    corruption_rate: f64,
+    corruption_rate_low: f64,
+    corruption_rate_high: f64,
+}
+
+impl Sample {
+    fn take(ctx: &Context, n: usize) -> Result<Self, ReadContentError> {
+        let object_ids = ctx.list_object_ids()?;
+        let mut sample = Sample {
+            sampled: 0,
+            loose: 0,
+            packed: 0,
+            corrupt: Vec::new(),
+            corruption_rate: 0.0,
+            corruption_rate_low: 0.0,
+            corruption_rate_high: 0.0,
+        };
+        for object_id in object_ids.choose_multiple(&mut thread_rng(), n) {
+            sample.sampled += 1;
+            match ctx.object_file(object_id).exists() {
+                true => sample.loose += 1,
+                false => sample.packed += 1,
+            }
+            let intact = match ctx.read_object(object_id) {
+                Ok(contents) => ObjectID::from_contents(&contents) == *object_id,
+                Err(e) => {
+                    log::warn!("{}: {}", object_id, e);
+                    false
+                }
+            };
+            if !intact {
+                sample.corrupt.push(object_id.to_string());
+            }
+        }
+        sample.corrupt.sort();
+
+        if let Some((rate, low, high)) =
+            wilson_interval(sample.corrupt.len() as u64, sample.sampled)
+        {
+            sample.corruption_rate = rate;
+            sample.corruption_rate_low = low;
+            sample.corruption_rate_high = high;
+        }
+        Ok(sample)
+    }
+}
+
+// The rate of `k` in `n` with its 95% Wilson score interval, which unlike
+// the normal approximation stays within 0 and 1 when nothing is found.
+fn wilson_interval(k: u64, n: u64) -> Option<(f64, f64, f64)> {
+    if n == 0 {
+        return None;
+    }
+    const Z: f64 = 1.96;
+    let (k, n) = (k as f64, n as f64);
+    let p = k / n;
+    let denominator = 1.0 + Z * Z / n;
+    let center = (p + Z * Z / (2.0 * n)) / denominator;
+    let half = Z * (p * (1.0 - p) / n + Z * Z / (4.0 * n * n)).sqrt() / denominator;
+    Some((p, (center - half).max(0.0), (center + half).min(1.0)))
+}
+
+#[derive(Debug, Serialize)]
+struct Histograms {
+    /// Sizes of the distinct tree objects in bytes; file contents are not stored
+    tree_size: Histogram,
+    /// Entries per directory
+    fan_out: Histogram,
+    /// Depth of each entry, the entries of the root being at depth 1
+    depth: Histogram,
+}
+
+impl Default for Histograms {
+    fn default() -> Self {
+        Histograms {
+            tree_size: Histogram::log2(),
+            fan_out: Histogram::log2(),
+            depth: Histogram::linear(),
+        }
+    }
+}
+
+impl Stats {
+    fn walk(
+        &mut self,
+        ctx: &Context,
+        object_id: &ObjectID,
+        depth: u64,
+        histograms: &mut Histograms,
+    ) -> Result<(), ReadContentError> {
+        self.trees += 1;
+        if self.seen_trees.insert(*object_id) {
+            let size = ctx.read_object(object_id)?.len() as u64;
+            self.distinct_trees += 1;
+            self.tree_bytes += size;
+            histograms.tree_size.add(size);
+        }
+
+        let objects = ctx.read_tree_contents(object_id)?;
+        histograms.fan_out.add(objects.len() as u64);
+        for object in objects {
+            histograms.depth.add(depth + 1);
+            self.max_depth = self.max_depth.max(depth + 1);
+            if object.is_tree() {
+                self.walk(ctx, &object.object_id, depth + 1, histograms)?;
+            } else {
+                self.files += 1;
+                if self.seen_files.insert(object.object_id) {
+                    self.distinct_files += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Report<'_> {
+    fn print<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let stats = self.stats;
+        writeln!(w, "{:<16}{}", "trees", stats.trees)?;
+        writeln!(w, "{:<16}{}", "files", stats.files)?;
+        writeln!(w, "{:<16}{}", "distinct trees", stats.distinct_trees)?;
+        writeln!(w, "{:<16}{}", "distinct files", stats.distinct_files)?;
+        writeln!(w, "{:<16}{}", "tree bytes", stats.tree_bytes)?;
+        writeln!(w, "{:<16}{}", "max depth", stats.max_depth)?;
+
+        if let Some(histograms) = self.histograms {
+            histograms.tree_size.print(w, "tree size (bytes)")?;
+            histograms.fan_out.print(w, "entries per directory")?;
+            histograms.depth.print(w, "depth")?;
+        }
+
+        if let Some(sample) = self.sample {
+            writeln!(w)?;
+            writeln!(
+                w,
+                "{:<16}{} ({} loose, {} packed)",
+                "sampled", sample.sampled, sample.loose, sample.packed
+            )?;
+            writeln!(w, "{:<16}{}", "corrupt", sample.corrupt.len())?;
+            for object_id in &sample.corrupt {
+                writeln!(w, "  {}", object_id)?;
+            }
+            if sample.sampled > 0 {
+                writeln!(
+                    w,
+                    "{:<16}{:.1}% (95% CI {:.1}%-{:.1}%)",
+                    "corruption",
+                    sample.corruption_rate * 100.0,
+                    sample.corruption_rate_low * 100.0,
+                    sample.corruption_rate_high * 100.0
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Counts of values in power-of-two buckets, or one bucket per value.
+#[derive(Debug)]
+struct Histogram {
+    log2: bool,
+    counts: BTreeMap<u64, u64>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct Bucket {
+    min: u64,
+    max: u64,
+    count: u64,
+}
+
+impl Histogram {
+    fn log2() -> Self {
+        Histogram {
+            log2: true,
+            counts: BTreeMap::new(),
+        }
+    }
+
+    fn linear() -> Self {
+        Histogram {
+            log2: false,
+            counts: BTreeMap::new(),
+        }
+    }
+
+    fn add(&mut self, value: u64) {
+        let key = match self.log2 {
+            // the number of bits, so 0 has a bucket of its own
+            true => (u64::BITS - value.leading_zeros()) as u64,
+            false => value,
+        };
+        *self.counts.entry(key).or_default() += 1;
+    }
+
+    fn buckets(&self) -> Vec<Bucket> {
+        self.counts
+            .iter()
+            .map(|(&key, &count)| {
+                let (min, max) = match (self.log2, key) {
+                    (true, 0) => (0, 0),
+                    (true, bits) => (1 << (bits - 1), u64::MAX >> (u64::BITS as u64 - bits)),
+                    (false, value) => (value, value),
+                };
+                Bucket { min, max, count }
+            })
+            .collect()
+    }
+
+    fn print<W: Write>(&self, w: &mut W, title: &str) -> io::Result<()> {
+        writeln!(w)?;
+        writeln!(w, "{}", title)?;
+        for bucket in self.buckets() {
+            let range = match bucket.min == bucket.max {
+                true => bucket.min.to_string(),
+                false => format!("{}-{}", bucket.min, bucket.max),
+            };
+            writeln!(w, "  {:<14}{}", range, bucket.count)?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for Histogram {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.buckets().serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{wilson_interval, Bucket, Histogram};
+
+    #[test]
+    fn test_histogram() {
+        let mut histogram = Histogram::log2();
+        for value in [0, 1, 2, 3, 4, 7, 8, 1000] {
+            histogram.add(value);
+        }
+        let buckets: Vec<_> = histogram
+            .buckets()
+            .iter()
+            .map(|b| (b.min, b.max, b.count))
+            .collect();
+        assert_eq!(
+            buckets,
+            vec![
+                (0, 0, 1),
+                (1, 1, 1),
+                (2, 3, 2),
+                (4, 7, 2),
+                (8, 15, 1),
+                (512, 1023, 1)
+            ]
+        );
+
+        let mut histogram = Histogram::linear();
+        histogram.add(3);
+        histogram.add(3);
+        assert_eq!(
+            histogram.buckets(),
+            vec![Bucket {
+                min: 3,
+                max: 3,
+                count: 2
+            }]
+        );
+    }
+
+    #[test]
+    fn test_wilson_interval() {
+        assert_eq!(wilson_interval(0, 0), None);
+
+        let (rate, low, high) = wilson_interval(0, 100).unwrap();
+        assert_eq!((rate, low), (0.0, 0.0));
+        assert!((high - 0.037).abs() < 0.001);
+
+        let (rate, low, high) = wilson_interval(10, 100).unwrap();
+        assert_eq!(rate, 0.1);
+        assert!((low - 0.0552).abs() < 0.001);
+        assert!((high - 0.1744).abs() < 0.001);
+    }
+}
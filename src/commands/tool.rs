@@ -1,56 +1,187 @@
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io;
-use std::io::Read;
 use std::io::Write;
-#[cfg(not(windows))]
-use std::path::Path;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use clap::Args;
+use clap::{Args, ValueEnum};
 use indicatif::ProgressBar;
-use rand::prelude::{thread_rng, Distribution};
+use rand::prelude::{Distribution, Rng, SeedableRng, StdRng};
 use rand_distr::Normal;
 use rayon::prelude::*;
-use redb::ReadableTable;
+use redb::{Database, ReadableTable};
 
-use crate::{filesystem, Context, ObjectID, PACKED_OBJECTS_TABLE};
+use crate::builder::{Builder, ScanTargetGenerator};
+use crate::filter::MatchAllFilter;
+use crate::store::NullObjectStore;
+use crate::{filesystem, header, Context, ObjectID, PACKED_DELTAS_TABLE, PACKED_OBJECTS_TABLE};
 
 #[derive(Debug, Args)]
 pub struct Hash {
     input: Vec<PathBuf>,
+
+    /// If true, hash directories as trees and print their root object ID.
+    /// Nothing is written to the repository.
+    #[clap(long, default_value_t = false, verbatim_doc_comment)]
+    tree: bool,
+
+    /// If true, scan hidden files when hashing a tree.
+    #[clap(long, default_value_t = false, requires = "tree", verbatim_doc_comment)]
+    hidden: bool,
 }
 
 impl Hash {
     pub fn run(&self) -> anyhow::Result<()> {
         if self.input.is_empty() {
-            let contents = Self::contents_from_stdin()?;
-            println!("{} -", crate::hash::Hash::from_contents(contents));
+            println!("{} -", crate::hash::hash_reader(io::stdin().lock())?);
             return Ok(());
         }
         for path in &self.input {
             if path.is_dir() {
-                println!("{} {}", " ".repeat(16), path.display());
+                if self.tree {
+                    println!("{} {}", tree_root(path, self.hidden)?, path.display());
+                } else {
+                    println!("{} {}", " ".repeat(16), path.display());
+                }
                 continue;
             }
 
-            let contents = std::fs::read(path)?;
-            println!(
-                "{} {}",
-                crate::hash::Hash::from_contents(&contents),
-                path.display()
-            );
+            let file = std::fs::File::open(path)?;
+            println!("{} {}", crate::hash::hash_reader(file)?, path.display());
+        }
+
+        Ok(())
+    }
+}
+
+// root tree ID of `dir`, computed without writing anything
+fn tree_root(dir: &Path, hidden: bool) -> anyhow::Result<ObjectID> {
+    let root_dir = dir.canonicalize()?;
+    let ctx = Context::with_store(&root_dir, Box::new(NullObjectStore));
+
+    let filter = Box::new(MatchAllFilter::new(root_dir));
+    let generator = Box::new(ScanTargetGenerator::new(filter, hidden));
+    let object = Builder::new(generator, false).build(&ctx)?;
+    Ok(object.object_id)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MergePolicy {
+    /// Keep the entry from the first manifest
+    First,
+    /// Keep the entry from the second manifest
+    Second,
+    /// Keep the entry from the most recently modified manifest
+    Newest,
+    /// Keep the entry from the least recently modified manifest
+    Oldest,
+    /// Fail on the first conflict
+    Fail,
+}
+
+#[derive(Debug, Args)]
+pub struct ManifestMerge {
+    /// Manifest in "hash path" format, as printed by "tool hash"
+    first: PathBuf,
+
+    /// Manifest in "hash path" format, as printed by "tool hash"
+    second: PathBuf,
+
+    /// How to resolve paths listed with different hashes
+    #[clap(long, value_enum, default_value_t = MergePolicy::Fail)]
+    prefer: MergePolicy,
+}
+
+impl ManifestMerge {
+    pub fn run(&self) -> anyhow::Result<()> {
+        let first = Self::read_manifest(&self.first)?;
+        let second = Self::read_manifest(&self.second)?;
+
+        let prefer_first = match self.prefer {
+            MergePolicy::First => Some(true),
+            MergePolicy::Second => Some(false),
+            MergePolicy::Newest => Some(Self::mtime(&self.first)? >= Self::mtime(&self.second)?),
+            MergePolicy::Oldest => Some(Self::mtime(&self.first)? <= Self::mtime(&self.second)?),
+            MergePolicy::Fail => None,
+        };
+
+        let mut merged = first;
+        let mut conflicts = 0;
+        for (path, hash) in second {
+            match merged.get(&path) {
+                Some(current) if *current == hash => {}
+                Some(current) => {
+                    conflicts += 1;
+                    match prefer_first {
+                        Some(true) => {}
+                        Some(false) => {
+                            merged.insert(path, hash);
+                        }
+                        None => anyhow::bail!(
+                            "conflict at \"{}\": {} / {}",
+                            path,
+                            Self::display_hash(current),
+                            Self::display_hash(&hash)
+                        ),
+                    }
+                }
+                None => {
+                    merged.insert(path, hash);
+                }
+            }
         }
+        log::info!("resolved {} conflicts", conflicts);
 
+        let stdout = io::stdout();
+        let mut stdout = io::BufWriter::new(stdout.lock());
+        for (path, hash) in merged {
+            writeln!(stdout, "{} {}", Self::display_hash(&hash), path)?;
+        }
         Ok(())
     }
 
-    fn contents_from_stdin() -> anyhow::Result<Vec<u8>> {
-        let mut contents = Vec::new();
-        io::stdin().read_to_end(&mut contents)?;
-        Ok(contents)
+    fn display_hash(hash: &Option<crate::hash::Hash>) -> String {
+        match hash {
+            Some(hash) => hash.to_string(),
+            None => " ".repeat(16),
+        }
+    }
+
+    fn mtime(path: &Path) -> io::Result<std::time::SystemTime> {
+        std::fs::metadata(path)?.modified()
+    }
+
+    fn read_manifest(path: &Path) -> anyhow::Result<BTreeMap<String, Option<crate::hash::Hash>>> {
+        let contents = std::fs::read_to_string(path)?;
+
+        let mut manifest = BTreeMap::new();
+        for (i, line) in contents.lines().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            let Some((hash, path_name)) = parse_manifest_line(line) else {
+                anyhow::bail!("{}:{}: invalid manifest line", path.display(), i + 1);
+            };
+            manifest.insert(path_name.to_string(), hash);
+        }
+        Ok(manifest)
     }
 }
 
+/// Parse a "hash path" line. Directories have a blank hash.
+fn parse_manifest_line(line: &str) -> Option<(Option<crate::hash::Hash>, &str)> {
+    if line.len() < 18 || !line.is_char_boundary(17) || line.as_bytes()[16] != b' ' {
+        return None;
+    }
+    let (hash, path) = (&line[..16], &line[17..]);
+    if hash.trim().is_empty() {
+        return Some((None, path));
+    }
+    crate::hash::Hash::from_hex(hash)
+        .ok()
+        .map(|hash| (Some(hash), path))
+}
+
 #[derive(Debug, Args)]
 pub struct Generate {
     dir: String,
@@ -64,35 +195,63 @@ pub struct Generate {
 
     #[clap(short, long, default_value = "2", value_delimiter = ',')]
     prefix_bytes: Vec<usize>,
+
+    /// Seed for the random contents. The same seed and options generate the
+    /// same files, and so the same tree.
+    #[clap(long, value_name = "n", verbatim_doc_comment)]
+    seed: Option<u64>,
+
+    /// Write a manifest of the generated files to this file: a "hash size
+    /// path" line for each file, after a "tree-id - dir" line with the root
+    /// tree ID of the directory.
+    #[clap(long, value_name = "file", verbatim_doc_comment)]
+    manifest: Option<PathBuf>,
 }
 
 impl Generate {
     pub async fn run_async(&self) -> anyhow::Result<()> {
         let dir = std::path::Path::new(&self.dir);
         let pb = ProgressBar::new(self.nfile as u64);
-        (0..self.nfile).into_par_iter().for_each(|_i| {
-            pb.inc(1);
-
-            let mut normal = Normal::new(
-                (self.num_kilobytes * 1024) as f64,
-                (self.num_kilobytes_stddev * 1024) as f64,
-            )
-            .unwrap();
-
-            let random_contents = Self::generate_bytes(&mut normal);
-            let hash = crate::hash::Hash::from_contents(&random_contents);
-            let hash = hash.to_string();
-
-            let (prefix, rest) = Self::split_by_prefixes(&hash, &self.prefix_bytes);
-
-            let path = dir.join(prefix);
-            std::fs::create_dir_all(&path).unwrap();
-
-            let path = path.join(rest);
-            let mut file = File::create(path).unwrap();
-            file.write_all(&random_contents).unwrap();
-        });
-
+        let normal = Normal::new(
+            (self.num_kilobytes * 1024) as f64,
+            (self.num_kilobytes_stddev * 1024) as f64,
+        )?;
+        let mut files = (0..self.nfile)
+            .into_par_iter()
+            .map(|i| {
+                pb.inc(1);
+
+                // one generator per file, as the files are made in any order
+                let mut rng = match self.seed {
+                    Some(seed) => StdRng::seed_from_u64(seed.wrapping_add(i as u64)),
+                    None => StdRng::from_entropy(),
+                };
+                let random_contents = Self::generate_bytes(&normal, &mut rng);
+                let hash = crate::hash::Hash::from_contents(&random_contents);
+                let hash_string = hash.to_string();
+
+                let (prefix, rest) = Self::split_by_prefixes(&hash_string, &self.prefix_bytes);
+
+                let path = dir.join(prefix);
+                std::fs::create_dir_all(&path)?;
+
+                let path = path.join(rest);
+                let mut file = File::create(&path)?;
+                file.write_all(&random_contents)?;
+                Ok((path, hash, random_contents.len()))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        pb.finish_and_clear();
+
+        if let Some(manifest) = &self.manifest {
+            files.sort();
+            let mut writer = io::BufWriter::new(File::create(manifest)?);
+            writeln!(writer, "{} - {}", tree_root(dir, false)?, dir.display())?;
+            for (path, hash, size) in files {
+                writeln!(writer, "{} {} {}", hash, size, path.display())?;
+            }
+            writer.flush()?;
+        }
         Ok(())
     }
 
@@ -116,11 +275,11 @@ impl Generate {
         (path, &x[start..])
     }
 
-    fn generate_bytes(normal: &mut Normal<f64>) -> Vec<u8> {
-        let need_bytes = normal.sample(&mut thread_rng()) as usize;
-        (0..need_bytes)
-            .map(|_| rand::random::<u8>())
-            .collect::<Vec<_>>()
+    fn generate_bytes<R: Rng>(normal: &Normal<f64>, rng: &mut R) -> Vec<u8> {
+        let need_bytes = normal.sample(rng) as usize;
+        let mut bytes = vec![0; need_bytes];
+        rng.fill_bytes(&mut bytes);
+        bytes
     }
 }
 
@@ -148,7 +307,7 @@ impl Fincore {
 
         let len = metadata.len();
         let pagesize = page_size::get() as u64;
-        let npages = (len + pagesize - 1) / pagesize;
+        let npages = len.div_ceil(pagesize);
 
         let mut vec = vec![0u8; npages as usize];
         let m = unsafe {
@@ -220,10 +379,14 @@ pub struct ReDB {
 
 impl ReDB {
     pub fn run(&self, ctx: Context) -> anyhow::Result<()> {
-        let Some(db) = ctx.packed_db else {
+        let pack_file = ctx.pack_file();
+        drop(ctx);
+
+        if !pack_file.exists() {
             println!("no packed db");
             return Ok(());
-        };
+        }
+        let db = Database::open(pack_file)?;
 
         let read_txn = db.begin_read()?;
         let Some(object_id) = &self.key else {
@@ -239,11 +402,42 @@ impl ReDB {
         match table.get(object_id)? {
             Some(val) => {
                 let content = val.value();
-                let s = String::from_utf8_lossy(&content);
+                let (_, payload) = header::decode(&content)?;
+                let s = String::from_utf8_lossy(payload);
                 println!("{}", s);
             }
-            None => println!("not found"),
+            None => match read_txn.open_table(PACKED_DELTAS_TABLE) {
+                Ok(table) => match table.get(object_id)? {
+                    Some(val) => println!("delta against {}", val.value().0),
+                    None => println!("not found"),
+                },
+                Err(_) => println!("not found"),
+            },
         };
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::parse_manifest_line;
+    use crate::hash::Hash;
+
+    #[test]
+    fn test_parse_manifest_line() {
+        assert_eq!(
+            parse_manifest_line("d447b1ea40e6988b foo/bar baz"),
+            Some((
+                Some(Hash::from_hex("d447b1ea40e6988b").unwrap()),
+                "foo/bar baz"
+            ))
+        );
+        assert_eq!(
+            parse_manifest_line("                 foo"),
+            Some((None, "foo"))
+        );
+        assert_eq!(parse_manifest_line("d447b1ea40e6988b"), None);
+        assert_eq!(parse_manifest_line("d447b1ea40e6988bX foo"), None);
+        assert_eq!(parse_manifest_line("not-a-hash-value foo"), None);
+    }
+}
@@ -0,0 +1,636 @@
+//! Settings read from environment variables, for CI and containers where long
+//! command lines are awkward, and from config files.
+//!
+//! A setting given on the command line wins over its variable, which wins
+//! over the repository's `.mtl/config`, which wins over the user's
+//! `~/.config/mtl/config`, which wins over the default. Empty variables count
+//! as unset.
+//!
+//! Config files are written in a subset of TOML: `[section]` headers and
+//! `key = value` lines, where a value is a string, an integer or a boolean.
+//!
+//! ```toml
+//! [core]
+//! jobs = 4
+//!
+//! [gc]
+//! grace = "7d"
+//! ```
+
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::{datetime, ConfigError, MIN_OBJECT_ID_PREFIX, OBJECT_ID_LEN};
+
+/// Working directory, like `--dir`.
+pub const DIR_VAR: &str = "MTL_DIR";
+/// Number of threads for hashing and scanning, like `--jobs`.
+pub const JOBS_VAR: &str = "MTL_JOBS";
+/// Whether to wait for the repository lock, like `--wait` and `--no-wait`.
+pub const WAIT_VAR: &str = "MTL_WAIT";
+/// Whether builds show a progress bar, like `--progress`.
+pub const PROGRESS_VAR: &str = "MTL_PROGRESS";
+/// Format of the statistics footer, like `--stats=<format>`.
+pub const STATS_VAR: &str = "MTL_STATS";
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct EnvConfig {
+    pub dir: Option<PathBuf>,
+    pub jobs: Option<usize>,
+    pub wait: Option<bool>,
+    pub progress: Option<bool>,
+    pub stats: Option<String>,
+}
+
+impl EnvConfig {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_lookup(|name| env::var_os(name))
+    }
+
+    fn from_lookup(lookup: impl Fn(&str) -> Option<OsString>) -> Result<Self, ConfigError> {
+        let var = |name: &'static str| -> Result<Option<String>, ConfigError> {
+            match lookup(name).filter(|value| !value.is_empty()) {
+                Some(value) => {
+                    value
+                        .into_string()
+                        .map(Some)
+                        .map_err(|value| ConfigError::InvalidValue {
+                            name,
+                            value: value.to_string_lossy().into_owned(),
+                        })
+                }
+                None => Ok(None),
+            }
+        };
+
+        let jobs = match var(JOBS_VAR)? {
+            Some(value) => match value.parse() {
+                Ok(jobs) if jobs > 0 => Some(jobs),
+                _ => {
+                    return Err(ConfigError::InvalidValue {
+                        name: JOBS_VAR,
+                        value,
+                    })
+                }
+            },
+            None => None,
+        };
+        Ok(EnvConfig {
+            // paths need not be UTF-8
+            dir: lookup(DIR_VAR)
+                .filter(|value| !value.is_empty())
+                .map(PathBuf::from),
+            jobs,
+            wait: var(WAIT_VAR)?
+                .map(|v| parse_bool(WAIT_VAR, v))
+                .transpose()?,
+            progress: var(PROGRESS_VAR)?
+                .map(|v| parse_bool(PROGRESS_VAR, v))
+                .transpose()?,
+            stats: var(STATS_VAR)?,
+        })
+    }
+}
+
+fn parse_bool(name: &'static str, value: String) -> Result<bool, ConfigError> {
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err(ConfigError::InvalidValue { name, value }),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Boolean,
+    Integer,
+    String,
+}
+
+/// The keys config files may set, as "section.key".
+const KEYS: &[(&str, Kind)] = &[
+    // default of --hidden for the local commands
+    ("build.hidden", Kind::Boolean),
+    // an ignore file relative to the scanned root, used where it exists
+    ("build.ignore-file", Kind::String),
+    // like --sqlite, for builds without --input
+    ("build.sqlite", Kind::String),
+    // like --sqlite-query
+    ("build.sqlite-query", Kind::String),
+    // hex digits of object IDs in text output, unless --full-ids
+    ("core.abbrev", Kind::Integer),
+    // like --jobs
+    ("core.jobs", Kind::Integer),
+    // like MTL_PROGRESS
+    ("core.progress", Kind::Boolean),
+    // like --wait
+    ("core.wait", Kind::Boolean),
+    // default of gc --grace
+    ("gc.grace", Kind::String),
+    // default of pack --delta
+    ("pack.delta", Kind::Boolean),
+];
+
+/// Fail unless config files may set `key`.
+pub fn check_key(key: &str) -> Result<(), ConfigError> {
+    lookup_key(key).map(|_| ())
+}
+
+/// The key of a remote's URL, for any remote name.
+const REMOTE_URL: &str = "remote.<name>.url";
+
+/// Schemes a remote URL may have.
+pub const REMOTE_SCHEMES: &[&str] = &["ssh", "http", "https", "s3"];
+
+/// The key holding the URL of remote `name`.
+pub fn remote_key(name: &str) -> String {
+    format!("remote.{}.url", name)
+}
+
+// "origin" of "remote.origin.url"
+fn remote_name(key: &str) -> Option<&str> {
+    key.strip_prefix("remote.")?
+        .strip_suffix(".url")
+        .filter(|name| is_bare_key(name))
+}
+
+/// Whether `name` can name a remote: letters, digits, '-' and '_'.
+pub fn is_remote_name(name: &str) -> bool {
+    is_bare_key(name)
+}
+
+fn is_remote_url(url: &str) -> bool {
+    match url.split_once("://") {
+        Some((scheme, rest)) => REMOTE_SCHEMES.contains(&scheme) && !rest.is_empty(),
+        None => false,
+    }
+}
+
+fn lookup_key(key: &str) -> Result<(&'static str, Kind), ConfigError> {
+    if remote_name(key).is_some() {
+        return Ok((REMOTE_URL, Kind::String));
+    }
+    KEYS.iter()
+        .find(|(name, _)| *name == key)
+        .copied()
+        .ok_or_else(|| ConfigError::UnknownKey(key.to_string()))
+}
+
+/// A value in a config file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+}
+
+impl Value {
+    fn kind(&self) -> Kind {
+        match self {
+            Value::String(_) => Kind::String,
+            Value::Integer(_) => Kind::Integer,
+            Value::Boolean(_) => Kind::Boolean,
+        }
+    }
+
+    fn to_toml(&self) -> String {
+        match self {
+            Value::String(s) => {
+                let mut quoted = String::from('"');
+                for c in s.chars() {
+                    match c {
+                        '"' => quoted.push_str("\\\""),
+                        '\\' => quoted.push_str("\\\\"),
+                        '\n' => quoted.push_str("\\n"),
+                        '\t' => quoted.push_str("\\t"),
+                        c => quoted.push(c),
+                    }
+                }
+                quoted.push('"');
+                quoted
+            }
+            value => value.to_string(),
+        }
+    }
+}
+
+/// Strings print unquoted, as `config get` shows them.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::String(s) => write!(f, "{}", s),
+            Value::Integer(n) => write!(f, "{}", n),
+            Value::Boolean(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+/// The values of one config file, by "section.key".
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ConfigFile {
+    values: BTreeMap<String, Value>,
+}
+
+impl ConfigFile {
+    /// Read a config file; a missing one is empty.
+    pub fn read(path: &Path) -> Result<Self, ConfigError> {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::parse(path, &text),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Parse the text of a config file, `path` only naming it in errors.
+    pub fn parse(path: &Path, text: &str) -> Result<Self, ConfigError> {
+        let mut values = BTreeMap::new();
+        let mut section = String::new();
+        for (i, line) in text.lines().enumerate() {
+            let syntax = |message: &str| ConfigError::Syntax {
+                path: path.to_path_buf(),
+                line: i + 1,
+                message: message.to_string(),
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('[') {
+                let name = header
+                    .split_once(']')
+                    .filter(|(_, rest)| is_blank(rest))
+                    .map(|(name, _)| name.trim())
+                    .filter(|name| name.split('.').all(|part| is_bare_key(part.trim())))
+                    .ok_or_else(|| syntax("invalid section header"))?;
+                section = name.split('.').map(str::trim).collect::<Vec<_>>().join(".");
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| syntax("expected \"key = value\""))?;
+            let key = key.trim();
+            if !is_bare_key(key) {
+                return Err(syntax("invalid key"));
+            }
+            let value = parse_value(value.trim()).map_err(syntax)?;
+            let key = match section.is_empty() {
+                true => key.to_string(),
+                false => format!("{}.{}", section, key),
+            };
+            if values.insert(key, value).is_some() {
+                return Err(syntax("duplicate key"));
+            }
+        }
+        Ok(ConfigFile { values })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.values.get(key)
+    }
+
+    /// Set a key from its command line form, e.g. "core.jobs" to "4".
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        let (name, kind) = lookup_key(key)?;
+        let value = match kind {
+            Kind::Boolean => Value::Boolean(parse_bool(name, value.to_string())?),
+            Kind::Integer => {
+                Value::Integer(value.parse().map_err(|_| ConfigError::InvalidValue {
+                    name,
+                    value: value.to_string(),
+                })?)
+            }
+            Kind::String => Value::String(value.to_string()),
+        };
+        // e.g. a grace period that does not parse
+        Config::from_values(&[(key, &value)])?;
+        self.values.insert(key.to_string(), value);
+        Ok(())
+    }
+
+    /// Remove a key, returning whether it was set.
+    pub fn remove(&mut self, key: &str) -> bool {
+        self.values.remove(key).is_some()
+    }
+
+    /// Take the values of `other` over those of this file.
+    pub fn merge(&mut self, other: &ConfigFile) {
+        for (key, value) in &other.values {
+            self.values.insert(key.clone(), value.clone());
+        }
+    }
+
+    /// Write the file back. Comments in the file read are not kept.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, self.to_toml())
+    }
+
+    fn to_toml(&self) -> String {
+        let mut text = String::new();
+        let mut current = None;
+        // top-level keys first, as they would land in the section above them
+        let (top, sectioned): (Vec<_>, Vec<_>) =
+            self.values.iter().partition(|(key, _)| !key.contains('.'));
+        for (key, value) in top {
+            text.push_str(&format!("{} = {}\n", key, value.to_toml()));
+        }
+        for (key, value) in sectioned {
+            let (section, name) = key.rsplit_once('.').unwrap();
+            if current != Some(section) {
+                if !text.is_empty() {
+                    text.push('\n');
+                }
+                text.push_str(&format!("[{}]\n", section));
+                current = Some(section);
+            }
+            text.push_str(&format!("{} = {}\n", name, value.to_toml()));
+        }
+        text
+    }
+}
+
+fn is_bare_key(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+// nothing but a comment left on the line
+fn is_blank(s: &str) -> bool {
+    let s = s.trim_start();
+    s.is_empty() || s.starts_with('#')
+}
+
+fn parse_value(s: &str) -> Result<Value, &'static str> {
+    if let Some(rest) = s.strip_prefix('\'') {
+        // literal string, without escapes
+        let (value, rest) = rest.split_once('\'').ok_or("unterminated string")?;
+        return match is_blank(rest) {
+            true => Ok(Value::String(value.to_string())),
+            false => Err("unexpected text after the value"),
+        };
+    }
+    if let Some(rest) = s.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    return match is_blank(&rest[i + 1..]) {
+                        true => Ok(Value::String(value)),
+                        false => Err("unexpected text after the value"),
+                    };
+                }
+                '\\' => match chars.next() {
+                    Some((_, '"')) => value.push('"'),
+                    Some((_, '\\')) => value.push('\\'),
+                    Some((_, 'n')) => value.push('\n'),
+                    Some((_, 't')) => value.push('\t'),
+                    _ => return Err("invalid escape"),
+                },
+                c => value.push(c),
+            }
+        }
+        return Err("unterminated string");
+    }
+    let s = s.split('#').next().unwrap_or_default().trim();
+    match s {
+        "true" => Ok(Value::Boolean(true)),
+        "false" => Ok(Value::Boolean(false)),
+        _ => s
+            .parse()
+            .map(Value::Integer)
+            .map_err(|_| "expected a string, an integer or a boolean"),
+    }
+}
+
+/// Where the user's config file is: `$XDG_CONFIG_HOME/mtl/config`, or
+/// `~/.config/mtl/config`.
+pub fn user_config_path() -> Option<PathBuf> {
+    let nonempty = |name| env::var_os(name).filter(|value| !value.is_empty());
+    match nonempty("XDG_CONFIG_HOME") {
+        Some(dir) => Some(PathBuf::from(dir).join("mtl").join("config")),
+        None => nonempty("HOME").map(|home| PathBuf::from(home).join(".config/mtl/config")),
+    }
+}
+
+/// The settings of the config files. `None` leaves a setting to the command
+/// line, the environment or the default.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Config {
+    pub abbrev: Option<usize>,
+    pub jobs: Option<usize>,
+    pub wait: Option<bool>,
+    pub progress: Option<bool>,
+    pub hidden: Option<bool>,
+    pub ignore_file: Option<PathBuf>,
+    /// Path or `file:` URI of the database listing the files to build
+    pub sqlite: Option<String>,
+    pub sqlite_query: Option<String>,
+    pub grace: Option<Duration>,
+    pub delta: Option<bool>,
+    /// URLs by remote name
+    pub remotes: BTreeMap<String, String>,
+}
+
+impl Config {
+    /// The user's config file, with the repository's one over it.
+    pub fn load(repo_file: &Path) -> Result<Self, ConfigError> {
+        let mut file = match user_config_path() {
+            Some(path) => ConfigFile::read(&path)?,
+            None => ConfigFile::default(),
+        };
+        file.merge(&ConfigFile::read(repo_file)?);
+        Self::from_file(&file)
+    }
+
+    /// The URL of a remote, or `remote` itself when it is a URL already.
+    pub fn remote_url<'a>(&'a self, remote: &'a str) -> Option<&'a str> {
+        match is_remote_url(remote) {
+            true => Some(remote),
+            false => self.remotes.get(remote).map(String::as_str),
+        }
+    }
+
+    pub fn from_file(file: &ConfigFile) -> Result<Self, ConfigError> {
+        let mut values = Vec::new();
+        for (key, value) in &file.values {
+            match lookup_key(key) {
+                Ok(_) => values.push((key.as_str(), value)),
+                // maybe meant for a newer mtl
+                Err(_) => log::warn!("unknown config key \"{}\"", key),
+            }
+        }
+        Self::from_values(&values)
+    }
+
+    fn from_values(values: &[(&str, &Value)]) -> Result<Self, ConfigError> {
+        let mut config = Config::default();
+        for &(key, value) in values {
+            let (name, kind) = lookup_key(key)?;
+            let invalid = || ConfigError::InvalidValue {
+                name,
+                value: value.to_string(),
+            };
+            if value.kind() != kind {
+                return Err(invalid());
+            }
+            match (name, value) {
+                ("build.hidden", Value::Boolean(b)) => config.hidden = Some(*b),
+                ("build.ignore-file", Value::String(s)) => config.ignore_file = Some(s.into()),
+                ("build.sqlite", Value::String(s)) => config.sqlite = Some(s.clone()),
+                ("build.sqlite-query", Value::String(s)) => config.sqlite_query = Some(s.clone()),
+                ("core.abbrev", Value::Integer(n)) => match usize::try_from(*n) {
+                    Ok(width) if (MIN_OBJECT_ID_PREFIX..=OBJECT_ID_LEN).contains(&width) => {
+                        config.abbrev = Some(width)
+                    }
+                    _ => return Err(invalid()),
+                },
+                ("core.jobs", Value::Integer(n)) => match usize::try_from(*n) {
+                    Ok(jobs) if jobs > 0 => config.jobs = Some(jobs),
+                    _ => return Err(invalid()),
+                },
+                ("core.progress", Value::Boolean(b)) => config.progress = Some(*b),
+                ("core.wait", Value::Boolean(b)) => config.wait = Some(*b),
+                ("gc.grace", Value::String(s)) => {
+                    config.grace = Some(datetime::parse_duration(s).map_err(|_| invalid())?)
+                }
+                ("pack.delta", Value::Boolean(b)) => config.delta = Some(*b),
+                (REMOTE_URL, Value::String(url)) => match (remote_name(key), is_remote_url(url)) {
+                    (Some(remote), true) => {
+                        config.remotes.insert(remote.to_string(), url.clone());
+                    }
+                    _ => return Err(invalid()),
+                },
+                _ => unreachable!("{} is not handled", name),
+            }
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, ConfigFile, EnvConfig, Value};
+    use std::collections::HashMap;
+    use std::ffi::OsString;
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+
+    fn config(vars: &[(&str, &str)]) -> Result<EnvConfig, crate::ConfigError> {
+        let vars: HashMap<_, _> = vars.iter().copied().collect();
+        EnvConfig::from_lookup(|name| vars.get(name).map(OsString::from))
+    }
+
+    #[test]
+    fn test_env_config() {
+        assert_eq!(config(&[]).unwrap(), EnvConfig::default());
+        assert_eq!(
+            config(&[
+                ("MTL_DIR", "/data"),
+                ("MTL_JOBS", "4"),
+                ("MTL_WAIT", "yes"),
+                ("MTL_PROGRESS", "0"),
+                ("MTL_STATS", "json"),
+            ])
+            .unwrap(),
+            EnvConfig {
+                dir: Some(PathBuf::from("/data")),
+                jobs: Some(4),
+                wait: Some(true),
+                progress: Some(false),
+                stats: Some("json".to_string()),
+            }
+        );
+        assert_eq!(config(&[("MTL_WAIT", "")]).unwrap(), EnvConfig::default());
+
+        assert!(config(&[("MTL_JOBS", "0")]).is_err());
+        assert!(config(&[("MTL_JOBS", "many")]).is_err());
+        assert!(config(&[("MTL_WAIT", "maybe")]).is_err());
+    }
+
+    fn parse(text: &str) -> Result<ConfigFile, crate::ConfigError> {
+        ConfigFile::parse(Path::new("config"), text)
+    }
+
+    #[test]
+    fn test_parse_config_file() {
+        let file = parse(
+            "# comment\n\
+             [core]\n\
+             jobs = 4 # threads\n\
+             wait = true\n\
+             \n\
+             [ gc ]\n\
+             grace = \"7d\"\n\
+             [build]\n\
+             ignore-file = 'a \"b\"'\n",
+        )
+        .unwrap();
+        assert_eq!(file.get("core.jobs"), Some(&Value::Integer(4)));
+        assert_eq!(file.get("core.wait"), Some(&Value::Boolean(true)));
+        assert_eq!(file.get("gc.grace"), Some(&Value::String("7d".into())));
+        assert_eq!(
+            file.get("build.ignore-file"),
+            Some(&Value::String("a \"b\"".into()))
+        );
+        assert_eq!(parse(&file.to_toml()).unwrap(), file);
+
+        assert!(parse("[core\n").is_err());
+        assert!(parse("jobs\n").is_err());
+        assert!(parse("jobs = four\n").is_err());
+        assert!(parse("name = \"a\n").is_err());
+        assert!(parse("a = 1\na = 2\n").is_err());
+    }
+
+    #[test]
+    fn test_config_layers() {
+        let mut user = parse("[core]\njobs = 2\nwait = true\n[future]\nkey = 1\n").unwrap();
+        let mut repo = ConfigFile::default();
+        repo.set("core.jobs", "8").unwrap();
+        repo.set("gc.grace", "1h").unwrap();
+        user.merge(&repo);
+        assert_eq!(
+            Config::from_file(&user).unwrap(),
+            Config {
+                jobs: Some(8),
+                wait: Some(true),
+                grace: Some(Duration::from_secs(3600)),
+                ..Config::default()
+            }
+        );
+
+        assert!(repo.set("core.jobs", "0").is_err());
+        assert!(repo.set("core.abbrev", "3").is_err());
+        assert!(repo.set("core.abbrev", "17").is_err());
+        assert!(repo.set("gc.grace", "soon").is_err());
+        assert!(repo.set("core.color", "true").is_err());
+        assert!(Config::from_file(&parse("[core]\njobs = \"4\"\n").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_remotes() {
+        let mut file = parse("[remote.origin]\nurl = \"ssh://host/data\"\n").unwrap();
+        file.set("remote.backup.url", "s3://bucket/data").unwrap();
+        assert_eq!(parse(&file.to_toml()).unwrap(), file);
+        let config = Config::from_file(&file).unwrap();
+        assert_eq!(config.remote_url("origin"), Some("ssh://host/data"));
+        assert_eq!(config.remote_url("backup"), Some("s3://bucket/data"));
+        assert_eq!(config.remote_url("https://host/x"), Some("https://host/x"));
+        assert_eq!(config.remote_url("other"), None);
+
+        assert!(file.set("remote.origin.url", "/data").is_err());
+        assert!(file.set("remote.origin.url", "ftp://host/data").is_err());
+        assert!(file.set("remote.a.b.url", "ssh://host/data").is_err());
+        assert!(file.remove("remote.backup.url"));
+        assert!(!file.remove("remote.backup.url"));
+    }
+}
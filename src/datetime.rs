@@ -0,0 +1,229 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use clap::ValueEnum;
+
+use crate::ParseError;
+
+/// How timestamps are printed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum DateFormat {
+    /// RFC 3339 in UTC, e.g. "2024-01-15T12:00:00Z"
+    #[default]
+    Iso,
+    /// Seconds since the Unix epoch
+    Unix,
+    /// Time elapsed until now, e.g. "3 hours ago"
+    Relative,
+    /// RFC 3339 in the local timezone, e.g. "2024-01-15T21:00:00+09:00"
+    Local,
+}
+
+impl DateFormat {
+    /// Format seconds since the Unix epoch.
+    pub fn format(self, secs: u64) -> String {
+        match self {
+            DateFormat::Iso => format_timestamp(secs),
+            DateFormat::Unix => secs.to_string(),
+            DateFormat::Relative => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_secs());
+                format_relative(secs, now)
+            }
+            DateFormat::Local => format_offset(secs, local_offset(secs)),
+        }
+    }
+}
+
+/// Parse a duration such as "90", "30s", "15m", "12h", "7d" or "2w".
+///
+/// A bare number is taken as seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, ParseError> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Err(ParseError::EmptyToken);
+    }
+
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(pos) => s.split_at(pos),
+        None => (s, "s"),
+    };
+    let number: u64 = number
+        .parse()
+        .map_err(|_| ParseError::InvalidToken(s.to_string()))?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(ParseError::InvalidToken(s.to_string())),
+    };
+    number
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(|| ParseError::InvalidToken(s.to_string()))
+}
+
+/// Convert days since the Unix epoch to a (year, month, day) UTC date.
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Format seconds since the Unix epoch as an RFC 3339 UTC timestamp.
+pub fn format_timestamp(secs: u64) -> String {
+    format_offset(secs, 0)
+}
+
+/// Format seconds since the Unix epoch as a UTC timestamp without separators,
+/// e.g. "20240115T120000Z", for ref and file names.
+pub fn format_compact(secs: u64) -> String {
+    format_timestamp(secs)
+        .chars()
+        .filter(|c| *c != '-' && *c != ':')
+        .collect()
+}
+
+/// Format seconds since the Unix epoch as an RFC 3339 timestamp at an offset
+/// from UTC in seconds.
+pub fn format_offset(secs: u64, offset: i64) -> String {
+    let local = secs as i64 + offset;
+    let (year, month, day) = civil_from_days(local.div_euclid(86400));
+    let time = local.rem_euclid(86400);
+    let date = format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    );
+    match offset {
+        0 => format!("{}Z", date),
+        _ => {
+            let sign = if offset < 0 { '-' } else { '+' };
+            let offset = offset.abs();
+            format!(
+                "{}{}{:02}:{:02}",
+                date,
+                sign,
+                offset / 3600,
+                offset % 3600 / 60
+            )
+        }
+    }
+}
+
+/// Format the time from `secs` to `now` as e.g. "5 minutes ago".
+pub fn format_relative(secs: u64, now: u64) -> String {
+    if secs > now {
+        return "in the future".to_string();
+    }
+    let elapsed = now - secs;
+    let (count, unit) = match elapsed {
+        0..=59 => (elapsed, "second"),
+        60..=3599 => (elapsed / 60, "minute"),
+        3600..=86399 => (elapsed / 3600, "hour"),
+        // days up to two weeks, as "9 days" reads better than "1 week"
+        86400..=1209599 => (elapsed / 86400, "day"),
+        1209600..=5183999 => (elapsed / 604800, "week"),
+        5184000..=31535999 => (elapsed / 2592000, "month"),
+        _ => (elapsed / 31536000, "year"),
+    };
+    match count {
+        1 => format!("1 {} ago", unit),
+        _ => format!("{} {}s ago", count, unit),
+    }
+}
+
+// offset of the local timezone from UTC in seconds at the given time
+fn local_offset(secs: u64) -> i64 {
+    let time = secs as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return 0;
+    }
+    tm.tm_gmtoff as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        civil_from_days, format_compact, format_offset, format_relative, format_timestamp,
+        parse_duration,
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("15m").unwrap(), Duration::from_secs(900));
+        assert_eq!(parse_duration("12h").unwrap(), Duration::from_secs(43200));
+        assert_eq!(parse_duration("7d").unwrap(), Duration::from_secs(604800));
+        assert_eq!(parse_duration("2w").unwrap(), Duration::from_secs(1209600));
+
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("7y").is_err());
+        assert!(parse_duration("1h30m").is_err());
+        assert!(parse_duration("999999999999999w").is_err());
+        assert!(parse_duration("99999999999999999999").is_err());
+    }
+
+    #[test]
+    fn test_civil_from_days() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+        assert_eq!(civil_from_days(19782), (2024, 2, 29));
+        assert_eq!(civil_from_days(20742), (2026, 10, 16));
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(1705320000), "2024-01-15T12:00:00Z");
+        assert_eq!(format_timestamp(1709251199), "2024-02-29T23:59:59Z");
+        assert_eq!(format_compact(1705320000), "20240115T120000Z");
+    }
+
+    #[test]
+    fn test_format_offset() {
+        assert_eq!(format_offset(1705320000, 0), "2024-01-15T12:00:00Z");
+        assert_eq!(
+            format_offset(1705320000, 9 * 3600),
+            "2024-01-15T21:00:00+09:00"
+        );
+        assert_eq!(
+            format_offset(1705320000, -(5 * 3600 + 1800)),
+            "2024-01-15T06:30:00-05:30"
+        );
+        assert_eq!(format_offset(0, -3600), "1969-12-31T23:00:00-01:00");
+    }
+
+    #[test]
+    fn test_format_relative() {
+        let now = 1705320000;
+        assert_eq!(format_relative(now, now), "0 seconds ago");
+        assert_eq!(format_relative(now - 1, now), "1 second ago");
+        assert_eq!(format_relative(now - 300, now), "5 minutes ago");
+        assert_eq!(format_relative(now - 3600, now), "1 hour ago");
+        assert_eq!(format_relative(now - 9 * 86400, now), "9 days ago");
+        assert_eq!(format_relative(now - 21 * 86400, now), "3 weeks ago");
+        assert_eq!(format_relative(now - 90 * 86400, now), "3 months ago");
+        assert_eq!(format_relative(now - 800 * 86400, now), "2 years ago");
+        assert_eq!(format_relative(now + 10, now), "in the future");
+    }
+}
@@ -0,0 +1,111 @@
+//! Line-based deltas between objects, used to pack similar trees.
+//!
+//! A delta is a sequence of operations that rebuild the target from the
+//! lines of the base:
+//!
+//! - `=start,count\n` copies `count` lines of the base starting at `start`
+//! - `+len\n` followed by `len` bytes inserts those bytes
+
+use std::io::Write;
+
+use similar::{Algorithm, DiffOp};
+
+use crate::ParseError;
+
+/// Compute the delta that turns `base` into `target`.
+pub fn encode(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let base_lines = split_lines(base);
+    let target_lines = split_lines(target);
+
+    let mut delta = Vec::new();
+    let ops = similar::capture_diff_slices(Algorithm::Myers, &base_lines, &target_lines);
+    for op in ops {
+        match op {
+            DiffOp::Equal { old_index, len, .. } => {
+                writeln!(delta, "={},{}", old_index, len).unwrap();
+            }
+            DiffOp::Delete { .. } => {}
+            DiffOp::Insert {
+                new_index, new_len, ..
+            }
+            | DiffOp::Replace {
+                new_index, new_len, ..
+            } => {
+                let inserted = target_lines[new_index..new_index + new_len].concat();
+                writeln!(delta, "+{}", inserted.len()).unwrap();
+                delta.extend_from_slice(&inserted);
+            }
+        }
+    }
+    delta
+}
+
+/// Rebuild the target from `base` and a delta made by [`encode`].
+pub fn apply(base: &[u8], delta: &[u8]) -> Result<Vec<u8>, ParseError> {
+    let base_lines = split_lines(base);
+    let invalid = || ParseError::InvalidToken(String::from_utf8_lossy(delta).into_owned());
+
+    let mut target = Vec::with_capacity(base.len());
+    let mut rest = delta;
+    while !rest.is_empty() {
+        let end = rest.iter().position(|&b| b == b'\n').ok_or_else(invalid)?;
+        let op = std::str::from_utf8(&rest[..end]).map_err(|_| invalid())?;
+        rest = &rest[end + 1..];
+
+        if let Some(range) = op.strip_prefix('=') {
+            let (start, count) = range.split_once(',').ok_or_else(invalid)?;
+            let start: usize = start.parse().map_err(|_| invalid())?;
+            let count: usize = count.parse().map_err(|_| invalid())?;
+            let lines = base_lines.get(start..start + count).ok_or_else(invalid)?;
+            for line in lines {
+                target.extend_from_slice(line);
+            }
+        } else if let Some(len) = op.strip_prefix('+') {
+            let len: usize = len.parse().map_err(|_| invalid())?;
+            let inserted = rest.get(..len).ok_or_else(invalid)?;
+            target.extend_from_slice(inserted);
+            rest = &rest[len..];
+        } else {
+            return Err(invalid());
+        }
+    }
+    Ok(target)
+}
+
+// lines including their newline, so that joining them gives back the input
+fn split_lines(contents: &[u8]) -> Vec<&[u8]> {
+    contents.split_inclusive(|&b| b == b'\n').collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply, encode};
+
+    #[test]
+    fn test_delta() {
+        let base = b"tree\t1\ta\nfile\t2\tb\nfile\t3\tc\nfile\t4\td\n";
+        let cases: [&[u8]; 5] = [
+            b"tree\t1\ta\nfile\t2\tb\nfile\t3\tc\nfile\t4\td\n",
+            b"tree\t1\ta\nfile\t9\tb\nfile\t3\tc\nfile\t4\td\nfile\t5\te\n",
+            b"file\t4\td\n",
+            b"",
+            b"no newline at the end",
+        ];
+        for target in cases {
+            let delta = encode(base, target);
+            assert_eq!(apply(base, &delta).unwrap(), target);
+        }
+
+        let delta = encode(base, cases[1]);
+        assert_eq!(delta, b"=0,1\n+9\nfile\t9\tb\n=2,2\n+9\nfile\t5\te\n");
+    }
+
+    #[test]
+    fn test_invalid_delta() {
+        let base = b"a\nb\n";
+        assert!(apply(base, b"=0,3\n").is_err());
+        assert!(apply(base, b"+5\nab").is_err());
+        assert!(apply(base, b"-1\n").is_err());
+        assert!(apply(base, b"=0,1").is_err());
+    }
+}
@@ -1,8 +1,11 @@
 use std::io;
 use std::num::ParseIntError;
+use std::path::PathBuf;
 
 use redb::{StorageError, TableError, TransactionError};
 
+use crate::exit_code;
+
 #[derive(thiserror::Error, Debug)]
 pub enum ReadContentError {
     #[error("object not found")]
@@ -14,6 +17,34 @@ pub enum ReadContentError {
     #[error("absolute path is not supported")]
     AbsolutePathNotSupported,
 
+    #[error("drive letter in \"{0}\" is not supported, list paths relative to the root directory")]
+    DrivePathNotSupported(String),
+
+    #[error("\"..\" in \"{0}\" is not supported")]
+    ParentPathNotSupported(String),
+
+    // trees are lists of "kind<TAB>id<TAB>name" lines
+    #[error("tab or newline in {0:?} is not supported")]
+    TabOrNewlineNotSupported(String),
+
+    #[error("the pack is unreadable ({0}), run \"mtl pack rebuild\" to replace it")]
+    PackUnreadable(String),
+
+    #[error(transparent)]
+    Header(#[from] HeaderError),
+
+    #[error("object {id} is corrupt: {error}")]
+    CorruptObject { id: String, error: HeaderError },
+
+    #[error("ref \"{0}\" not found")]
+    RefNotFound(String),
+
+    #[error("no reflog entry for \"{0}\"")]
+    ReflogEntryNotFound(String),
+
+    #[error("short object ID \"{prefix}\" is ambiguous: {candidates}")]
+    AmbiguousObjectID { prefix: String, candidates: String },
+
     #[error(transparent)]
     IOError(#[from] io::Error),
 
@@ -31,6 +62,50 @@ pub enum ReadContentError {
 
     #[error(transparent)]
     TransactionError(#[from] TransactionError),
+
+    #[cfg(feature = "sqlite")]
+    #[error(transparent)]
+    SqliteError(#[from] rusqlite::Error),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigError {
+    #[error("invalid value \"{value}\" for {name}")]
+    InvalidValue { name: &'static str, value: String },
+
+    #[error("{}:{line}: {message}", .path.display())]
+    Syntax {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+
+    #[error("unknown config key \"{0}\"")]
+    UnknownKey(String),
+
+    #[error(transparent)]
+    IOError(#[from] io::Error),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum HeaderError {
+    #[error("unknown object kind {0}")]
+    UnknownKind(u8),
+
+    #[error("object format version {0} is not supported by this mtl")]
+    UnsupportedVersion(u8),
+
+    #[error("object has {actual} bytes, its header says {expected}")]
+    LengthMismatch { expected: u64, actual: u64 },
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum FilterError {
+    #[error(transparent)]
+    Glob(#[from] globset::Error),
+
+    #[error(transparent)]
+    Ignore(#[from] ignore::Error),
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -56,3 +131,120 @@ pub enum ParseHashError {
     #[error(transparent)]
     IntError(#[from] ParseIntError),
 }
+
+#[derive(thiserror::Error, Debug)]
+pub enum TreeError {
+    #[error("invalid path: \"{}\"", .0.display())]
+    InvalidPath(PathBuf),
+
+    #[error("conflicting entries for \"{}\"", .0.display())]
+    Conflict(PathBuf),
+
+    #[error("no entry at \"{}\"", .0.display())]
+    NotFound(PathBuf),
+
+    #[error("tree {0} not found")]
+    MissingTree(String),
+
+    #[error(transparent)]
+    ReadContentError(#[from] ReadContentError),
+
+    #[error(transparent)]
+    IOError(#[from] io::Error),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum HookError {
+    #[error("{hook} hook could not be run: {error}")]
+    Spawn {
+        hook: &'static str,
+        error: io::Error,
+    },
+
+    #[error("{hook} hook failed: {status}")]
+    Failed {
+        hook: &'static str,
+        status: std::process::ExitStatus,
+    },
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum LockError {
+    #[error("repository is busy: {} is held by another process ({holder}), retry later or pass --wait", path.display())]
+    Busy { path: PathBuf, holder: String },
+
+    #[error(transparent)]
+    IOError(#[from] io::Error),
+}
+
+/// The failures of [`crate::Context::new`] and
+/// [`crate::repository::Repository`] that callers may want to tell apart.
+/// Everything else is [`MtlError::Other`].
+#[derive(thiserror::Error, Debug)]
+pub enum MtlError {
+    #[error("{} is not a directory", .0.display())]
+    NotARepository(PathBuf),
+
+    #[error("repository is busy: {} is held by another process ({holder}), retry later or pass --wait", path.display())]
+    LockHeld { path: PathBuf, holder: String },
+
+    #[error("object {id} is corrupt: {reason}")]
+    CorruptObject { id: String, reason: String },
+
+    #[error("object not found")]
+    ObjectNotFound,
+
+    #[error("ref \"{name}\" not found")]
+    RefNotFound { name: String },
+
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl MtlError {
+    /// The exit status of the CLI for this error, see [`crate::exit_code`].
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            MtlError::NotARepository(_) => exit_code::NOT_A_REPOSITORY,
+            MtlError::LockHeld { .. } => exit_code::LOCK_BUSY,
+            MtlError::CorruptObject { .. } => exit_code::CORRUPTION,
+            MtlError::ObjectNotFound | MtlError::RefNotFound { .. } => exit_code::NOT_FOUND,
+            MtlError::Other(_) => exit_code::FAILURE,
+        }
+    }
+}
+
+impl From<LockError> for MtlError {
+    fn from(e: LockError) -> Self {
+        match e {
+            LockError::Busy { path, holder } => MtlError::LockHeld { path, holder },
+            e => MtlError::Other(e.into()),
+        }
+    }
+}
+
+impl From<ReadContentError> for MtlError {
+    fn from(e: ReadContentError) -> Self {
+        match e {
+            ReadContentError::ObjectNotFound => MtlError::ObjectNotFound,
+            ReadContentError::RefNotFound(name) => MtlError::RefNotFound { name },
+            ReadContentError::CorruptObject { id, error } => MtlError::CorruptObject {
+                id,
+                reason: error.to_string(),
+            },
+            e => MtlError::Other(e.into()),
+        }
+    }
+}
+
+impl From<FilterError> for MtlError {
+    fn from(e: FilterError) -> Self {
+        MtlError::Other(e.into())
+    }
+}
+
+impl From<io::Error> for MtlError {
+    fn from(e: io::Error) -> Self {
+        MtlError::Other(e.into())
+    }
+}
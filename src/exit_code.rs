@@ -0,0 +1,41 @@
+//! Exit statuses of the CLI, for scripts to branch on instead of reading
+//! stderr. 0 is success.
+
+use crate::{LockError, MtlError, ReadContentError};
+
+/// `diff` found differences
+pub const DIFFERENCES: u8 = 1;
+/// Invalid command line, as reported by clap
+pub const USAGE: u8 = 2;
+/// The repository directory does not exist
+pub const NOT_A_REPOSITORY: u8 = 3;
+/// Another process holds the repository lock
+pub const LOCK_BUSY: u8 = 4;
+/// A corrupt object was read, or found by `stats --sample`
+pub const CORRUPTION: u8 = 5;
+/// An object or ref does not exist
+pub const NOT_FOUND: u8 = 6;
+/// Any other error
+pub const FAILURE: u8 = 7;
+
+/// The exit status for an error returned by a command, going by the
+/// outermost error in its chain with a status of its own.
+pub fn of(err: &anyhow::Error) -> u8 {
+    err.chain()
+        .find_map(|cause| {
+            if let Some(e) = cause.downcast_ref::<MtlError>() {
+                return Some(e.exit_code()).filter(|&code| code != FAILURE);
+            }
+            if let Some(LockError::Busy { .. }) = cause.downcast_ref::<LockError>() {
+                return Some(LOCK_BUSY);
+            }
+            match cause.downcast_ref::<ReadContentError>()? {
+                ReadContentError::CorruptObject { .. } => Some(CORRUPTION),
+                ReadContentError::ObjectNotFound | ReadContentError::RefNotFound(_) => {
+                    Some(NOT_FOUND)
+                }
+                _ => None,
+            }
+        })
+        .unwrap_or(FAILURE)
+}
@@ -2,10 +2,14 @@ use std::borrow::Cow;
 use std::ffi::OsStr;
 use std::fs;
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use clap::ValueEnum;
 
+use crate::ReadContentError;
+
 #[derive(Debug, Clone, Copy, ValueEnum)]
 pub enum Advise {
     Normal,
@@ -54,14 +58,81 @@ pub fn fadvise(
     Ok(())
 }
 
+/// Write `path` by renaming a finished file from `tmp_dir` over it, so that
+/// readers and other processes writing the same path never see it
+/// half-written. `tmp_dir` must be on the same filesystem as `path`.
+pub fn write_atomic(path: &Path, tmp_dir: &Path, contents: &[u8]) -> io::Result<()> {
+    let tmp_file = write_tmp(tmp_dir, contents)?;
+    let result = fs::rename(&tmp_file, path);
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_file);
+    }
+    result
+}
+
+/// Like [`write_atomic`], but fails with [`io::ErrorKind::AlreadyExists`]
+/// instead of replacing `path`, even when another process creates it at
+/// the same time.
+pub fn create_atomic(path: &Path, tmp_dir: &Path, contents: &[u8]) -> io::Result<()> {
+    let tmp_file = write_tmp(tmp_dir, contents)?;
+    // unlike a rename, a link never replaces its target
+    let result = fs::hard_link(&tmp_file, path);
+    let _ = fs::remove_file(&tmp_file);
+    result
+}
+
+// a new file in tmp_dir holding `contents`
+fn write_tmp(tmp_dir: &Path, contents: &[u8]) -> io::Result<PathBuf> {
+    // threads of one process may write the same object at the same time
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    fs::create_dir_all(tmp_dir)?;
+    let tmp_file = tmp_dir.join(format!(
+        "{}-{}",
+        process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    if let Err(e) = fs::write(&tmp_file, contents) {
+        let _ = fs::remove_file(&tmp_file);
+        return Err(e);
+    }
+    Ok(tmp_file)
+}
+
+/// Flush pending writes of the filesystem containing `path` to disk.
+#[cfg(target_os = "linux")]
+pub fn syncfs<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+    let file = fs::File::open(path)?;
+
+    let ret = unsafe { libc::syncfs(file.as_raw_fd()) };
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+pub fn syncfs<P: AsRef<Path>>(_path: P) -> io::Result<()> {
+    unsafe { libc::sync() };
+    Ok(())
+}
+
+#[cfg(not(unix))]
+#[inline]
+pub fn syncfs<P: AsRef<Path>>(_path: P) -> io::Result<()> {
+    Ok(())
+}
+
 #[cfg(any(unix, target_os = "redox"))]
-pub fn osstr_to_bytes(input: &OsStr) -> Cow<[u8]> {
+pub fn osstr_to_bytes(input: &OsStr) -> Cow<'_, [u8]> {
     use std::os::unix::ffi::OsStrExt;
     Cow::Borrowed(input.as_bytes())
 }
 
 #[cfg(windows)]
-pub fn osstr_to_bytes(input: &OsStr) -> Cow<[u8]> {
+pub fn osstr_to_bytes(input: &OsStr) -> Cow<'_, [u8]> {
     let string = input.to_string_lossy();
 
     match string {
@@ -86,9 +157,45 @@ pub fn strip_current_dir(path: &Path) -> &Path {
     path.strip_prefix(".").unwrap_or(path)
 }
 
+/// Normalize a path from a file list to the "a/b/c" form trees use.
+///
+/// Empty and "." components are dropped. With `windows`, `\` is accepted as
+/// a separator and paths with a drive letter are rejected.
+pub fn normalize_listed_path(path: &str, windows: bool) -> Result<String, ReadContentError> {
+    let normalized = match windows {
+        true => Cow::Owned(path.replace('\\', "/")),
+        false => Cow::Borrowed(path),
+    };
+    if windows && has_drive_letter(&normalized) {
+        return Err(ReadContentError::DrivePathNotSupported(path.to_string()));
+    }
+    if normalized.starts_with('/') {
+        return Err(ReadContentError::AbsolutePathNotSupported);
+    }
+
+    let mut names = Vec::new();
+    for name in normalized.split('/') {
+        match name {
+            "" | "." => continue,
+            ".." => return Err(ReadContentError::ParentPathNotSupported(path.to_string())),
+            name if name.contains(['\t', '\n']) => {
+                return Err(ReadContentError::TabOrNewlineNotSupported(path.to_string()))
+            }
+            name => names.push(name),
+        }
+    }
+    Ok(names.join("/"))
+}
+
+fn has_drive_letter(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':'
+}
+
 #[cfg(test)]
 mod tests {
-    use super::strip_current_dir;
+    use super::{normalize_listed_path, strip_current_dir};
+    use crate::ReadContentError;
     use std::path::Path;
 
     #[test]
@@ -104,4 +211,59 @@ mod tests {
             Path::new("foo/bar/baz")
         );
     }
+
+    #[test]
+    fn normalize_listed_path_unix() {
+        assert_eq!(
+            normalize_listed_path("./foo/bar/", false).unwrap(),
+            "foo/bar"
+        );
+        assert_eq!(
+            normalize_listed_path("foo//./bar", false).unwrap(),
+            "foo/bar"
+        );
+        assert_eq!(normalize_listed_path("./", false).unwrap(), "");
+        assert_eq!(
+            normalize_listed_path("foo\\bar", false).unwrap(),
+            "foo\\bar"
+        );
+        assert_eq!(normalize_listed_path("C:foo", false).unwrap(), "C:foo");
+        assert!(matches!(
+            normalize_listed_path("/foo", false),
+            Err(ReadContentError::AbsolutePathNotSupported)
+        ));
+        assert!(matches!(
+            normalize_listed_path("foo/../bar", false),
+            Err(ReadContentError::ParentPathNotSupported(_))
+        ));
+        assert!(matches!(
+            normalize_listed_path("foo/a\nb", false),
+            Err(ReadContentError::TabOrNewlineNotSupported(_))
+        ));
+    }
+
+    #[test]
+    fn normalize_listed_path_windows() {
+        assert_eq!(normalize_listed_path("foo\\bar", true).unwrap(), "foo/bar");
+        assert_eq!(
+            normalize_listed_path(".\\foo\\bar\\", true).unwrap(),
+            "foo/bar"
+        );
+        assert_eq!(
+            normalize_listed_path("foo/bar\\baz", true).unwrap(),
+            "foo/bar/baz"
+        );
+        for path in ["C:\\data\\foo", "c:foo", "D:/foo"] {
+            assert!(matches!(
+                normalize_listed_path(path, true),
+                Err(ReadContentError::DrivePathNotSupported(_))
+            ));
+        }
+        for path in ["\\foo", "\\\\server\\share\\foo"] {
+            assert!(matches!(
+                normalize_listed_path(path, true),
+                Err(ReadContentError::AbsolutePathNotSupported)
+            ));
+        }
+    }
 }
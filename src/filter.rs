@@ -1,11 +1,36 @@
+//! Selection of the paths a build takes.
+//!
+//! Filters are combined by wrapping one in another, e.g. a [`GlobFilter`]
+//! around a [`PathFilter`], or with [`AndFilter`], [`OrFilter`] and
+//! [`NotFilter`]. [`FilterBuilder`] assembles the usual combination.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
 
-use crate::{RelativePath, MTL_DIR};
+use crate::{FilterError, RelativePath, MTL_DIR};
 
+/// Decides which entries below `root` are taken.
+///
+/// Paths are relative to the root. A directory that doesn't match is not
+/// descended into, so filters keep the directories that may have matching
+/// entries below them.
 pub trait Filter: Send + Sync {
     fn root(&self) -> &Path;
 
     fn path_matches(&self, path: &RelativePath) -> bool;
+
+    /// Like `path_matches`, for filters that treat directories differently.
+    /// Directories that don't match are not descended into.
+    fn entry_matches(&self, path: &RelativePath, _is_dir: bool) -> bool {
+        self.path_matches(path)
+    }
 }
 
 #[derive(Clone)]
@@ -75,6 +100,592 @@ impl Filter for PathFilter {
     }
 }
 
+/// Narrows another filter with globs over the relative path.
+///
+/// Excluded files and directories are skipped. When there are include globs,
+/// only the files matching one of them are kept; directories are always
+/// descended into, since something below them may match.
+pub struct GlobFilter {
+    inner: Box<dyn Filter>,
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+    report: Option<Arc<SkipReport>>,
+}
+
+impl GlobFilter {
+    pub fn new(
+        inner: Box<dyn Filter>,
+        include: &[Glob],
+        exclude: &[Glob],
+    ) -> Result<Self, globset::Error> {
+        let include = match include.is_empty() {
+            true => None,
+            false => Some(glob_set(include)?),
+        };
+        Ok(Self {
+            inner,
+            include,
+            exclude: glob_set(exclude)?,
+            report: None,
+        })
+    }
+
+    /// Record the entries skipped by the globs in `report`.
+    pub fn with_report(mut self, report: Arc<SkipReport>) -> Self {
+        self.report = Some(report);
+        self
+    }
+}
+
+fn glob_set(globs: &[Glob]) -> Result<GlobSet, globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(glob.clone());
+    }
+    builder.build()
+}
+
+impl Filter for GlobFilter {
+    fn root(&self) -> &Path {
+        self.inner.root()
+    }
+
+    fn path_matches(&self, path: &RelativePath) -> bool {
+        self.entry_matches(path, false)
+    }
+
+    fn entry_matches(&self, path: &RelativePath, is_dir: bool) -> bool {
+        if !self.inner.entry_matches(path, is_dir) {
+            return false;
+        }
+        if path.is_root() {
+            return true;
+        }
+
+        let name = path.to_string();
+        // "dir/" so that "**/tmp/**" excludes the tmp directory itself
+        let excluded =
+            self.exclude.is_match(&name) || (is_dir && self.exclude.is_match(format!("{}/", name)));
+        let reason = if excluded {
+            SkipReason::Excluded
+        } else if is_dir || self.include.as_ref().is_none_or(|i| i.is_match(&name)) {
+            return true;
+        } else {
+            SkipReason::NotIncluded
+        };
+        if let Some(report) = &self.report {
+            report.record(path, is_dir, reason);
+        }
+        false
+    }
+}
+
+/// Takes the entries both filters take. The root is the first filter's.
+pub struct AndFilter {
+    left: Box<dyn Filter>,
+    right: Box<dyn Filter>,
+}
+
+impl AndFilter {
+    pub fn new(left: Box<dyn Filter>, right: Box<dyn Filter>) -> Self {
+        Self { left, right }
+    }
+}
+
+impl Filter for AndFilter {
+    fn root(&self) -> &Path {
+        self.left.root()
+    }
+
+    fn path_matches(&self, path: &RelativePath) -> bool {
+        self.entry_matches(path, false)
+    }
+
+    fn entry_matches(&self, path: &RelativePath, is_dir: bool) -> bool {
+        self.left.entry_matches(path, is_dir) && self.right.entry_matches(path, is_dir)
+    }
+}
+
+/// Takes the entries either filter takes. The root is the first filter's.
+pub struct OrFilter {
+    left: Box<dyn Filter>,
+    right: Box<dyn Filter>,
+}
+
+impl OrFilter {
+    pub fn new(left: Box<dyn Filter>, right: Box<dyn Filter>) -> Self {
+        Self { left, right }
+    }
+}
+
+impl Filter for OrFilter {
+    fn root(&self) -> &Path {
+        self.left.root()
+    }
+
+    fn path_matches(&self, path: &RelativePath) -> bool {
+        self.entry_matches(path, false)
+    }
+
+    fn entry_matches(&self, path: &RelativePath, is_dir: bool) -> bool {
+        self.left.entry_matches(path, is_dir) || self.right.entry_matches(path, is_dir)
+    }
+}
+
+/// Takes the files the inner filter doesn't.
+///
+/// Directories are always descended into: the inner filter keeps a directory
+/// when something below it may match, which says nothing about the rest.
+/// Use it inside an [`AndFilter`] with a [`MatchAllFilter`] to keep skipping
+/// `.mtl`.
+pub struct NotFilter {
+    inner: Box<dyn Filter>,
+}
+
+impl NotFilter {
+    pub fn new(inner: Box<dyn Filter>) -> Self {
+        Self { inner }
+    }
+}
+
+impl Filter for NotFilter {
+    fn root(&self) -> &Path {
+        self.inner.root()
+    }
+
+    fn path_matches(&self, path: &RelativePath) -> bool {
+        self.entry_matches(path, false)
+    }
+
+    fn entry_matches(&self, path: &RelativePath, is_dir: bool) -> bool {
+        is_dir || !self.inner.entry_matches(path, is_dir)
+    }
+}
+
+/// Skips the paths matched by gitignore-syntax files, for the targets that
+/// aren't scanned, where the walker applies `.gitignore` and friends itself.
+pub struct IgnoreFilter {
+    root: PathBuf,
+    gitignore: Gitignore,
+    report: Option<Arc<SkipReport>>,
+}
+
+impl IgnoreFilter {
+    /// Read the rules of `files`; their patterns are relative to `root`.
+    pub fn new(root: PathBuf, files: &[PathBuf]) -> Result<Self, ignore::Error> {
+        let mut builder = GitignoreBuilder::new(&root);
+        for file in files {
+            if let Some(e) = builder.add(file) {
+                return Err(e);
+            }
+        }
+        Ok(Self {
+            gitignore: builder.build()?,
+            root,
+            report: None,
+        })
+    }
+
+    /// Record the entries skipped by the rules in `report`.
+    pub fn with_report(mut self, report: Arc<SkipReport>) -> Self {
+        self.report = Some(report);
+        self
+    }
+}
+
+impl Filter for IgnoreFilter {
+    fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn path_matches(&self, path: &RelativePath) -> bool {
+        self.entry_matches(path, false)
+    }
+
+    fn entry_matches(&self, path: &RelativePath, is_dir: bool) -> bool {
+        if path.is_root() {
+            return true;
+        }
+        let ignored = self
+            .gitignore
+            .matched_path_or_any_parents(path.as_path(), is_dir)
+            .is_ignore();
+        if ignored {
+            if let Some(report) = &self.report {
+                report.record(path, is_dir, SkipReason::Ignored);
+            }
+        }
+        !ignored
+    }
+}
+
+/// Which hidden entries, those named with a leading dot, a scan takes.
+///
+/// Hidden entries are taken if they match an include glob, or if they match
+/// no exclude glob and all hidden entries are taken. Entries that aren't
+/// hidden are always taken; those below an included hidden directory are
+/// therefore taken unless hidden themselves.
+#[derive(Clone, Debug)]
+pub struct HiddenPolicy {
+    take_all: bool,
+    include: GlobSet,
+    exclude: GlobSet,
+}
+
+impl HiddenPolicy {
+    pub fn new(take_all: bool, include: &[Glob], exclude: &[Glob]) -> Result<Self, globset::Error> {
+        Ok(Self {
+            take_all,
+            include: glob_set(include)?,
+            exclude: glob_set(exclude)?,
+        })
+    }
+
+    pub fn takes(&self, path: &RelativePath, is_dir: bool) -> bool {
+        let hidden = path
+            .as_path()
+            .file_name()
+            .is_some_and(|name| name.as_encoded_bytes().starts_with(b"."));
+        if !hidden {
+            return true;
+        }
+        let path = path.to_string();
+        // "dir/" as for GlobFilter
+        let matches =
+            |set: &GlobSet| set.is_match(&path) || (is_dir && set.is_match(format!("{}/", path)));
+        if matches(&self.include) {
+            return true;
+        }
+        self.take_all && !matches(&self.exclude)
+    }
+}
+
+impl From<bool> for HiddenPolicy {
+    /// Take all hidden entries or none of them.
+    fn from(take_all: bool) -> Self {
+        Self {
+            take_all,
+            include: GlobSet::empty(),
+            exclude: GlobSet::empty(),
+        }
+    }
+}
+
+/// Why a filter skipped an entry, for a [`SkipReport`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SkipReason {
+    TooSmall,
+    TooLarge,
+    /// Neither a regular file nor a directory, e.g. a FIFO or a symlink
+    Special,
+    /// Matched by an exclude glob
+    Excluded,
+    /// A file matched by none of the include globs
+    NotIncluded,
+    /// Matched by a rule of an ignore file
+    Ignored,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match self {
+            SkipReason::TooSmall => "too-small",
+            SkipReason::TooLarge => "too-large",
+            SkipReason::Special => "special",
+            SkipReason::Excluded => "excluded",
+            SkipReason::NotIncluded => "not-included",
+            SkipReason::Ignored => "ignored",
+        };
+        f.write_str(reason)
+    }
+}
+
+/// The entries skipped by the filters reporting to it, shared with them
+/// while a build runs. A skipped directory stands for everything below it,
+/// which is never looked at.
+#[derive(Debug, Default)]
+pub struct SkipReport {
+    entries: Mutex<Vec<SkippedEntry>>,
+}
+
+/// One entry of a [`SkipReport`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedEntry {
+    pub path: RelativePath,
+    pub is_dir: bool,
+    pub reason: SkipReason,
+}
+
+impl SkipReport {
+    pub fn record(&self, path: &RelativePath, is_dir: bool, reason: SkipReason) {
+        self.entries.lock().unwrap().push(SkippedEntry {
+            path: path.clone(),
+            is_dir,
+            reason,
+        });
+    }
+
+    /// The skipped entries, sorted by path.
+    pub fn entries(&self) -> Vec<SkippedEntry> {
+        let mut entries = self.entries.lock().unwrap().clone();
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        entries
+    }
+
+    /// The number of skipped entries for each reason, in the order of
+    /// [`SkipReason`].
+    pub fn counts(&self) -> Vec<(SkipReason, usize)> {
+        let entries = self.entries.lock().unwrap();
+        let mut counts = BTreeMap::new();
+        for entry in entries.iter() {
+            *counts.entry(entry.reason).or_default() += 1;
+        }
+        counts.into_iter().collect()
+    }
+
+    /// Write "reason<TAB>path" lines.
+    pub fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        for entry in self.entries() {
+            writeln!(w, "{}\t{}", entry.reason, entry.path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Takes the regular files whose size is within bounds, read from the
+/// filesystem. Anything else is always taken.
+pub struct SizeFilter {
+    root: PathBuf,
+    min: Option<u64>,
+    max: Option<u64>,
+    report: Option<Arc<SkipReport>>,
+}
+
+impl SizeFilter {
+    pub fn new(root: PathBuf, min: Option<u64>, max: Option<u64>) -> Self {
+        Self {
+            root,
+            min,
+            max,
+            report: None,
+        }
+    }
+
+    /// Record the files skipped in `report`.
+    pub fn with_report(mut self, report: Arc<SkipReport>) -> Self {
+        self.report = Some(report);
+        self
+    }
+}
+
+impl Filter for SizeFilter {
+    fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn path_matches(&self, path: &RelativePath) -> bool {
+        self.entry_matches(path, false)
+    }
+
+    fn entry_matches(&self, path: &RelativePath, is_dir: bool) -> bool {
+        if is_dir {
+            return true;
+        }
+        // files that can't be read are left for the build to report, and
+        // special files for the file type filter
+        let Ok(metadata) = fs::symlink_metadata(self.root.join(path.as_path())) else {
+            return true;
+        };
+        if !metadata.is_file() {
+            return true;
+        }
+        let size = metadata.len();
+        let reason = if self.min.is_some_and(|min| size < min) {
+            SkipReason::TooSmall
+        } else if self.max.is_some_and(|max| size > max) {
+            SkipReason::TooLarge
+        } else {
+            return true;
+        };
+        if let Some(report) = &self.report {
+            report.record(path, false, reason);
+        }
+        false
+    }
+}
+
+/// Takes only regular files and directories, skipping FIFOs, sockets,
+/// devices and symlinks. Scans skip these anyway; listed paths are taken as
+/// files otherwise, and reading a FIFO blocks the build.
+pub struct FileTypeFilter {
+    root: PathBuf,
+    report: Option<Arc<SkipReport>>,
+}
+
+impl FileTypeFilter {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root, report: None }
+    }
+
+    /// Record the files skipped in `report`.
+    pub fn with_report(mut self, report: Arc<SkipReport>) -> Self {
+        self.report = Some(report);
+        self
+    }
+}
+
+impl Filter for FileTypeFilter {
+    fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn path_matches(&self, path: &RelativePath) -> bool {
+        self.entry_matches(path, false)
+    }
+
+    fn entry_matches(&self, path: &RelativePath, _is_dir: bool) -> bool {
+        if path.is_root() {
+            return true;
+        }
+        let Ok(metadata) = fs::symlink_metadata(self.root.join(path.as_path())) else {
+            return true;
+        };
+        let file_type = metadata.file_type();
+        if file_type.is_file() || file_type.is_dir() {
+            return true;
+        }
+        if let Some(report) = &self.report {
+            report.record(path, false, SkipReason::Special);
+        }
+        false
+    }
+}
+
+/// Assembles the path, glob, ignore-file, file type and size filters into one.
+///
+/// ```
+/// use globset::Glob;
+/// use mtl::filter::FilterBuilder;
+///
+/// let filter = FilterBuilder::new("/data")
+///     .with_path("logs")
+///     .with_globs(&[Glob::new("**/*.log").unwrap()], &[])
+///     .with_size_range(None, Some(1 << 20))
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct FilterBuilder {
+    root: PathBuf,
+    path: Option<RelativePath>,
+    include: Vec<Glob>,
+    exclude: Vec<Glob>,
+    ignore_files: Vec<PathBuf>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    skip_special: bool,
+    report: Option<Arc<SkipReport>>,
+    report_rules: bool,
+}
+
+impl FilterBuilder {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Only take the entries below `path`.
+    pub fn with_path(mut self, path: impl Into<RelativePath>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Add include and exclude globs, see [`GlobFilter`].
+    pub fn with_globs(mut self, include: &[Glob], exclude: &[Glob]) -> Self {
+        self.include.extend_from_slice(include);
+        self.exclude.extend_from_slice(exclude);
+        self
+    }
+
+    /// Skip the paths matched by a gitignore-syntax file.
+    pub fn with_ignore_file(mut self, file: impl Into<PathBuf>) -> Self {
+        self.ignore_files.push(file.into());
+        self
+    }
+
+    /// Only take files of `min` to `max` bytes, both inclusive.
+    pub fn with_size_range(mut self, min: Option<u64>, max: Option<u64>) -> Self {
+        self.min_size = min;
+        self.max_size = max;
+        self
+    }
+
+    /// Skip what isn't a regular file or a directory, see [`FileTypeFilter`].
+    pub fn with_skip_special(mut self, skip_special: bool) -> Self {
+        self.skip_special = skip_special;
+        self
+    }
+
+    /// Record the files skipped by size or file type in `report`.
+    pub fn with_report(mut self, report: Arc<SkipReport>) -> Self {
+        self.report = Some(report);
+        self
+    }
+
+    /// Like `with_report`, also recording the entries skipped by globs and
+    /// ignore files.
+    pub fn with_full_report(mut self, report: Arc<SkipReport>) -> Self {
+        self.report = Some(report);
+        self.report_rules = true;
+        self
+    }
+
+    // the report for the glob and ignore file filters, if they have one
+    fn rule_report(&self) -> Option<Arc<SkipReport>> {
+        self.report.clone().filter(|_| self.report_rules)
+    }
+
+    pub fn build(&self) -> Result<Box<dyn Filter>, FilterError> {
+        let root = self.root.clone();
+        let mut filter: Box<dyn Filter> = match &self.path {
+            Some(path) => Box::new(PathFilter::new(root.clone(), path.clone())),
+            None => Box::new(MatchAllFilter::new(root.clone())),
+        };
+        if !self.include.is_empty() || !self.exclude.is_empty() {
+            let mut glob = GlobFilter::new(filter, &self.include, &self.exclude)?;
+            if let Some(report) = self.rule_report() {
+                glob = glob.with_report(report);
+            }
+            filter = Box::new(glob);
+        }
+        if !self.ignore_files.is_empty() {
+            let mut ignore = IgnoreFilter::new(root.clone(), &self.ignore_files)?;
+            if let Some(report) = self.rule_report() {
+                ignore = ignore.with_report(report);
+            }
+            filter = Box::new(AndFilter::new(filter, Box::new(ignore)));
+        }
+        // before the size filter, so that a FIFO is reported as special
+        if self.skip_special {
+            let mut file_type = FileTypeFilter::new(root.clone());
+            if let Some(report) = &self.report {
+                file_type = file_type.with_report(report.clone());
+            }
+            filter = Box::new(AndFilter::new(filter, Box::new(file_type)));
+        }
+        if self.min_size.is_some() || self.max_size.is_some() {
+            let mut size = SizeFilter::new(root, self.min_size, self.max_size);
+            if let Some(report) = &self.report {
+                size = size.with_report(report.clone());
+            }
+            filter = Box::new(AndFilter::new(filter, Box::new(size)));
+        }
+        Ok(filter)
+    }
+}
+
 #[allow(dead_code)]
 pub fn path_clean(path: &std::path::Path) -> std::path::PathBuf {
     let mut ret = std::path::PathBuf::new();
@@ -101,9 +712,14 @@ pub fn path_clean(path: &std::path::Path) -> std::path::PathBuf {
 
 #[cfg(test)]
 mod tests {
-    use crate::filter::{path_clean, Filter, PathFilter};
+    use crate::filter::{
+        path_clean, AndFilter, Filter, GlobFilter, HiddenPolicy, MatchAllFilter, NotFilter,
+        OrFilter, PathFilter, SkipReason, SkipReport,
+    };
     use crate::RelativePath;
+    use globset::Glob;
     use std::path::PathBuf;
+    use std::sync::Arc;
 
     #[test]
     fn test_filter() {
@@ -158,6 +774,106 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_glob_filter() {
+        let inner = Box::new(MatchAllFilter::new(PathBuf::new()));
+        let include = [Glob::new("**/*.bin").unwrap()];
+        let exclude = [Glob::new("**/tmp/**").unwrap()];
+        let filter = GlobFilter::new(inner, &include, &exclude).unwrap();
+
+        let table = [
+            ("a.bin", false, true),
+            ("data/a.bin", false, true),
+            ("data/a.txt", false, false),
+            ("data", true, true),
+            ("data/tmp", true, false),
+            ("data/tmp/a.bin", false, false),
+            (".mtl", true, false),
+        ];
+        for (path, is_dir, expected) in table {
+            let path = RelativePath::from(path);
+            assert_eq!(filter.entry_matches(&path, is_dir), expected, "{}", path);
+        }
+        assert!(filter.entry_matches(&RelativePath::Root, true));
+    }
+
+    #[test]
+    fn test_hidden_policy() {
+        let include = [Glob::new("**/.well-known").unwrap()];
+        let exclude = [Glob::new("**/.cache").unwrap()];
+        let none = HiddenPolicy::new(false, &include, &exclude).unwrap();
+        let all = HiddenPolicy::new(true, &include, &exclude).unwrap();
+
+        // (path, is_dir, none, all)
+        let table = [
+            ("a", false, true, true),
+            (".a", false, false, true),
+            ("d/.well-known", true, true, true),
+            ("d/.well-known/a", false, true, true),
+            (".cache", true, false, false),
+        ];
+        for (path, is_dir, expected_none, expected_all) in table {
+            let path = RelativePath::from(path);
+            assert_eq!(none.takes(&path, is_dir), expected_none, "none {}", path);
+            assert_eq!(all.takes(&path, is_dir), expected_all, "all {}", path);
+        }
+        assert!(!HiddenPolicy::from(false).takes(&RelativePath::from(".a"), false));
+    }
+
+    #[test]
+    fn test_skip_report() {
+        let report = Arc::new(SkipReport::default());
+        let inner = Box::new(MatchAllFilter::new(PathBuf::new()));
+        let include = [Glob::new("**/*.bin").unwrap()];
+        let exclude = [Glob::new("**/tmp/**").unwrap()];
+        let filter = GlobFilter::new(inner, &include, &exclude)
+            .unwrap()
+            .with_report(report.clone());
+
+        for (path, is_dir) in [
+            ("tmp", true),
+            ("a.txt", false),
+            ("b.txt", false),
+            ("a.bin", false),
+        ] {
+            filter.entry_matches(&RelativePath::from(path), is_dir);
+        }
+        let entries = report.entries();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[2].path, RelativePath::from("tmp"));
+        assert!(entries[2].is_dir);
+        assert_eq!(
+            report.counts(),
+            [(SkipReason::Excluded, 1), (SkipReason::NotIncluded, 2)]
+        );
+    }
+
+    #[test]
+    fn test_combinators() {
+        let path = |target: &str| Box::new(PathFilter::new(PathBuf::new(), target));
+        let all = || Box::new(MatchAllFilter::new(PathBuf::new()));
+
+        let and = AndFilter::new(path("a"), path("a/b"));
+        let or = OrFilter::new(path("a/b"), path("c"));
+        let not = AndFilter::new(all(), Box::new(NotFilter::new(path("a/b"))));
+
+        // (path, is_dir, and, or, not)
+        let table = [
+            ("a", true, true, true, true),
+            ("a/b", true, true, true, true),
+            ("a/b/f", false, true, true, false),
+            ("a/f", false, false, false, true),
+            ("c/f", false, false, true, true),
+            (".mtl", true, false, false, false),
+        ];
+        for (p, is_dir, expected_and, expected_or, expected_not) in table {
+            let p = RelativePath::from(p);
+            assert_eq!(and.entry_matches(&p, is_dir), expected_and, "and {}", p);
+            assert_eq!(or.entry_matches(&p, is_dir), expected_or, "or {}", p);
+            assert_eq!(not.entry_matches(&p, is_dir), expected_not, "not {}", p);
+        }
+    }
+
     #[test]
     fn path_clean_test() {
         let path = path_clean(std::path::Path::new("/foo/bar/baz/.././foo"));
@@ -1,9 +1,18 @@
 use byteorder::ByteOrder;
 use std::borrow::Borrow;
 use std::fmt;
+use std::io::{self, Read};
+
+use xxhash_rust::xxh3::Xxh3;
 
 use crate::ParseHashError;
 
+/// Name of the hash behind object IDs.
+pub const ALGORITHM: &str = "xxh3-64";
+
+// bytes read at a time by hash_reader
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
 #[derive(Debug, PartialEq, Clone, Copy, Eq, std::hash::Hash, PartialOrd, Ord)]
 pub struct Hash {
     xxh3: u64,
@@ -54,6 +63,22 @@ pub fn xxh3_contents<T: AsRef<[u8]>>(contents: T) -> u64 {
     xxhash_rust::xxh3::xxh3_64(contents.as_ref())
 }
 
+/// Hash everything `reader` yields, a buffer at a time, so that large files
+/// need not fit in memory. Gives the same hash as [`Hash::from_contents`].
+pub fn hash_reader<R: Read>(mut reader: R) -> io::Result<Hash> {
+    let mut hasher = Xxh3::new();
+    let mut buf = vec![0; READ_BUFFER_SIZE];
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => hasher.update(&buf[..n]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(Hash::new(hasher.digest()))
+}
+
 pub fn xxh64_contents<T: AsRef<[u8]>>(contents: T) -> u64 {
     xxhash_rust::xxh64::xxh64(contents.as_ref(), 0)
 }
@@ -73,6 +98,22 @@ mod tests {
         assert_eq!(actual, super::Hash::from_hex("d447b1ea40e6988b").unwrap());
     }
 
+    #[test]
+    fn test_hash_reader() {
+        let contents: Vec<u8> = (0..200_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        for len in [0, 1, 240, 65536, 65543, 200_000] {
+            let contents = &contents[..len];
+            // a reader that returns short reads
+            let reader = std::io::Read::chain(&contents[..len / 3], &contents[len / 3..]);
+            assert_eq!(
+                super::hash_reader(reader).unwrap(),
+                super::Hash::from_contents(contents),
+                "{} bytes",
+                len
+            );
+        }
+    }
+
     #[test]
     fn test_hash_error() {
         let actual = super::Hash::from_hex("g");
@@ -0,0 +1,123 @@
+//! Header in front of stored objects, so that the kind of an object can be
+//! told without guessing from its contents.
+//!
+//! A header is [`MAGIC`], a kind byte, a format version byte and the length
+//! of the payload as a little-endian u64. Objects written before headers
+//! existed are read as they are, with an unknown kind.
+
+use crate::{HeaderError, ObjectType};
+
+/// Start of every header. Trees never start with a NUL byte, so legacy
+/// objects are not mistaken for headed ones.
+pub const MAGIC: &[u8; 4] = b"\0mtl";
+/// Format version written by this mtl.
+pub const VERSION: u8 = 1;
+/// Format versions read by this mtl, 0 standing for the objects written
+/// before headers existed.
+pub const READABLE_VERSIONS: &[u8] = &[0, VERSION];
+/// Length of a header in bytes.
+pub const HEADER_LEN: usize = MAGIC.len() + 2 + 8;
+
+fn kind_byte(kind: ObjectType) -> u8 {
+    match kind {
+        ObjectType::Tree => 1,
+        ObjectType::File => 2,
+    }
+}
+
+/// Stored form of an object: the header followed by `payload`.
+pub fn encode(kind: ObjectType, payload: &[u8]) -> Vec<u8> {
+    let mut stored = Vec::with_capacity(HEADER_LEN + payload.len());
+    stored.extend_from_slice(MAGIC);
+    stored.push(kind_byte(kind));
+    stored.push(VERSION);
+    stored.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    stored.extend_from_slice(payload);
+    stored
+}
+
+/// Stored form of an object read back as `kind` and `payload`. An object
+/// of unknown kind is kept without a header, as it was.
+pub fn restore(kind: Option<ObjectType>, payload: Vec<u8>) -> Vec<u8> {
+    match kind {
+        Some(kind) => encode(kind, &payload),
+        None => payload,
+    }
+}
+
+/// Split a stored object into its kind and payload. The kind is `None` for
+/// a legacy object without a header.
+pub fn decode(stored: &[u8]) -> Result<(Option<ObjectType>, &[u8]), HeaderError> {
+    if stored.len() < HEADER_LEN || !stored.starts_with(MAGIC) {
+        return Ok((None, stored));
+    }
+
+    let (header, payload) = stored.split_at(HEADER_LEN);
+    let kind = match header[4] {
+        1 => ObjectType::Tree,
+        2 => ObjectType::File,
+        kind => return Err(HeaderError::UnknownKind(kind)),
+    };
+    if header[5] != VERSION {
+        return Err(HeaderError::UnsupportedVersion(header[5]));
+    }
+    let len = u64::from_le_bytes(header[6..].try_into().unwrap());
+    if len != payload.len() as u64 {
+        return Err(HeaderError::LengthMismatch {
+            expected: len,
+            actual: payload.len() as u64,
+        });
+    }
+    Ok((Some(kind), payload))
+}
+
+/// Like [`decode`], reusing the buffer of `stored` for the payload.
+pub fn decode_vec(mut stored: Vec<u8>) -> Result<(Option<ObjectType>, Vec<u8>), HeaderError> {
+    let (kind, payload_len) = match decode(&stored)? {
+        (Some(kind), payload) => (Some(kind), payload.len()),
+        (None, _) => return Ok((None, stored)),
+    };
+    stored.drain(..stored.len() - payload_len);
+    Ok((kind, stored))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, decode_vec, encode, HEADER_LEN};
+    use crate::{HeaderError, ObjectType};
+
+    #[test]
+    fn test_header() {
+        let tree = b"file\td447b1ea40e6988b\tREADME\n";
+        let stored = encode(ObjectType::Tree, tree);
+        assert_eq!(stored.len(), HEADER_LEN + tree.len());
+        assert_eq!(
+            decode(&stored).unwrap(),
+            (Some(ObjectType::Tree), &tree[..])
+        );
+        assert_eq!(
+            decode_vec(encode(ObjectType::File, b"")).unwrap(),
+            (Some(ObjectType::File), vec![])
+        );
+
+        // legacy objects have no header
+        assert_eq!(decode(tree).unwrap(), (None, &tree[..]));
+        assert_eq!(decode(b"").unwrap(), (None, &b""[..]));
+        assert_eq!(decode_vec(tree.to_vec()).unwrap(), (None, tree.to_vec()));
+
+        let mut stored = encode(ObjectType::Tree, tree);
+        stored[5] = 2;
+        assert!(matches!(
+            decode(&stored),
+            Err(HeaderError::UnsupportedVersion(2))
+        ));
+        stored[4] = 9;
+        assert!(matches!(decode(&stored), Err(HeaderError::UnknownKind(9))));
+
+        let stored = encode(ObjectType::Tree, tree);
+        assert!(matches!(
+            decode(&stored[..stored.len() - 1]),
+            Err(HeaderError::LengthMismatch { .. })
+        ));
+    }
+}
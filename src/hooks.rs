@@ -0,0 +1,117 @@
+//! Scripts in `.mtl/hooks/` run when the repository changes, to send
+//! notifications or start downstream pipelines.
+//!
+//! | Hook | When | Arguments |
+//! |------|------|-----------|
+//! | `post-build` | `local build` wrote its tree | old ID, new ID, ref |
+//! | `pre-pack` | before `pack`; failing stops the pack | |
+//! | `post-gc` | `gc` deleted objects | number of objects |
+//! | `ref-update` | HEAD or a ref moved or was deleted | old ID, new ID, ref |
+//!
+//! A missing ID is `0000000000000000`, and the ref of `post-build` is empty
+//! with `--no-write-head`. The arguments are also in `MTL_OLD`, `MTL_NEW`,
+//! `MTL_REF` and `MTL_DELETED_OBJECTS`.
+//!
+//! A hook runs in the root directory with `MTL_HOOK` set to its name and
+//! `MTL_DIR` to the root, so that `mtl` finds the repository. Missing and
+//! non-executable hooks are skipped. Hooks run while the command holds the
+//! repository lock, so they can read the repository but not change it.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::{config, Context, HookError, NULL_OBJECT_ID};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    PostBuild,
+    PrePack,
+    PostGc,
+    RefUpdate,
+}
+
+impl Hook {
+    pub fn name(self) -> &'static str {
+        match self {
+            Hook::PostBuild => "post-build",
+            Hook::PrePack => "pre-pack",
+            Hook::PostGc => "post-gc",
+            Hook::RefUpdate => "ref-update",
+        }
+    }
+}
+
+/// The hooks of one repository. It does not borrow the context, so hooks
+/// can still run after a command has dropped it.
+#[derive(Debug, Clone)]
+pub struct Hooks {
+    dir: PathBuf,
+    root_dir: PathBuf,
+}
+
+impl Hooks {
+    pub fn new(ctx: &Context) -> Self {
+        Hooks {
+            dir: ctx.hooks_dir(),
+            root_dir: ctx.root_dir().to_path_buf(),
+        }
+    }
+
+    /// Run `hook`, failing if it does not exit successfully.
+    pub fn run(&self, hook: Hook, args: &[&str], env: &[(&str, &str)]) -> Result<(), HookError> {
+        let path = self.dir.join(hook.name());
+        if !is_executable(&path) {
+            return Ok(());
+        }
+        log::info!("running hook {}", path.display());
+        let status = Command::new(&path)
+            .args(args)
+            .envs(env.iter().copied())
+            .env("MTL_HOOK", hook.name())
+            .env(config::DIR_VAR, &self.root_dir)
+            .current_dir(&self.root_dir)
+            .status()
+            .map_err(|error| HookError::Spawn {
+                hook: hook.name(),
+                error,
+            })?;
+        match status.success() {
+            true => Ok(()),
+            false => Err(HookError::Failed {
+                hook: hook.name(),
+                status,
+            }),
+        }
+    }
+
+    /// Like [`Hooks::run`], only warning when the hook fails, for hooks run
+    /// after the change they are told about.
+    pub fn notify(&self, hook: Hook, args: &[&str], env: &[(&str, &str)]) {
+        if let Err(e) = self.run(hook, args, env) {
+            eprintln!("warning: {}", e);
+        }
+    }
+
+    /// Tell `hook` that `reference` moved from `old` to `new`.
+    pub fn notify_update(&self, hook: Hook, reference: &str, old: Option<&str>, new: Option<&str>) {
+        let old = old.unwrap_or(NULL_OBJECT_ID);
+        let new = new.unwrap_or(NULL_OBJECT_ID);
+        self.notify(
+            hook,
+            &[old, new, reference],
+            &[("MTL_OLD", old), ("MTL_NEW", new), ("MTL_REF", reference)],
+        );
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|m| m.is_file())
+}
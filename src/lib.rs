@@ -1,29 +1,56 @@
-pub(crate) mod builder;
+pub mod audit;
+pub mod builder;
 pub mod commands;
+pub mod config;
+pub mod datetime;
+pub mod delta;
 pub mod error;
+pub mod exit_code;
 pub(crate) mod filesystem;
-mod filter;
+pub mod filter;
 pub mod hash;
-pub(crate) mod progress;
+pub mod header;
+pub mod hooks;
+pub mod lock;
+pub mod output;
+pub mod patch;
+pub mod priority;
+pub mod progress;
+pub mod repository;
+pub mod retention;
+pub mod store;
+pub mod summary;
+pub mod tree;
+pub mod xattr;
 
 pub use error::*;
 pub use filesystem::*;
-use std::borrow::Borrow;
+use std::borrow::{Borrow, Cow};
 
 use std::cmp::Ordering;
-use std::collections::HashSet;
 use std::fmt;
 use std::fs;
 use std::io::{self, Write};
 use std::ops::Deref;
 use std::path::{Components, Path, PathBuf};
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use byteorder::ByteOrder;
 use clap::ValueEnum;
-use redb::{ReadableTable, RedbKey, RedbValue, TableDefinition, TypeName};
+use redb::{RedbKey, RedbValue, TableDefinition, TypeName};
 
+use crate::audit::AuditEntry;
+use crate::config::Config;
 use crate::hash::Hash;
+use crate::hooks::{Hook, Hooks};
+use crate::lock::RepositoryLock;
+use crate::output::{OutputFormat, PathStyle};
+use crate::store::{
+    LocalObjectStore, MemoryObjectStore, ObjectStore, OverlayObjectStore, StoreCounters,
+};
+use crate::xattr::XattrSelector;
 #[cfg(feature = "jemalloc")]
 use tikv_jemallocator::Jemalloc;
 
@@ -47,7 +74,7 @@ impl RelativePath {
             RelativePath::Root => RelativePath::Root,
             RelativePath::Path(path) => match path.parent() {
                 None => RelativePath::Root,
-                Some(parent) if parent.as_os_str().eq("") => RelativePath::Root,
+                Some(parent) if parent.as_os_str().is_empty() => RelativePath::Root,
                 Some(parent) => RelativePath::Path(parent.to_path_buf()),
             },
         }
@@ -167,6 +194,11 @@ impl ObjectID {
     pub fn from_contents<T: AsRef<[u8]>>(contents: T) -> Self {
         ObjectID::new(Hash::from_contents(contents))
     }
+
+    /// Like `from_contents`, reading the contents a buffer at a time.
+    pub fn from_reader<R: io::Read>(reader: R) -> io::Result<Self> {
+        Ok(ObjectID::new(hash::hash_reader(reader)?))
+    }
 }
 
 impl fmt::Display for ObjectID {
@@ -363,6 +395,10 @@ impl Object {
         23 + self.file_path.as_os_str().len()
     }
 
+    pub fn object_id(&self) -> &ObjectID {
+        &self.object_id
+    }
+
     pub fn as_object_ref(&self) -> ObjectRef {
         ObjectRef::new_id(self.object_id)
     }
@@ -401,31 +437,158 @@ impl fmt::Display for Object {
 const MTL_DIR: &str = ".mtl";
 pub(crate) const PACKED_OBJECTS_TABLE: TableDefinition<ObjectID, Vec<u8>> =
     TableDefinition::new("packed-objects");
+// trees packed as a delta against a base object in PACKED_OBJECTS_TABLE
+pub(crate) const PACKED_DELTAS_TABLE: TableDefinition<ObjectID, (ObjectID, Vec<u8>)> =
+    TableDefinition::new("packed-deltas");
 
 pub struct Context {
-    // root of the repository
+    // root of the repository, or of the directory scanned for another
+    // repository; see Context::for_root
     root_dir: PathBuf,
 
+    // .mtl directory of the repository
+    mtl_dir: PathBuf,
+
     drop_cache: bool,
 
-    packed_db: Option<redb::Database>,
+    // files at least this large are hashed through a memory map
+    mmap_threshold: Option<u64>,
+
+    // extended attributes counted towards file IDs
+    xattrs: Option<XattrSelector>,
+
+    fsync: bool,
+
+    lock_wait: bool,
+
+    // threads for scanning
+    jobs: usize,
+
+    progress: bool,
+
+    output_format: OutputFormat,
+
+    // hex digits of object IDs in text output
+    id_width: usize,
+
+    path_style: PathStyle,
+
+    // settings of the config files
+    config: Arc<Config>,
+
+    store: Arc<dyn ObjectStore>,
+
+    counters: Arc<StoreCounters>,
+
+    pack_error: Option<String>,
 }
 
 impl Context {
-    pub fn new<P: Into<PathBuf>>(root_dir: P) -> anyhow::Result<Self> {
+    pub fn new<P: Into<PathBuf>>(root_dir: P) -> Result<Self, MtlError> {
         let root_dir = root_dir.into();
-        let packed_db_file = root_dir.join(MTL_DIR).join("pack").join("packed.redb");
+        if !root_dir.is_dir() {
+            return Err(MtlError::NotARepository(root_dir));
+        }
+        let mtl_dir = root_dir.join(MTL_DIR);
+        let store = LocalObjectStore::open(
+            mtl_dir.join("objects"),
+            mtl_dir.join("pack").join("packed.redb"),
+        )?;
+        let counters = store.counters();
+        let pack_error = store.pack_error().map(str::to_string);
 
-        let packed_db = if packed_db_file.exists() {
-            Some(redb::Database::open(&packed_db_file)?)
-        } else {
-            None
-        };
+        let mut ctx = Self::with_store(root_dir, Box::new(store));
+        ctx.counters = counters;
+        ctx.pack_error = pack_error;
+        Ok(ctx)
+    }
 
-        Ok(Context {
+    /// Create a context whose objects live only in memory.
+    ///
+    /// The root directory is the current directory, so relative paths still
+    /// resolve when building from the filesystem.
+    pub fn in_memory() -> Self {
+        Self::with_store(PathBuf::new(), Box::new(MemoryObjectStore::new()))
+    }
+
+    pub fn with_store<P: Into<PathBuf>>(root_dir: P, store: Box<dyn ObjectStore>) -> Self {
+        let root_dir = root_dir.into();
+        Context {
+            mtl_dir: root_dir.join(MTL_DIR),
             root_dir,
             drop_cache: false,
-            packed_db,
+            mmap_threshold: None,
+            xattrs: None,
+            fsync: false,
+            lock_wait: false,
+            jobs: num_cpus::get(),
+            progress: false,
+            output_format: OutputFormat::Text,
+            id_width: OBJECT_ID_LEN,
+            path_style: PathStyle::Root,
+            config: Arc::default(),
+            store: Arc::from(store),
+            counters: Arc::default(),
+            pack_error: None,
+        }
+    }
+
+    /// A context scanning `root_dir` into this repository: it shares the
+    /// objects, refs and settings of this context, and only builds read
+    /// files from `root_dir` instead.
+    pub fn for_root<P: Into<PathBuf>>(&self, root_dir: P) -> Self {
+        Context {
+            root_dir: root_dir.into(),
+            mtl_dir: self.mtl_dir.clone(),
+            drop_cache: self.drop_cache,
+            mmap_threshold: self.mmap_threshold,
+            xattrs: self.xattrs.clone(),
+            fsync: self.fsync,
+            lock_wait: self.lock_wait,
+            jobs: self.jobs,
+            progress: self.progress,
+            output_format: self.output_format,
+            id_width: self.id_width,
+            path_style: self.path_style.clone(),
+            config: self.config.clone(),
+            store: self.store.clone(),
+            counters: self.counters.clone(),
+            pack_error: self.pack_error.clone(),
+        }
+    }
+
+    /// A context with the settings of this one that reads and writes
+    /// objects through `store` instead.
+    pub fn for_store(&self, store: Box<dyn ObjectStore>) -> Self {
+        Context {
+            store: Arc::from(store),
+            pack_error: None,
+            ..self.for_root(&self.root_dir)
+        }
+    }
+
+    /// A context with the settings of this one whose writes are kept in
+    /// memory, so that trees can be built and compared with the repository
+    /// without writing them.
+    pub fn scratch(&self) -> Self {
+        let store = OverlayObjectStore::new(self.store.clone());
+        Context {
+            pack_error: self.pack_error.clone(),
+            ..self.for_store(Box::new(store))
+        }
+    }
+
+    /// A context with the settings and counters of this one, whose local
+    /// store is opened again. redb opens a pack once per process, so a
+    /// command replacing the pack drops its context and the next one reopens
+    /// it.
+    pub fn reopen(&self) -> Result<Self, MtlError> {
+        let store = LocalObjectStore::open(self.objects_dir(), self.pack_file())?
+            .with_counters(self.counters.clone());
+        let pack_error = store.pack_error().map(str::to_string);
+        Ok(Context {
+            pack_error,
+            ..self.for_store(Box::new(store))
         })
     }
 
@@ -433,19 +596,166 @@ impl Context {
         self.drop_cache = drop_cache;
     }
 
+    pub fn set_mmap_threshold(&mut self, mmap_threshold: Option<u64>) {
+        self.mmap_threshold = mmap_threshold;
+    }
+
+    pub fn set_xattrs(&mut self, xattrs: Option<XattrSelector>) {
+        self.xattrs = xattrs;
+    }
+
+    pub fn set_fsync(&mut self, fsync: bool) {
+        self.fsync = fsync;
+    }
+
+    pub fn set_lock_wait(&mut self, lock_wait: bool) {
+        self.lock_wait = lock_wait;
+    }
+
+    pub fn lock_wait(&self) -> bool {
+        self.lock_wait
+    }
+
+    pub fn set_jobs(&mut self, jobs: usize) {
+        self.jobs = jobs.max(1);
+    }
+
+    pub fn jobs(&self) -> usize {
+        self.jobs
+    }
+
+    /// Show a progress bar in builds even if the command doesn't ask for one.
+    pub fn set_progress(&mut self, progress: bool) {
+        self.progress = progress;
+    }
+
+    pub fn progress(&self) -> bool {
+        self.progress
+    }
+
+    /// Cut object IDs in text output to `width` hex digits, from
+    /// [`MIN_OBJECT_ID_PREFIX`] to [`OBJECT_ID_LEN`].
+    pub fn set_id_width(&mut self, width: usize) {
+        self.id_width = width.clamp(MIN_OBJECT_ID_PREFIX, OBJECT_ID_LEN);
+    }
+
+    pub fn id_width(&self) -> usize {
+        self.id_width
+    }
+
+    /// `object_id` as text output shows it. Abbreviated IDs are not checked
+    /// for uniqueness; they are for reading, while JSON output keeps them
+    /// whole.
+    pub fn abbrev(&self, object_id: &ObjectID) -> String {
+        self.abbrev_str(&object_id.to_string()).to_string()
+    }
+
+    /// Like [`Context::abbrev`], for IDs kept as strings.
+    pub fn abbrev_str<'a>(&self, object_id: &'a str) -> &'a str {
+        object_id.get(..self.id_width).unwrap_or(object_id)
+    }
+
+    pub fn set_path_style(&mut self, path_style: PathStyle) {
+        self.path_style = path_style;
+    }
+
+    /// A path of a tree as commands print it, see [`PathStyle`].
+    pub fn render_path<'a>(&self, path: &'a Path) -> Cow<'a, Path> {
+        self.path_style.render(path)
+    }
+
+    pub fn set_config(&mut self, config: Config) {
+        self.config = Arc::new(config);
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// How commands print their results, see [`crate::output`].
+    pub fn set_output_format(&mut self, output_format: OutputFormat) {
+        self.output_format = output_format;
+    }
+
+    pub fn output_format(&self) -> OutputFormat {
+        self.output_format
+    }
+
+    /// Take the repository lock for a mutating operation.
+    pub fn lock(&self) -> Result<RepositoryLock, LockError> {
+        RepositoryLock::acquire(self.lock_file(), self.lock_wait)
+    }
+
+    /// Take the repository lock alongside other builds, which only add
+    /// objects and replace refs whole, but not alongside anything holding
+    /// it with [`Context::lock`].
+    pub fn lock_shared(&self) -> Result<RepositoryLock, LockError> {
+        RepositoryLock::acquire_shared(self.lock_file(), self.lock_wait)
+    }
+
+    pub fn lock_file(&self) -> PathBuf {
+        self.mtl_dir.join("lock")
+    }
+
+    /// Where files are written before being renamed in place.
+    pub fn tmp_dir(&self) -> PathBuf {
+        self.mtl_dir.join("tmp")
+    }
+
     #[inline]
     pub fn root_dir(&self) -> &Path {
         &self.root_dir
     }
 
+    #[inline]
+    pub fn mtl_dir(&self) -> &Path {
+        &self.mtl_dir
+    }
+
+    #[inline]
+    pub fn store(&self) -> &dyn ObjectStore {
+        self.store.as_ref()
+    }
+
+    /// Counts of the object reads and writes, which only the local store keeps.
+    pub fn counters(&self) -> Arc<StoreCounters> {
+        self.counters.clone()
+    }
+
+    /// Why the pack could not be opened, if it exists but is unreadable.
+    /// Only loose objects can be read then.
+    pub fn pack_error(&self) -> Option<&str> {
+        self.pack_error.as_deref()
+    }
+
+    /// Fail if the pack is unreadable, for operations that would lose the
+    /// objects in it.
+    pub fn check_pack(&self) -> Result<(), ReadContentError> {
+        match &self.pack_error {
+            Some(reason) => Err(ReadContentError::PackUnreadable(reason.clone())),
+            None => Ok(()),
+        }
+    }
+
     #[inline]
     pub fn objects_dir(&self) -> PathBuf {
-        self.root_dir.as_path().join(MTL_DIR).join("objects")
+        self.mtl_dir.join("objects")
+    }
+
+    /// The repository's config file, see [`config`].
+    #[inline]
+    pub fn config_file(&self) -> PathBuf {
+        self.mtl_dir.join("config")
+    }
+
+    /// Where `gc --trash` moves the objects it deletes, one directory per run.
+    pub fn trash_dir(&self) -> PathBuf {
+        self.mtl_dir.join("trash")
     }
 
     #[inline]
     pub fn pack_dir(&self) -> PathBuf {
-        self.root_dir.as_path().join(MTL_DIR).join("pack")
+        self.mtl_dir.join("pack")
     }
 
     pub fn pack_file(&self) -> PathBuf {
@@ -458,107 +768,50 @@ impl Context {
     }
 
     pub fn object_file(&self, object_id: &ObjectID) -> PathBuf {
-        let object_string = object_id.to_string();
-        self.objects_dir()
-            .join(&object_string[0..2])
-            .join(&object_string[2..])
+        store::loose_object_file(&self.objects_dir(), object_id)
     }
 
     pub fn read_object(&self, object_id: &ObjectID) -> anyhow::Result<Vec<u8>, ReadContentError> {
-        let object_file = self.object_file(object_id);
-        if let Ok(contents) = fs::read(object_file) {
-            return Ok(contents);
-        }
-
-        let Some(packed_db) = &self.packed_db else {
-            return Err(ReadContentError::ObjectNotFound);
-        };
-
-        let read_txn = packed_db.begin_read()?;
-        let table = read_txn.open_table(PACKED_OBJECTS_TABLE)?;
-        let ret = match table.get(object_id)? {
-            Some(v) => Ok(v.value()),
-            None => Err(ReadContentError::ObjectNotFound),
-        };
-        ret
+        self.store.get(object_id)
     }
 
     pub fn object_files(&self) -> anyhow::Result<Vec<PathBuf>, ReadContentError> {
-        let dir_name = self.root_dir.as_path();
-        let object_dir = dir_name.join(MTL_DIR).join("objects");
+        store::loose_object_files(&self.objects_dir())
+    }
 
-        let mut object_files = Vec::new();
-        for entry in fs::read_dir(object_dir)? {
-            let entry = entry?;
-            let path = entry.path();
+    pub fn list_object_ids(&self) -> anyhow::Result<Vec<ObjectID>, ReadContentError> {
+        self.store.list()
+    }
 
-            if path.is_file() {
-                log::warn!("Unexpected file in object directory: {}", path.display());
-            }
-            if path.is_dir() {
-                for entry in fs::read_dir(path)? {
-                    let entry = entry?;
-                    let path = entry.path();
-
-                    if path.is_dir() {
-                        log::warn!(
-                            "Unexpected directory in object directory: {}",
-                            path.display()
-                        );
-                    }
-                    if path.is_file() {
-                        object_files.push(path);
-                    }
-                }
-            }
-        }
+    /// Append-only log of the mutating operations on this repository.
+    pub fn audit_file(&self) -> PathBuf {
+        self.mtl_dir.join("audit.log")
+    }
 
-        Ok(object_files)
+    /// Record an operation in the audit log.
+    pub fn audit(&self, entry: &AuditEntry) -> io::Result<()> {
+        audit::append(self.audit_file(), entry)
     }
 
-    pub fn list_object_ids(&self) -> anyhow::Result<Vec<ObjectID>, ReadContentError> {
-        let mut object_ids = HashSet::new();
-        for entry in self.object_files()? {
-            let dir_name = entry
-                .parent()
-                .and_then(|f| f.file_name())
-                .and_then(|f| f.to_str())
-                .ok_or(ParseError::EmptyToken)?;
-            let file_name = entry
-                .file_name()
-                .and_then(|f| f.to_str())
-                .ok_or(ParseError::EmptyToken)?;
-
-            let mut buf = String::with_capacity(dir_name.len() + file_name.len());
-            buf.push_str(dir_name);
-            buf.push_str(file_name);
-
-            let object_id: ObjectID = buf.parse()?;
-
-            object_ids.insert(object_id);
-            assert_eq!(object_id.to_string(), buf)
-        }
-
-        if let Some(packed_db) = &self.packed_db {
-            let read_txn = packed_db.begin_read()?;
-
-            let table = read_txn.open_table(PACKED_OBJECTS_TABLE)?;
-            for range in table.iter()? {
-                let (object_id, _) = range?;
-                object_ids.insert(object_id.value());
-            }
-        }
+    pub fn hooks_dir(&self) -> PathBuf {
+        self.mtl_dir.join("hooks")
+    }
 
-        Ok(object_ids.into_iter().collect())
+    pub fn hooks(&self) -> Hooks {
+        Hooks::new(self)
+    }
+
+    /// File listing extra object IDs that gc must treat as reachable.
+    pub fn keep_file(&self) -> PathBuf {
+        self.mtl_dir.join("keep")
     }
 
     pub fn head_file(&self) -> PathBuf {
-        let head_name = self.root_dir.as_path();
-        head_name.join(MTL_DIR).join("HEAD")
+        self.mtl_dir.join("HEAD")
     }
 
     pub fn reference_dir(&self) -> PathBuf {
-        self.root_dir.as_path().join(MTL_DIR).join("refs")
+        self.mtl_dir.join("refs")
     }
 
     pub fn reference_file(&self, reference: &str) -> PathBuf {
@@ -567,18 +820,133 @@ impl Context {
 
     pub fn deref_object_ref(&self, object_ref: &ObjectRef) -> Result<ObjectID, ReadContentError> {
         match object_ref {
-            ObjectRef::Reference(reference) if reference == "HEAD" => self.read_head(),
-            ObjectRef::Reference(reference) => {
-                let ref_file = self.reference_file(reference);
-                let contents = fs::read_to_string(ref_file)?;
-                let contents = contents.trim();
+            ObjectRef::Reference(reference) => match split_reflog_suffix(reference)? {
+                Some((reference, nth)) => self.read_reflog_entry(reference, nth),
+                None if reference == "HEAD" => self.read_head(),
+                None => {
+                    let ref_file = self.reference_file(reference);
+                    if !ref_file.exists() {
+                        if is_object_id_prefix(reference) {
+                            return self.resolve_object_id_prefix(reference);
+                        }
+                        return Err(ReadContentError::RefNotFound(reference.clone()));
+                    }
+                    let contents = fs::read_to_string(ref_file)?;
+                    let contents = contents.trim();
 
-                Ok(contents.parse()?)
-            }
+                    Ok(contents.parse()?)
+                }
+            },
             ObjectRef::ID(object_id) => Ok(*object_id),
         }
     }
 
+    /// Resolve an abbreviated object ID against the loose and packed objects.
+    pub fn resolve_object_id_prefix(&self, prefix: &str) -> Result<ObjectID, ReadContentError> {
+        let prefix = prefix.to_ascii_lowercase();
+        let mut candidates = self
+            .list_object_ids()?
+            .into_iter()
+            .filter(|object_id| object_id.to_string().starts_with(&prefix))
+            .collect::<Vec<_>>();
+        candidates.sort();
+
+        match candidates.as_slice() {
+            [] => Err(ReadContentError::ObjectNotFound),
+            [object_id] => Ok(*object_id),
+            candidates => Err(ReadContentError::AmbiguousObjectID {
+                prefix,
+                candidates: candidates
+                    .iter()
+                    .map(|id| id.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            }),
+        }
+    }
+
+    /// Log of the values `reference` ("HEAD" or a ref name) has been set to.
+    pub fn reflog_file(&self, reference: &str) -> PathBuf {
+        let logs_dir = self.mtl_dir.join("logs");
+        match reference {
+            "HEAD" => logs_dir.join("HEAD"),
+            reference => logs_dir.join("refs").join(reference),
+        }
+    }
+
+    /// Read the values recorded in the reflog of `reference`, newest first.
+    pub fn read_reflog(&self, reference: &str) -> Result<Vec<ObjectID>, ReadContentError> {
+        let reflog_file = self.reflog_file(reference);
+        if !reflog_file.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut object_ids = Vec::new();
+        for line in fs::read_to_string(reflog_file)?.lines() {
+            let object_id = line.split('\t').next().ok_or(ParseError::EmptyToken)?;
+            object_ids.push(object_id.parse()?);
+        }
+        object_ids.reverse();
+        Ok(object_ids)
+    }
+
+    /// When `reference` was last written, from its reflog, or None if it has
+    /// no reflog.
+    pub fn read_reflog_time(
+        &self,
+        reference: &str,
+    ) -> Result<Option<SystemTime>, ReadContentError> {
+        let reflog_file = self.reflog_file(reference);
+        if !reflog_file.exists() {
+            return Ok(None);
+        }
+
+        let reflog = fs::read_to_string(reflog_file)?;
+        let Some(line) = reflog.lines().last() else {
+            return Ok(None);
+        };
+        let secs = line
+            .split('\t')
+            .nth(1)
+            .ok_or(ParseError::EmptyToken)?
+            .parse::<u64>()
+            .map_err(|_| ParseError::InvalidToken(line.to_string()))?;
+        Ok(Some(UNIX_EPOCH + Duration::from_secs(secs)))
+    }
+
+    // "ref@{0}" is the current value, "ref@{n}" the value n updates before it
+    fn read_reflog_entry(&self, reference: &str, nth: usize) -> Result<ObjectID, ReadContentError> {
+        if nth == 0 {
+            return self.deref_object_ref(&ObjectRef::new_reference(reference));
+        }
+        self.read_reflog(reference)?
+            .get(nth)
+            .copied()
+            .ok_or_else(|| {
+                ReadContentError::ReflogEntryNotFound(format!("{}@{{{}}}", reference, nth))
+            })
+    }
+
+    fn append_reflog(&self, reference: &str, object_id: &ObjectID) -> io::Result<()> {
+        let reflog_file = self.reflog_file(reference);
+        if let Some(dir) = reflog_file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let line = format!("{}\t{}\n", object_id, now);
+
+        // one write per line, so lines of concurrent writers don't interleave
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(reflog_file)?;
+        file.write_all(line.as_bytes())
+    }
+
     pub fn search_object(
         &self,
         base: &ObjectRef,
@@ -661,31 +1029,80 @@ impl Context {
         ref_name: S,
         object_id: ObjectID,
     ) -> io::Result<()> {
+        check_ref_name(ref_name.as_ref())?;
         let ref_dir = self.reference_dir();
         fs::create_dir_all(ref_dir)?;
 
         let ref_file = self.reference_file(ref_name.as_ref());
-        fs::write(ref_file, object_id.to_string())?;
+        let previous = read_ref_file(&ref_file);
+        filesystem::write_atomic(&ref_file, &self.tmp_dir(), object_id.to_string().as_bytes())?;
+        self.append_reflog(ref_name.as_ref(), &object_id)?;
+        self.notify_ref_update(ref_name.as_ref(), previous, Some(&object_id));
+        Ok(())
+    }
+
+    /// Like [`Context::write_object_ref`], but fails with
+    /// [`io::ErrorKind::AlreadyExists`] instead of moving an existing ref.
+    pub fn create_object_ref<S: AsRef<str>>(
+        &self,
+        ref_name: S,
+        object_id: ObjectID,
+    ) -> io::Result<()> {
+        check_ref_name(ref_name.as_ref())?;
+        let ref_dir = self.reference_dir();
+        fs::create_dir_all(ref_dir)?;
+
+        let ref_file = self.reference_file(ref_name.as_ref());
+        filesystem::create_atomic(&ref_file, &self.tmp_dir(), object_id.to_string().as_bytes())?;
+        self.append_reflog(ref_name.as_ref(), &object_id)?;
+        self.notify_ref_update(ref_name.as_ref(), None, Some(&object_id));
         Ok(())
     }
 
     pub fn delete_object_ref<S: AsRef<str>>(&self, ref_name: S) -> io::Result<()> {
+        check_ref_name(ref_name.as_ref())?;
         let reference_file = self.reference_file(ref_name.as_ref());
+        let previous = read_ref_file(&reference_file);
         fs::remove_file(reference_file)?;
-        Ok(())
+        self.notify_ref_update(ref_name.as_ref(), previous, None);
+
+        match fs::remove_file(self.reflog_file(ref_name.as_ref())) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    // run the ref-update hook for a ref that held `previous` before
+    fn notify_ref_update(&self, reference: &str, previous: Option<String>, new: Option<&ObjectID>) {
+        let new = new.map(ObjectID::to_string);
+        self.hooks().notify_update(
+            Hook::RefUpdate,
+            reference,
+            previous.as_deref(),
+            new.as_deref(),
+        );
     }
 
     pub fn write_tree_contents<T: AsRef<Object>>(&self, entries: &[T]) -> io::Result<ObjectID> {
-        let tree_contents = serialize_entries(entries)?;
-        let object_id = ObjectID::from_contents(&tree_contents);
+        let (object_id, tree_contents) = tree_object(entries)?;
+        self.store
+            .put(&object_id, ObjectType::Tree, &tree_contents)?;
 
-        let dir_name = self.object_dir(&object_id);
-        let file_name = self.object_file(&object_id);
+        Ok(object_id)
+    }
 
-        fs::create_dir_all(dir_name)?;
-        fs::write(file_name, tree_contents)?;
+    /// Write serialized trees in one batch, syncing them once if fsync is enabled.
+    pub fn write_objects(&self, objects: &[(ObjectID, Vec<u8>)]) -> io::Result<()> {
+        self.write_objects_of(ObjectType::Tree, objects)
+    }
 
-        Ok(object_id)
+    /// Like [`Context::write_objects`], for objects of any one kind.
+    pub fn write_objects_of(
+        &self,
+        kind: ObjectType,
+        objects: &[(ObjectID, Vec<u8>)],
+    ) -> io::Result<()> {
+        self.store.put_batch(kind, objects, self.fsync)
     }
 
     pub fn read_tree_contents(
@@ -708,21 +1125,117 @@ impl Context {
         Ok(objects)
     }
 
+    /// Iterate over the entries below a tree, see [`tree::TreeIter`].
+    pub fn tree_iter(&self, object_id: &ObjectID) -> tree::TreeIter<'_> {
+        tree::TreeIter::new(self, object_id)
+    }
+
+    /// Visit the entries below a tree depth-first, each tree before its
+    /// entries. `visit` gets the path of each entry and the entry itself, and
+    /// decides whether to descend into it, to go on, or to stop.
+    pub fn walk_tree<F>(&self, object_id: &ObjectID, mut visit: F) -> Result<(), ReadContentError>
+    where
+        F: FnMut(&RelativePath, &Object) -> tree::WalkControl,
+    {
+        let mut iter = self.tree_iter(object_id);
+        while let Some(entry) = iter.next() {
+            let entry = entry?;
+            let path = RelativePath::from(entry.path);
+            let name = path.file_name().unwrap_or_default();
+            let object = Object::new(entry.object_type, entry.object_id, name);
+            match visit(&path, &object) {
+                tree::WalkControl::Continue => {}
+                tree::WalkControl::SkipChildren => iter.skip_children(),
+                tree::WalkControl::Stop => break,
+            }
+        }
+        Ok(())
+    }
+
     pub fn write_head(&self, object_id: &ObjectID) -> io::Result<()> {
         let head_name = self.head_file();
-        fs::write(head_name, object_id.to_string())?;
+        let previous = read_ref_file(&head_name);
+        filesystem::write_atomic(
+            &head_name,
+            &self.tmp_dir(),
+            object_id.to_string().as_bytes(),
+        )?;
+        self.append_reflog("HEAD", object_id)?;
+        self.notify_ref_update("HEAD", previous, Some(object_id));
 
         Ok(())
     }
 
     pub fn read_head(&self) -> anyhow::Result<ObjectID, ReadContentError> {
-        let head = fs::read_to_string(self.head_file())?;
+        let head = match fs::read_to_string(self.head_file()) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Err(ReadContentError::RefNotFound("HEAD".to_string()))
+            }
+            head => head?,
+        };
         let head = head.trim();
 
         Ok(head.parse()?)
     }
 }
 
+/// Shortest prefix accepted as an abbreviated object ID.
+pub const MIN_OBJECT_ID_PREFIX: usize = 4;
+/// Hex digits of a whole object ID.
+pub const OBJECT_ID_LEN: usize = 16;
+/// Stands for no object where an ID is expected, e.g. an `update-ref --old`
+/// requiring the ref not to exist yet.
+pub const NULL_OBJECT_ID: &str = "0000000000000000";
+
+fn is_object_id_prefix(s: &str) -> bool {
+    (MIN_OBJECT_ID_PREFIX..OBJECT_ID_LEN).contains(&s.len())
+        && s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+// split "ref@{n}" into the reference and n; a bare "@{n}" refers to HEAD
+fn split_reflog_suffix(reference: &str) -> Result<Option<(&str, usize)>, ParseError> {
+    let Some((name, suffix)) = reference.split_once("@{") else {
+        return Ok(None);
+    };
+    let nth = suffix
+        .strip_suffix('}')
+        .and_then(|nth| nth.parse().ok())
+        .ok_or_else(|| ParseError::InvalidToken(reference.to_string()))?;
+    match name {
+        "" => Ok(Some(("HEAD", nth))),
+        name => Ok(Some((name, nth))),
+    }
+}
+
+// the object ID a ref or HEAD file holds, if it exists
+fn read_ref_file(path: &Path) -> Option<String> {
+    let contents = fs::read_to_string(path).ok()?;
+    Some(contents.trim().to_string())
+}
+
+/// Fail with [`io::ErrorKind::InvalidInput`] unless `name` can be stored as a
+/// file right below the refs directory.
+pub fn check_ref_name(name: &str) -> io::Result<()> {
+    let invalid = name.is_empty()
+        || name == "HEAD"
+        || name.contains(['/', '\\', ':', '\0'])
+        || name.contains("..");
+    if invalid {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid ref name \"{}\"", name.escape_debug()),
+        ));
+    }
+    Ok(())
+}
+
+// serialize a tree without writing it; entries should be sorted
+pub(crate) fn tree_object<T: AsRef<Object>>(entries: &[T]) -> io::Result<(ObjectID, Vec<u8>)> {
+    let tree_contents = serialize_entries(entries)?;
+    let object_id = ObjectID::from_contents(&tree_contents);
+    Ok((object_id, tree_contents))
+}
+
 // serialize entries should be called with sorted entries
 fn serialize_entries<T: AsRef<Object>>(entries: &[T]) -> io::Result<Vec<u8>> {
     let size = entries.iter().map(|e| e.as_ref().size()).sum();
@@ -746,7 +1259,7 @@ mod tests {
     #[test]
     fn test_relative_path() {
         let root = RelativePath::Root;
-        assert_eq!(root.is_root(), true);
+        assert!(root.is_root());
         assert_eq!(root.file_name(), None);
 
         let mut m = HashMap::new();
@@ -756,15 +1269,37 @@ mod tests {
 
         let os_string_path = OsString::from("foo");
         let path = RelativePath::Path(PathBuf::from(os_string_path.clone()));
-        assert_eq!(path.is_root(), false);
+        assert!(!path.is_root());
         assert_eq!(path.parent(), RelativePath::Root);
         assert_eq!(path.file_name(), Some(PathBuf::from(os_string_path)));
 
         let path = RelativePath::Path(PathBuf::from("foo/bar"));
-        assert_eq!(path.is_root(), false);
+        assert!(!path.is_root());
         assert_eq!(path.parent(), RelativePath::Path(PathBuf::from("foo")));
 
-        assert_eq!(path.parent().parent().is_root(), true);
+        assert!(path.parent().parent().is_root());
+    }
+
+    #[test]
+    fn test_split_reflog_suffix() {
+        assert_eq!(split_reflog_suffix("HEAD").unwrap(), None);
+        assert_eq!(split_reflog_suffix("HEAD@{0}").unwrap(), Some(("HEAD", 0)));
+        assert_eq!(
+            split_reflog_suffix("root@{12}").unwrap(),
+            Some(("root", 12))
+        );
+        assert_eq!(split_reflog_suffix("@{1}").unwrap(), Some(("HEAD", 1)));
+        assert!(split_reflog_suffix("HEAD@{yesterday}").is_err());
+        assert!(split_reflog_suffix("HEAD@{1").is_err());
+    }
+
+    #[test]
+    fn test_check_ref_name() {
+        assert!(check_ref_name("snapshot-1").is_ok());
+        assert!(check_ref_name("v1.0").is_ok());
+        for name in ["", "HEAD", "a/b", "a\\b", "a:b", "..", "a..b", "a\0b"] {
+            assert!(check_ref_name(name).is_err(), "{:?}", name);
+        }
     }
 
     #[test]
@@ -808,14 +1343,64 @@ mod tests {
         assert_eq!(ctx.head_file(), Path::new("/tmp/.mtl/HEAD"));
     }
 
+    #[test]
+    fn test_in_memory_context() {
+        let ctx = Context::in_memory();
+
+        let file = Object::new_file(ObjectID::from_contents("hello world"), "hello");
+        let subtree_id = ctx.write_tree_contents(&[&file]).unwrap();
+        let subtree = Object::new_tree(subtree_id, "sub");
+        let root_id = ctx.write_tree_contents(&[&subtree]).unwrap();
+
+        assert_eq!(ctx.read_tree_contents(&subtree_id).unwrap(), vec![file]);
+        assert_eq!(
+            ctx.search_object(&ObjectRef::new_id(root_id), Path::new("sub/hello"))
+                .unwrap(),
+            ObjectID::from_contents("hello world")
+        );
+
+        let mut object_ids = ctx.list_object_ids().unwrap();
+        object_ids.sort();
+        let mut expected = vec![root_id, subtree_id];
+        expected.sort();
+        assert_eq!(object_ids, expected);
+    }
+
+    #[test]
+    fn test_resolve_object_id_prefix() {
+        let ctx = Context::in_memory();
+        let a = ObjectID::from_hex("abcd000000000001").unwrap();
+        let b = ObjectID::from_hex("abcd100000000000").unwrap();
+        ctx.store().put(&a, ObjectType::Tree, b"").unwrap();
+        ctx.store().put(&b, ObjectType::Tree, b"").unwrap();
+
+        let deref = |s: &str| ctx.deref_object_ref(&s.parse().unwrap());
+        assert_eq!(deref("abcd000000000001").unwrap(), a);
+        assert_eq!(deref("abcd1").unwrap(), b);
+        assert_eq!(deref("ABCD1").unwrap(), b);
+        assert!(matches!(
+            deref("abcd"),
+            Err(ReadContentError::AmbiguousObjectID { .. })
+        ));
+        assert!(matches!(
+            deref("ffff"),
+            Err(ReadContentError::ObjectNotFound)
+        ));
+        // too short to be taken as an object ID
+        assert!(matches!(
+            deref("abc"),
+            Err(ReadContentError::RefNotFound(_))
+        ));
+    }
+
     #[test]
     fn test_object_order() {
         let object_id = ObjectID::from_hex("d447b1ea40e6988b").unwrap();
         let mut objects = vec![
-            Object::new(ObjectType::File, object_id.clone(), PathBuf::from("c")),
-            Object::new(ObjectType::File, object_id.clone(), PathBuf::from("d")),
-            Object::new(ObjectType::File, object_id.clone(), PathBuf::from("a")),
-            Object::new(ObjectType::File, object_id.clone(), PathBuf::from("b")),
+            Object::new(ObjectType::File, object_id, PathBuf::from("c")),
+            Object::new(ObjectType::File, object_id, PathBuf::from("d")),
+            Object::new(ObjectType::File, object_id, PathBuf::from("a")),
+            Object::new(ObjectType::File, object_id, PathBuf::from("b")),
         ];
         let mut compare_target = objects.clone();
 
@@ -836,12 +1421,12 @@ mod tests {
     #[test]
     fn test_object_size() {
         let object_id = ObjectID::from_hex("d447b1ea40e6988b").unwrap();
-        let objects = vec![
-            Object::new(ObjectType::File, object_id.clone(), PathBuf::from("a")),
-            Object::new(ObjectType::File, object_id.clone(), PathBuf::from("aa")),
-            Object::new(ObjectType::File, object_id.clone(), PathBuf::from("aあ")),
-            Object::new(ObjectType::File, object_id.clone(), PathBuf::from("あ")),
-            Object::new(ObjectType::File, object_id.clone(), PathBuf::from("ああ")),
+        let objects = [
+            Object::new(ObjectType::File, object_id, PathBuf::from("a")),
+            Object::new(ObjectType::File, object_id, PathBuf::from("aa")),
+            Object::new(ObjectType::File, object_id, PathBuf::from("aあ")),
+            Object::new(ObjectType::File, object_id, PathBuf::from("あ")),
+            Object::new(ObjectType::File, object_id, PathBuf::from("ああ")),
         ];
         assert_eq!(objects[0].size(), 24);
         assert_eq!(objects[1].size(), 25);
@@ -0,0 +1,99 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use crate::LockError;
+
+/// Advisory lock on a repository, held until dropped.
+///
+/// Mutating commands take this lock so that, for example, `pack` does not
+/// delete loose objects while `gc` is scanning them. Builds share it, so
+/// that several can run at once but none alongside `gc` or `pack`.
+#[derive(Debug)]
+pub struct RepositoryLock {
+    // the lock is released when the file is closed
+    file: File,
+    shared: bool,
+}
+
+impl RepositoryLock {
+    pub fn acquire<P: AsRef<Path>>(path: P, wait: bool) -> Result<Self, LockError> {
+        Self::acquire_with(path.as_ref(), wait, false)
+    }
+
+    pub fn acquire_shared<P: AsRef<Path>>(path: P, wait: bool) -> Result<Self, LockError> {
+        Self::acquire_with(path.as_ref(), wait, true)
+    }
+
+    fn acquire_with(path: &Path, wait: bool, shared: bool) -> Result<Self, LockError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        if !lock_file(&file, wait, shared)? {
+            let mut pid = String::new();
+            file.read_to_string(&mut pid)?;
+            // only an exclusive holder leaves its pid
+            let holder = match pid.trim() {
+                "" => "shared by builds".to_string(),
+                pid => format!("pid {}", pid),
+            };
+            return Err(LockError::Busy {
+                path: PathBuf::from(path),
+                holder,
+            });
+        }
+
+        if !shared {
+            file.set_len(0)?;
+            file.rewind()?;
+            write!(file, "{}", std::process::id())?;
+        }
+        Ok(RepositoryLock { file, shared })
+    }
+}
+
+impl Drop for RepositoryLock {
+    fn drop(&mut self) {
+        // so that the pid is not blamed for the builds sharing the lock next
+        if !self.shared {
+            let _ = self.file.set_len(0);
+        }
+    }
+}
+
+// Returns false if the lock is held elsewhere and `wait` is false.
+#[cfg(unix)]
+fn lock_file(file: &File, wait: bool, shared: bool) -> io::Result<bool> {
+    use std::os::fd::AsRawFd;
+
+    let mut operation = if shared { libc::LOCK_SH } else { libc::LOCK_EX };
+    if !wait {
+        operation |= libc::LOCK_NB;
+    }
+    loop {
+        let ret = unsafe { libc::flock(file.as_raw_fd(), operation) };
+        if ret == 0 {
+            return Ok(true);
+        }
+
+        let err = io::Error::last_os_error();
+        match err.kind() {
+            io::ErrorKind::Interrupted => continue,
+            io::ErrorKind::WouldBlock => return Ok(false),
+            _ => return Err(err),
+        }
+    }
+}
+
+#[cfg(not(unix))]
+#[inline]
+fn lock_file(_file: &File, _wait: bool, _shared: bool) -> io::Result<bool> {
+    Ok(true)
+}
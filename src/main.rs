@@ -1,20 +1,119 @@
 use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use std::{env, time};
 
-use clap::{Args, CommandFactory, Parser, Subcommand};
+use anyhow::Context as _;
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
 use clap_complete::Shell;
+use itertools::Itertools;
+use serde::Serialize;
 
-use mtl::{commands, Context};
+use mtl::config::{self, Config, EnvConfig};
+use mtl::output::{self, ColorChoice, OutputFormat, PathStyle};
+use mtl::priority::{self, IoPriority};
+use mtl::store::StoreStats;
+use mtl::{commands, exit_code, hash, header, Context};
 
 /// MTL is a tool that recursively computes hash values for files.
 #[derive(Parser)]
 #[command(author, version, about, long_about=None)]
 #[command(propagate_version = true)]
 struct MTLCommands {
-    /// Working directory.
+    /// Working directory. [env: MTL_DIR]
     #[clap(short, long, value_name = "directory", verbatim_doc_comment)]
     dir: Option<PathBuf>,
 
+    /// Wait for the repository lock instead of failing when another process holds it.
+    /// [env: MTL_WAIT=1] [config: core.wait]
+    #[clap(long, global = true, overrides_with = "no_wait", verbatim_doc_comment)]
+    wait: bool,
+
+    /// Fail immediately if another process holds the repository lock (default).
+    /// [env: MTL_WAIT=0]
+    #[clap(long, global = true, overrides_with = "wait", verbatim_doc_comment)]
+    no_wait: bool,
+
+    /// Number of threads for hashing and scanning. Defaults to the number of CPUs.
+    /// [env: MTL_JOBS] [config: core.jobs]
+    #[clap(
+        short,
+        long,
+        visible_alias = "threads",
+        global = true,
+        value_name = "n",
+        verbatim_doc_comment
+    )]
+    jobs: Option<usize>,
+
+    /// Print the wall time and object reads and writes of the command to stderr
+    /// when it ends, as text or as a JSON line with "--stats=json".
+    /// [env: MTL_STATS]
+    #[clap(
+        long,
+        global = true,
+        value_name = "format",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "text",
+        verbatim_doc_comment
+    )]
+    stats: Option<StatsFormat>,
+
+    /// Nice value to run at, from -20 to 19. Going below the current value
+    /// needs privileges.
+    #[clap(
+        long,
+        global = true,
+        value_name = "n",
+        allow_hyphen_values = true,
+        value_parser = clap::value_parser!(i32).range(-20..=19),
+        verbatim_doc_comment
+    )]
+    nice: Option<i32>,
+
+    /// I/O scheduling class to run at, on Linux: "idle", or "best-effort" or
+    /// "realtime" with an optional ":level" from 0 (highest) to 7.
+    #[clap(
+        long,
+        global = true,
+        value_name = "class[:level]",
+        verbatim_doc_comment
+    )]
+    ionice: Option<IoPriority>,
+
+    /// Run at background priority, like "--nice 19 --ionice idle".
+    #[clap(long, global = true, conflicts_with_all = ["nice", "ionice"])]
+    background: bool,
+
+    /// Print the results as text, or as one JSON object per line for scripts.
+    /// Supported by ref list, remote list, print-tree, diff, gc, local status,
+    /// audit show, stats and version.
+    #[clap(long, global = true, value_enum, default_value_t = OutputFormat::Text, verbatim_doc_comment)]
+    format: OutputFormat,
+
+    /// Print the paths of print-tree, diff, find, local list and local status
+    /// under the repository root, instead of relative to it
+    #[clap(
+        long,
+        global = true,
+        conflicts_with = "relative_to",
+        verbatim_doc_comment
+    )]
+    absolute: bool,
+
+    /// Print the paths of print-tree, diff, find, local list and local status
+    /// relative to this directory, e.g. "."
+    #[clap(long, global = true, value_name = "directory", verbatim_doc_comment)]
+    relative_to: Option<PathBuf>,
+
+    /// Print object IDs whole in text output, whatever core.abbrev says
+    #[clap(long, global = true)]
+    full_ids: bool,
+
+    /// When to color the output
+    #[clap(long, global = true, value_enum, value_name = "when", default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
     #[command(subcommand)]
     commands: Commands,
 }
@@ -29,6 +128,22 @@ enum Commands {
     #[command(subcommand)]
     Ref(commands::RefCommand),
 
+    /// Log of the operations that changed the repository
+    #[command(subcommand)]
+    Audit(commands::AuditCommand),
+
+    /// Move objects between repositories as a single file
+    #[command(subcommand)]
+    Bundle(commands::BundleCommand),
+
+    /// Read and write the config files
+    #[command(subcommand)]
+    Config(commands::ConfigCommand),
+
+    /// Named URLs of other repositories
+    #[command(subcommand)]
+    Remote(commands::RemoteCommand),
+
     /// Print the content of an object
     CatObject(commands::CatObjectCommand),
 
@@ -38,21 +153,63 @@ enum Commands {
     /// Diff two tree objects
     Diff(commands::DiffCommand),
 
+    /// Apply a patch from `diff --emit-patch` to a tree
+    Apply(commands::ApplyCommand),
+
     /// Run garbage collection
     GC(commands::GCCommand),
 
     /// Pack the objects
     Pack(commands::PackCommand),
 
+    /// Delete snapshot refs according to a retention policy
+    PruneSnapshots(commands::PruneSnapshotsCommand),
+
     /// Print the tree of objects
     PrintTree(commands::PrintTreeCommand),
 
+    /// List the entries of a tree without descending into subtrees
+    Ls(commands::LsCommand),
+
+    /// Search a tree for entries by name or path pattern
+    Find(commands::FindCommand),
+
+    /// Count the entries of a tree, with histograms of their shape
+    Stats(commands::StatsCommand),
+
+    /// Rewrite a tree, dropping or moving paths
+    Rewrite(commands::RewriteCommand),
+
+    /// Print the ID of a subtree to use it as a root of its own
+    Reroot(commands::RerootCommand),
+
+    /// Nest a tree under new directories
+    Wrap(commands::WrapCommand),
+
+    /// Write a tree and the files it lists to a git repository
+    ExportGit(commands::ExportGitCommand),
+
+    /// Import the tree of a git revision and save it as a ref
+    ImportGit(commands::ImportGitCommand),
+
     /// Tool subcommands
     #[command(subcommand)]
     Tool(commands::ToolCommands),
 
+    /// Low-level commands for scripts
+    #[command(subcommand)]
+    Plumbing(commands::PlumbingCommand),
+
+    /// Run the main commands against a scratch repository to check the install
+    Selftest(commands::SelftestCommand),
+
     /// Generate shell completion script
     Completion(CompletionCommand),
+
+    /// Print the version and what this build supports, for tools to check
+    /// before running newer commands
+    #[clap(verbatim_doc_comment)]
+    Version(VersionCommand),
 }
 
 #[derive(Args, Debug)]
@@ -74,6 +231,114 @@ impl CompletionCommand {
     }
 }
 
+#[derive(Args, Debug)]
+struct VersionCommand {}
+
+#[derive(Serialize)]
+struct Capabilities {
+    version: &'static str,
+    /// Cargo features this binary was built with
+    features: Vec<&'static str>,
+    object_format_versions: &'static [u8],
+    hash_algorithms: Vec<&'static str>,
+    /// Every command, as the words to type after "mtl"
+    commands: Vec<String>,
+}
+
+impl Capabilities {
+    fn new() -> Self {
+        let mut features = Vec::new();
+        if cfg!(feature = "jemalloc") {
+            features.push("jemalloc");
+        }
+        if cfg!(feature = "sqlite") {
+            features.push("sqlite");
+        }
+        let mut commands = Vec::new();
+        collect_commands(&MTLCommands::command(), "", &mut commands);
+        Capabilities {
+            version: env!("CARGO_PKG_VERSION"),
+            features,
+            object_format_versions: header::READABLE_VERSIONS,
+            hash_algorithms: vec![hash::ALGORITHM],
+            commands,
+        }
+    }
+}
+
+fn collect_commands(cmd: &clap::Command, prefix: &str, commands: &mut Vec<String>) {
+    for sub in cmd.get_subcommands() {
+        let name = format!("{}{}", prefix, sub.get_name());
+        commands.push(name.clone());
+        if sub.has_subcommands() {
+            collect_commands(sub, &format!("{} ", name), commands);
+        }
+    }
+}
+
+impl VersionCommand {
+    pub fn run(&self, format: OutputFormat) -> anyhow::Result<()> {
+        let capabilities = Capabilities::new();
+        match format {
+            OutputFormat::Text => {
+                println!("mtl {}", capabilities.version);
+                match capabilities.features.is_empty() {
+                    true => println!("features: none"),
+                    false => println!("features: {}", capabilities.features.join(" ")),
+                }
+                println!(
+                    "object format versions: {}",
+                    capabilities.object_format_versions.iter().join(" ")
+                );
+                println!(
+                    "hash algorithms: {}",
+                    capabilities.hash_algorithms.join(" ")
+                );
+            }
+            OutputFormat::Json => output::print_json(&capabilities)?,
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum StatsFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Serialize)]
+struct Footer {
+    wall_time_secs: f64,
+    #[serde(flatten)]
+    store: StoreStats,
+}
+
+impl Footer {
+    fn print(&self, format: StatsFormat) -> anyhow::Result<()> {
+        let store = &self.store;
+        match format {
+            StatsFormat::Text => {
+                eprintln!("{:<16}{:.3}s", "wall time", self.wall_time_secs);
+                eprintln!(
+                    "{:<16}{} ({} bytes)",
+                    "loose reads", store.loose_reads, store.loose_bytes
+                );
+                eprintln!(
+                    "{:<16}{} ({} bytes, {} as deltas)",
+                    "packed reads", store.packed_reads, store.packed_bytes, store.delta_reads
+                );
+                eprintln!(
+                    "{:<16}{} ({} bytes)",
+                    "writes", store.writes, store.write_bytes
+                );
+            }
+            StatsFormat::Json => eprintln!("{}", serde_json::to_string(self)?),
+        }
+        Ok(())
+    }
+}
+
 fn setup_signal_handler() {
     #[cfg(not(target_os = "windows"))]
     unsafe {
@@ -81,35 +346,160 @@ fn setup_signal_handler() {
     }
 }
 
-fn main() -> anyhow::Result<()> {
+fn set_priority(mtl: &MTLCommands) -> anyhow::Result<()> {
+    let (nice, ionice) = match mtl.background {
+        true => (Some(priority::BACKGROUND_NICE), Some(IoPriority::Idle)),
+        false => (mtl.nice, mtl.ionice),
+    };
+    if let Some(nice) = nice {
+        priority::set_nice(nice).map_err(|e| anyhow::anyhow!("--nice {}: {}", nice, e))?;
+    }
+    if let Some(ionice) = ionice {
+        priority::set_io_priority(ionice)
+            .map_err(|e| anyhow::anyhow!("--ionice {}: {}", ionice, e))?;
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    match try_main() {
+        Ok(status) => status,
+        Err(err) => {
+            eprintln!("Error: {:?}", err);
+            ExitCode::from(exit_code::of(&err))
+        }
+    }
+}
+
+fn try_main() -> anyhow::Result<ExitCode> {
     env_logger::init();
     setup_signal_handler();
     let start = time::Instant::now();
 
     let mtl = MTLCommands::parse();
+    // before any worker thread is spawned, so that they all inherit it
+    set_priority(&mtl)?;
+    // needs no repository
+    if let Commands::Version(version) = &mtl.commands {
+        version.run(mtl.format)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    // the command line first, then the environment, then the config files
+    let config = EnvConfig::from_env()?;
 
-    let dir = mtl
-        .dir
-        .as_ref()
-        .unwrap_or(&env::current_dir()?)
-        .canonicalize()?;
+    let dir = match mtl.dir.clone().or(config.dir) {
+        Some(dir) => dir,
+        None => env::current_dir()?,
+    };
+    // a missing directory is reported by Context::new
+    let dir = dir.canonicalize().unwrap_or(dir);
     log::info!("dir: {}", dir.display());
 
-    let ctx = Context::new(&dir)?;
-    match &mtl.commands {
+    let mut ctx = Context::new(&dir)?;
+    if let Some(reason) = ctx.pack_error() {
+        eprintln!(
+            "warning: the pack is unreadable ({}), only loose objects can be read. \
+             Run \"mtl pack rebuild\" to replace it.",
+            reason
+        );
+    }
+    // so that a bad value can still be replaced with "mtl config set"
+    if !matches!(mtl.commands, Commands::Config(_)) {
+        ctx.set_config(Config::load(&ctx.config_file())?);
+    }
+    let file_config = ctx.config().clone();
+    let wait = match (mtl.wait, mtl.no_wait) {
+        (true, _) => true,
+        (_, true) => false,
+        _ => config.wait.or(file_config.wait).unwrap_or(false),
+    };
+    ctx.set_lock_wait(wait);
+    if let Some(jobs) = mtl.jobs.or(config.jobs).or(file_config.jobs) {
+        ctx.set_jobs(jobs);
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(ctx.jobs())
+            .build_global()?;
+    }
+    ctx.set_progress(config.progress.or(file_config.progress).unwrap_or(false));
+    ctx.set_output_format(mtl.format);
+    let root = ctx.root_dir().to_path_buf();
+    match (&mtl.relative_to, mtl.absolute) {
+        (Some(dir), _) => ctx.set_path_style(PathStyle::RelativeTo {
+            dir: dir
+                .canonicalize()
+                .with_context(|| format!("--relative-to {}", dir.display()))?,
+            root,
+        }),
+        (None, true) => ctx.set_path_style(PathStyle::Absolute { root }),
+        (None, false) => {}
+    }
+    if let (false, Some(width)) = (mtl.full_ids, file_config.abbrev) {
+        ctx.set_id_width(width);
+    }
+    mtl.color.apply();
+    let stats = match (mtl.stats, config.stats) {
+        (Some(format), _) => Some(format),
+        (None, Some(format)) => Some(
+            StatsFormat::from_str(&format, true)
+                .map_err(|e| anyhow::anyhow!("{}: {}", config::STATS_VAR, e))?,
+        ),
+        (None, None) => None,
+    };
+
+    // the commands take the context, so keep a handle on its counters
+    let counters = ctx.counters();
+    let result = run(&mtl.commands, ctx);
+
+    log::info!("Elapsed time: {:?}", start.elapsed());
+    if let Some(format) = stats {
+        let footer = Footer {
+            wall_time_secs: start.elapsed().as_secs_f64(),
+            store: counters.snapshot(),
+        };
+        footer.print(format)?;
+    }
+
+    result
+}
+
+fn run(commands: &Commands, ctx: Context) -> anyhow::Result<ExitCode> {
+    let mut status = ExitCode::SUCCESS;
+    match commands {
         Commands::Local(local) => local.run(ctx)?,
         Commands::Ref(ref_command) => ref_command.run(ctx)?,
+        Commands::Audit(audit) => audit.run(ctx)?,
+        Commands::Bundle(bundle) => bundle.run(ctx)?,
+        Commands::Config(config) => config.run(ctx)?,
+        Commands::Remote(remote) => remote.run(ctx)?,
         Commands::CatObject(cat_object) => cat_object.run(ctx)?,
         Commands::RevParse(rev_parse) => rev_parse.run(ctx)?,
-        Commands::Diff(diff) => diff.run(ctx)?,
+        Commands::Diff(diff) => {
+            if diff.run(ctx)? {
+                status = ExitCode::from(exit_code::DIFFERENCES);
+            }
+        }
+        Commands::Apply(apply) => apply.run(ctx)?,
         Commands::GC(gc) => gc.run(ctx)?,
         Commands::Pack(pack) => pack.run(ctx)?,
+        Commands::PruneSnapshots(prune_snapshots) => prune_snapshots.run(ctx)?,
         Commands::PrintTree(print_tree) => print_tree.run(ctx)?,
+        Commands::Ls(ls) => ls.run(ctx)?,
+        Commands::Find(find) => find.run(ctx)?,
+        Commands::Stats(stats) => {
+            if stats.run(ctx)? {
+                status = ExitCode::from(exit_code::CORRUPTION);
+            }
+        }
+        Commands::Rewrite(rewrite) => rewrite.run(ctx)?,
+        Commands::Reroot(reroot) => reroot.run(ctx)?,
+        Commands::Wrap(wrap) => wrap.run(ctx)?,
+        Commands::ExportGit(export_git) => export_git.run(ctx)?,
+        Commands::ImportGit(import_git) => import_git.run(ctx)?,
         Commands::Tool(tool) => tool.run(ctx)?,
+        Commands::Plumbing(plumbing) => plumbing.run(ctx)?,
+        Commands::Selftest(selftest) => selftest.run()?,
         Commands::Completion(completion) => completion.run(),
+        Commands::Version(_) => unreachable!("run before the context is made"),
     }
-
-    log::info!("Elapsed time: {:?}", start.elapsed());
-
-    Ok(())
+    Ok(status)
 }
@@ -0,0 +1,133 @@
+//! The output format and colors chosen with the global `--format` and
+//! `--color` options.
+//!
+//! With `--format json`, commands print one JSON object per line in place
+//! of each of their text lines, with the same information under fixed keys.
+
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Lines for people to read
+    #[default]
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// Color terminals, unless NO_COLOR is set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Turn the colors of stdout and stderr on or off.
+    pub fn apply(self) {
+        let enabled = match self {
+            ColorChoice::Auto => return,
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        };
+        console::set_colors_enabled(enabled);
+        console::set_colors_enabled_stderr(enabled);
+    }
+}
+
+/// How paths in trees are printed, chosen with the global `--absolute` and
+/// `--relative-to` options. The paths are taken to be relative to the root
+/// of the repository.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum PathStyle {
+    /// As stored, relative to the root
+    #[default]
+    Root,
+    /// Joined to the root, which is absolute
+    Absolute { root: PathBuf },
+    /// From `dir` to the path joined to the root, both absolute
+    RelativeTo { dir: PathBuf, root: PathBuf },
+}
+
+impl PathStyle {
+    pub fn render<'a>(&self, path: &'a Path) -> Cow<'a, Path> {
+        match self {
+            PathStyle::Root => Cow::Borrowed(path),
+            PathStyle::Absolute { root } => Cow::Owned(without_cur_dir(&root.join(path))),
+            PathStyle::RelativeTo { dir, root } => Cow::Owned(relative_path(dir, &root.join(path))),
+        }
+    }
+}
+
+// print-tree calls the root "."
+fn without_cur_dir(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| *component != Component::CurDir)
+        .collect()
+}
+
+/// The path from directory `dir` to `target`, both absolute, worked out
+/// without touching the filesystem: "../b" from "/a" to "/b".
+pub fn relative_path(dir: &Path, target: &Path) -> PathBuf {
+    let dir = without_cur_dir(dir);
+    let target = without_cur_dir(target);
+    let dir = dir.components().collect::<Vec<_>>();
+    let target = target.components().collect::<Vec<_>>();
+    let common = dir.iter().zip(&target).take_while(|(a, b)| a == b).count();
+    let path = std::iter::repeat_n(Component::ParentDir, dir.len() - common)
+        .chain(target[common..].iter().copied())
+        .collect::<PathBuf>();
+    match path.as_os_str().is_empty() {
+        true => PathBuf::from("."),
+        false => path,
+    }
+}
+
+/// Write `value` as one line of JSON.
+pub fn write_json<W: Write, T: Serialize>(out: &mut W, value: &T) -> io::Result<()> {
+    serde_json::to_writer(&mut *out, value)?;
+    writeln!(out)
+}
+
+/// Print `value` to stdout as one line of JSON.
+pub fn print_json<T: Serialize>(value: &T) -> io::Result<()> {
+    write_json(&mut io::stdout().lock(), value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::PathStyle;
+
+    #[test]
+    fn test_path_style() {
+        let render = |style: &PathStyle, path: &str| style.render(Path::new(path)).into_owned();
+        let root = PathBuf::from("/data");
+        assert_eq!(render(&PathStyle::Root, "dir1/a"), Path::new("dir1/a"));
+
+        let absolute = PathStyle::Absolute { root: root.clone() };
+        assert_eq!(render(&absolute, "dir1/a"), Path::new("/data/dir1/a"));
+        assert_eq!(render(&absolute, "."), Path::new("/data"));
+
+        let relative_to = |dir: &str| PathStyle::RelativeTo {
+            dir: PathBuf::from(dir),
+            root: root.clone(),
+        };
+        assert_eq!(render(&relative_to("/data"), "dir1/a"), Path::new("dir1/a"));
+        assert_eq!(render(&relative_to("/data/dir1"), "dir1/a"), Path::new("a"));
+        assert_eq!(
+            render(&relative_to("/data/dir1"), "dir2/b"),
+            Path::new("../dir2/b")
+        );
+        assert_eq!(render(&relative_to("/data/dir1"), "dir1"), Path::new("."));
+        assert_eq!(render(&relative_to("/other"), "."), Path::new("../data"));
+    }
+}
@@ -0,0 +1,260 @@
+//! Textual patches between trees.
+//!
+//! A patch is a list of `op<TAB>kind<TAB>id<TAB>path` lines where `op` is
+//! `add`, `remove` or `set`, and paths are escaped as in read-tree output.
+//! Added trees are listed file by file, so a patch can be applied to a
+//! repository that has none of the trees it adds.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use itertools::{EitherOrBoth, Itertools};
+
+use crate::tree::{self, TreeEditor};
+use crate::{Context, Object, ObjectID, ObjectType, ParseError, ReadContentError, TreeError};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PatchOp {
+    /// Add an entry that doesn't exist yet
+    Add,
+    /// Remove an existing entry
+    Remove,
+    /// Replace an existing entry with a new ID
+    Set,
+}
+
+impl fmt::Display for PatchOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            PatchOp::Add => write!(f, "add"),
+            PatchOp::Remove => write!(f, "remove"),
+            PatchOp::Set => write!(f, "set"),
+        }
+    }
+}
+
+impl FromStr for PatchOp {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "add" => Ok(PatchOp::Add),
+            "remove" => Ok(PatchOp::Remove),
+            "set" => Ok(PatchOp::Set),
+            "" => Err(ParseError::EmptyToken),
+            s => Err(ParseError::InvalidToken(s.to_string())),
+        }
+    }
+}
+
+/// One line of a patch. The ID of a removed entry is informational.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PatchEntry {
+    pub op: PatchOp,
+    pub object_type: ObjectType,
+    pub object_id: ObjectID,
+    pub path: PathBuf,
+}
+
+impl fmt::Display for PatchEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "{}\t{}\t{}\t{}",
+            self.op,
+            self.object_type,
+            self.object_id,
+            tree::escape_path(&self.path.to_string_lossy())
+        )
+    }
+}
+
+impl FromStr for PatchEntry {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(4, '\t');
+        let op = parts.next().ok_or(ParseError::EmptyToken)?.parse()?;
+        let object_type = parts.next().ok_or(ParseError::EmptyToken)?.parse()?;
+        let object_id = parts.next().ok_or(ParseError::EmptyToken)?.parse()?;
+        let path = tree::unescape_path(parts.next().ok_or(ParseError::EmptyToken)?)?;
+        Ok(PatchEntry {
+            op,
+            object_type,
+            object_id,
+            path: PathBuf::from(path),
+        })
+    }
+}
+
+/// Compute the patch that turns tree `a` into tree `b`.
+pub fn diff(
+    ctx: &Context,
+    a: &ObjectID,
+    b: &ObjectID,
+) -> Result<Vec<PatchEntry>, ReadContentError> {
+    let mut patch = Vec::new();
+    diff_trees(ctx, Path::new(""), a, b, &mut patch)?;
+    Ok(patch)
+}
+
+fn diff_trees(
+    ctx: &Context,
+    parent: &Path,
+    a: &ObjectID,
+    b: &ObjectID,
+    patch: &mut Vec<PatchEntry>,
+) -> Result<(), ReadContentError> {
+    if a == b {
+        return Ok(());
+    }
+
+    let tree_a = ctx.read_tree_contents(a)?;
+    let tree_b = ctx.read_tree_contents(b)?;
+    let pairs = tree_a
+        .into_iter()
+        .merge_join_by(tree_b, |x, y| x.file_path.cmp(&y.file_path));
+    for pair in pairs {
+        match pair {
+            EitherOrBoth::Left(x) => removed(parent, &x, patch),
+            EitherOrBoth::Right(y) => added(ctx, parent, &y, patch)?,
+            EitherOrBoth::Both(x, y) => match (&x.object_type, &y.object_type) {
+                _ if x == y => continue,
+                (ObjectType::Tree, ObjectType::Tree) => {
+                    let path = parent.join(&x.file_path);
+                    diff_trees(ctx, &path, &x.object_id, &y.object_id, patch)?;
+                }
+                (ObjectType::File, ObjectType::File) => patch.push(PatchEntry {
+                    op: PatchOp::Set,
+                    object_type: ObjectType::File,
+                    object_id: y.object_id,
+                    path: parent.join(&y.file_path),
+                }),
+                _ => {
+                    removed(parent, &x, patch);
+                    added(ctx, parent, &y, patch)?;
+                }
+            },
+        }
+    }
+    Ok(())
+}
+
+fn removed(parent: &Path, object: &Object, patch: &mut Vec<PatchEntry>) {
+    patch.push(PatchEntry {
+        op: PatchOp::Remove,
+        object_type: object.object_type.clone(),
+        object_id: object.object_id,
+        path: parent.join(&object.file_path),
+    });
+}
+
+fn added(
+    ctx: &Context,
+    parent: &Path,
+    object: &Object,
+    patch: &mut Vec<PatchEntry>,
+) -> Result<(), ReadContentError> {
+    let path = parent.join(&object.file_path);
+    let entries = match object.object_type {
+        ObjectType::File => Vec::new(),
+        ObjectType::Tree => tree::flatten(ctx, &object.object_id)?,
+    };
+    if entries.is_empty() {
+        // a file or an empty tree
+        patch.push(PatchEntry {
+            op: PatchOp::Add,
+            object_type: object.object_type.clone(),
+            object_id: object.object_id,
+            path,
+        });
+        return Ok(());
+    }
+
+    for entry in entries {
+        patch.push(PatchEntry {
+            op: PatchOp::Add,
+            object_type: entry.object_type,
+            object_id: entry.object_id,
+            path: path.join(entry.path),
+        });
+    }
+    Ok(())
+}
+
+/// Apply a patch to `base` and return the ID of the new root tree.
+pub fn apply(ctx: &Context, base: &ObjectID, patch: &[PatchEntry]) -> Result<ObjectID, TreeError> {
+    let mut editor = TreeEditor::new(ctx, *base);
+    for entry in patch {
+        let object_type = entry.object_type.clone();
+        match entry.op {
+            PatchOp::Add => editor.add(object_type, entry.object_id, &entry.path)?,
+            PatchOp::Remove => editor.remove(object_type, &entry.path)?,
+            PatchOp::Set => editor.set(object_type, entry.object_id, &entry.path)?,
+        }
+    }
+    editor.write()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply, diff, PatchEntry, PatchOp};
+    use crate::{Context, Object, ObjectID, ObjectType};
+
+    #[test]
+    fn test_patch_entry() {
+        let line = "add\tfile\td447b1ea40e6988b\ta\\tb/c";
+        let entry: PatchEntry = line.parse().unwrap();
+        assert_eq!(entry.op, PatchOp::Add);
+        assert_eq!(entry.object_type, ObjectType::File);
+        assert_eq!(entry.path.to_str(), Some("a\tb/c"));
+        assert_eq!(entry.to_string(), line);
+
+        assert!("move\tfile\td447b1ea40e6988b\ta"
+            .parse::<PatchEntry>()
+            .is_err());
+        assert!("add\tfile\td447b1ea40e6988b".parse::<PatchEntry>().is_err());
+    }
+
+    #[test]
+    fn test_diff_apply() {
+        let ctx = Context::in_memory();
+        let file = ObjectID::from_contents("hello world");
+        let other = ObjectID::from_contents("other");
+
+        let sub = ctx
+            .write_tree_contents(&[Object::new_file(file, "c")])
+            .unwrap();
+        let a = ctx
+            .write_tree_contents(&[
+                Object::new_file(file, "a"),
+                Object::new_tree(sub, "b"),
+                Object::new_file(file, "d"),
+            ])
+            .unwrap();
+        let changed = ctx
+            .write_tree_contents(&[Object::new_file(other, "c"), Object::new_tree(sub, "e")])
+            .unwrap();
+        let b = ctx
+            .write_tree_contents(&[Object::new_tree(changed, "b"), Object::new_tree(sub, "d")])
+            .unwrap();
+
+        let patch = diff(&ctx, &a, &b).unwrap();
+        let lines: Vec<_> = patch.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            lines,
+            vec![
+                format!("remove\tfile\t{}\ta", file),
+                format!("set\tfile\t{}\tb/c", other),
+                format!("add\tfile\t{}\tb/e/c", file),
+                format!("remove\tfile\t{}\td", file),
+                format!("add\tfile\t{}\td/c", file),
+            ]
+        );
+        assert_eq!(apply(&ctx, &a, &patch).unwrap(), b);
+
+        assert!(diff(&ctx, &a, &a).unwrap().is_empty());
+        assert!(apply(&ctx, &b, &patch).is_err());
+    }
+}
@@ -0,0 +1,126 @@
+//! Scheduling priority of the process, for scans run in the background.
+//!
+//! Priorities are set once at startup, before any worker thread is spawned,
+//! so that the walker, rayon and writer threads all inherit them.
+
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+use crate::ParseError;
+
+/// Nice value of `--background`.
+pub const BACKGROUND_NICE: i32 = 19;
+
+/// I/O scheduling class and level, as in ionice(1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoPriority {
+    /// Only gets disk time when no one else needs it
+    Idle,
+    /// The default class, with a level from 0 (highest) to 7
+    BestEffort(u8),
+    /// Always first, with a level from 0 (highest) to 7
+    Realtime(u8),
+}
+
+impl fmt::Display for IoPriority {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IoPriority::Idle => write!(f, "idle"),
+            IoPriority::BestEffort(level) => write!(f, "best-effort:{}", level),
+            IoPriority::Realtime(level) => write!(f, "realtime:{}", level),
+        }
+    }
+}
+
+impl FromStr for IoPriority {
+    type Err = ParseError;
+
+    /// "idle", or "best-effort" or "realtime" with an optional ":level".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (class, level) = match s.split_once(':') {
+            Some((class, level)) => match level.parse() {
+                Ok(level) if level <= 7 => (class, Some(level)),
+                _ => return Err(ParseError::InvalidToken(s.to_string())),
+            },
+            None => (s, None),
+        };
+        match (class, level) {
+            ("idle", None) => Ok(IoPriority::Idle),
+            ("best-effort", level) => Ok(IoPriority::BestEffort(level.unwrap_or(4))),
+            ("realtime", level) => Ok(IoPriority::Realtime(level.unwrap_or(4))),
+            ("", _) => Err(ParseError::EmptyToken),
+            _ => Err(ParseError::InvalidToken(s.to_string())),
+        }
+    }
+}
+
+/// Set the nice value of the process. Lowering it needs privileges.
+#[cfg(unix)]
+pub fn set_nice(nice: i32) -> io::Result<()> {
+    let ret = unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) };
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(unix))]
+pub fn set_nice(_nice: i32) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "--nice is not supported on this platform",
+    ))
+}
+
+/// Set the I/O priority of the process.
+#[cfg(target_os = "linux")]
+pub fn set_io_priority(priority: IoPriority) -> io::Result<()> {
+    // see linux/ioprio.h
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+    let (class, level) = match priority {
+        IoPriority::Realtime(level) => (1, level),
+        IoPriority::BestEffort(level) => (2, level),
+        IoPriority::Idle => (3, 0),
+    };
+    let ioprio = (class << IOPRIO_CLASS_SHIFT) | level as libc::c_int;
+
+    let ret = unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, ioprio) };
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_io_priority(_priority: IoPriority) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "--ionice is only supported on Linux",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IoPriority;
+
+    #[test]
+    fn test_parse_io_priority() {
+        assert_eq!("idle".parse::<IoPriority>().unwrap(), IoPriority::Idle);
+        assert_eq!(
+            "best-effort".parse::<IoPriority>().unwrap(),
+            IoPriority::BestEffort(4)
+        );
+        assert_eq!(
+            "realtime:0".parse::<IoPriority>().unwrap(),
+            IoPriority::Realtime(0)
+        );
+        assert!("idle:3".parse::<IoPriority>().is_err());
+        assert!("best-effort:8".parse::<IoPriority>().is_err());
+        assert!("low".parse::<IoPriority>().is_err());
+        assert!("".parse::<IoPriority>().is_err());
+    }
+}
@@ -1,70 +1,250 @@
+//! Progress of builds, reported to a [`ProgressSink`] so that whoever runs
+//! the build picks how it shows: bars on a terminal, nothing, or JSON lines
+//! for another program to read.
+
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use clap::ValueEnum;
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use serde::Serialize;
+use serde_json::json;
+
+use crate::output;
+
+/// Shortest time between two "progress" lines of [`JsonProgress`].
+pub const JSON_INTERVAL: Duration = Duration::from_millis(200);
+
+/// What a build is doing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "phase", rename_all = "snake_case")]
+pub enum Phase {
+    /// Looking for the entries to hash
+    Scan,
+    /// Hashing the entries found
+    Hash { files: u64, dirs: u64 },
+}
+
+/// Receives the progress of a build. The counters are bumped from the
+/// hashing threads.
+pub trait ProgressSink: Send + Sync {
+    fn set_phase(&self, phase: Phase);
+
+    fn inc_file(&self, delta: u64);
+
+    fn inc_dir(&self, delta: u64);
+
+    fn message(&self, message: &str);
+
+    /// The build is over, whether it succeeded or not.
+    fn finish(&self) {}
+}
+
+/// How `--progress` shows the progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ProgressFormat {
+    /// Bars on stderr
+    Bar,
+    /// JSON lines on stderr
+    Json,
+}
+
+impl ProgressFormat {
+    pub fn sink(format: Option<ProgressFormat>) -> Arc<dyn ProgressSink> {
+        match format {
+            Some(ProgressFormat::Bar) => Arc::new(BuildProgressBar::default()),
+            Some(ProgressFormat::Json) => Arc::new(JsonProgress::new(std::io::stderr())),
+            None => Arc::new(NoProgress),
+        }
+    }
+}
 
-#[derive(Clone)]
+/// Ignores the progress.
+pub struct NoProgress;
+
+impl ProgressSink for NoProgress {
+    fn set_phase(&self, _phase: Phase) {}
+
+    fn inc_file(&self, _delta: u64) {}
+
+    fn inc_dir(&self, _delta: u64) {}
+
+    fn message(&self, _message: &str) {}
+}
+
+/// A bar for the files and one for the directories, shown once hashing
+/// starts.
+#[derive(Default)]
 pub struct BuildProgressBar {
-    pb_file: Option<ProgressBar>,
-    pb_dir: Option<ProgressBar>,
+    multi: MultiProgress,
+    bars: Mutex<Option<(ProgressBar, ProgressBar)>>,
 }
 
 impl BuildProgressBar {
-    pub fn new(num_files: u64, num_dirs: u64, enabled: bool) -> Self {
-        if !enabled {
-            return BuildProgressBar {
-                pb_file: None,
-                pb_dir: None,
-            };
+    fn with_bars(&self, f: impl FnOnce(&ProgressBar, &ProgressBar)) {
+        if let Some((pb_file, pb_dir)) = self.bars.lock().unwrap().as_ref() {
+            f(pb_file, pb_dir);
         }
+    }
+}
 
+impl ProgressSink for BuildProgressBar {
+    fn set_phase(&self, phase: Phase) {
+        let Phase::Hash { files, dirs } = phase else {
+            return;
+        };
         let style = ProgressStyle::with_template(
             "[{elapsed_precise}] {bar:50.cyan/blue} {pos:>7}/{len:7} {msg}",
         )
         .unwrap()
         .progress_chars("##-");
-
-        let m = MultiProgress::new();
-        let pb_file = {
-            let pb = m.add(ProgressBar::new(num_files));
+        let bar = |len, message| {
+            let pb = self.multi.add(ProgressBar::new(len));
             pb.set_style(style.clone());
-            pb.set_message("files");
-            pb
-        };
-        let pb_dir = {
-            let pb = m.add(ProgressBar::new(num_dirs));
-            pb.set_style(style.clone());
-            pb.set_message("dirs");
+            pb.set_message(message);
             pb
         };
+        *self.bars.lock().unwrap() = Some((bar(files, "files"), bar(dirs, "dirs")));
+    }
 
-        BuildProgressBar {
-            pb_file: Some(pb_file),
-            pb_dir: Some(pb_dir),
-        }
+    fn inc_file(&self, delta: u64) {
+        self.with_bars(|pb_file, _| pb_file.inc(delta));
+    }
+
+    fn inc_dir(&self, delta: u64) {
+        self.with_bars(|_, pb_dir| pb_dir.inc(delta));
     }
 
-    pub fn finish(&self) {
-        if let Some(ref pb_file) = self.pb_file {
+    fn message(&self, message: &str) {
+        // printed above the bars, so that they are not torn
+        let _ = self.multi.println(message);
+    }
+
+    fn finish(&self) {
+        self.with_bars(|pb_file, pb_dir| {
             pb_file.finish();
-        }
-        if let Some(ref pb_dir) = self.pb_dir {
             pb_dir.finish();
-        }
+        });
+    }
+}
+
+impl Drop for BuildProgressBar {
+    fn drop(&mut self) {
+        self.finish();
     }
+}
+
+/// Writes one JSON object per line: `{"event":"phase",...}` on each phase,
+/// `{"event":"progress","files":n,"dirs":n}` at most every
+/// [`JSON_INTERVAL`], `{"event":"message",...}` and a last
+/// `{"event":"finish",...}` with the final counts. Write errors are ignored,
+/// as the build matters more than its progress.
+pub struct JsonProgress<W> {
+    out: Mutex<W>,
+    files: AtomicU64,
+    dirs: AtomicU64,
+    start: Instant,
+    // milliseconds from start to the last "progress" line
+    last: AtomicU64,
+}
 
-    pub fn inc_file(&self, delta: u64) {
-        if let Some(ref pb_file) = self.pb_file {
-            pb_file.inc(delta);
+impl<W: Write + Send> JsonProgress<W> {
+    pub fn new(out: W) -> Self {
+        JsonProgress {
+            out: Mutex::new(out),
+            files: AtomicU64::new(0),
+            dirs: AtomicU64::new(0),
+            start: Instant::now(),
+            last: AtomicU64::new(0),
         }
     }
 
-    pub fn inc_dir(&self, delta: u64) {
-        if let Some(ref pb_dir) = self.pb_dir {
-            pb_dir.inc(delta);
+    pub fn into_inner(self) -> W {
+        self.out.into_inner().unwrap()
+    }
+
+    fn emit(&self, value: serde_json::Value) {
+        let _ = output::write_json(&mut *self.out.lock().unwrap(), &value);
+    }
+
+    fn counts(&self, event: &str) -> serde_json::Value {
+        json!({
+            "event": event,
+            "files": self.files.load(Ordering::Relaxed),
+            "dirs": self.dirs.load(Ordering::Relaxed),
+        })
+    }
+
+    // a "progress" line, unless one was written less than JSON_INTERVAL ago
+    fn tick(&self) {
+        let now = self.start.elapsed().as_millis() as u64;
+        let last = self.last.load(Ordering::Relaxed);
+        if now < last + JSON_INTERVAL.as_millis() as u64 {
+            return;
+        }
+        if self
+            .last
+            .compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+        {
+            self.emit(self.counts("progress"));
         }
     }
 }
 
-impl Drop for BuildProgressBar {
-    fn drop(&mut self) {
-        self.finish();
+impl<W: Write + Send> ProgressSink for JsonProgress<W> {
+    fn set_phase(&self, phase: Phase) {
+        let mut value = serde_json::to_value(phase).unwrap();
+        value["event"] = json!("phase");
+        self.emit(value);
+    }
+
+    fn inc_file(&self, delta: u64) {
+        self.files.fetch_add(delta, Ordering::Relaxed);
+        self.tick();
+    }
+
+    fn inc_dir(&self, delta: u64) {
+        self.dirs.fetch_add(delta, Ordering::Relaxed);
+        self.tick();
+    }
+
+    fn message(&self, message: &str) {
+        self.emit(json!({"event": "message", "message": message}));
+    }
+
+    fn finish(&self) {
+        self.emit(self.counts("finish"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{JsonProgress, Phase, ProgressSink};
+
+    #[test]
+    fn test_json_progress() {
+        let progress = JsonProgress::new(Vec::new());
+        progress.set_phase(Phase::Scan);
+        progress.set_phase(Phase::Hash { files: 2, dirs: 1 });
+        progress.inc_file(1);
+        progress.inc_file(1);
+        progress.inc_dir(1);
+        progress.message("done");
+        progress.finish();
+
+        let out = String::from_utf8(progress.into_inner()).unwrap();
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], r#"{"event":"phase","phase":"scan"}"#);
+        assert_eq!(
+            lines[1],
+            r#"{"dirs":1,"event":"phase","files":2,"phase":"hash"}"#
+        );
+        // the counts come too fast for a "progress" line
+        assert_eq!(lines[2], r#"{"event":"message","message":"done"}"#);
+        assert_eq!(lines[3], r#"{"dirs":1,"event":"finish","files":2}"#);
+        assert_eq!(lines.len(), 4);
     }
 }
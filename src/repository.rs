@@ -0,0 +1,324 @@
+//! A handle on a repository for programs embedding mtl.
+//!
+//! [`Repository`] covers what the `local build`, `local status`, `diff` and
+//! `ref list` commands do, returning typed results instead of printing them.
+//! The [`Context`] underneath stays available for everything else. Failures
+//! are [`MtlError`]s, so that a busy lock or a missing ref can be told apart.
+//!
+//! ```no_run
+//! use mtl::repository::{BuildOptions, Repository};
+//!
+//! let repo = Repository::open("/data").unwrap();
+//! let build = repo.build(&BuildOptions::new().with_hidden(true)).unwrap();
+//! for entry in repo.read_tree_iter(&build.root) {
+//!     let entry = entry.unwrap();
+//!     println!("{} {}", entry.object_id, entry.path.display());
+//! }
+//! ```
+
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use globset::Glob;
+
+use crate::audit::AuditEntry;
+use crate::builder::{BuildCounts, Builder, ScanTargetGenerator};
+use crate::filter::FilterBuilder;
+use crate::hooks::Hook;
+use crate::patch::{self, PatchEntry, PatchOp};
+use crate::progress::ProgressSink;
+use crate::tree::TreeIter;
+use crate::{Context, MtlError, ObjectID, ObjectType};
+
+/// What to scan in [`Repository::build`], and whether to move HEAD.
+#[derive(Clone)]
+pub struct BuildOptions {
+    hidden: bool,
+    include: Vec<Glob>,
+    exclude: Vec<Glob>,
+    write_head: bool,
+    progress: Option<Arc<dyn ProgressSink>>,
+}
+
+impl fmt::Debug for BuildOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BuildOptions")
+            .field("hidden", &self.hidden)
+            .field("include", &self.include)
+            .field("exclude", &self.exclude)
+            .field("write_head", &self.write_head)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for BuildOptions {
+    fn default() -> Self {
+        Self {
+            hidden: false,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            write_head: true,
+            progress: None,
+        }
+    }
+}
+
+impl BuildOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Scan hidden files too.
+    pub fn with_hidden(mut self, hidden: bool) -> Self {
+        self.hidden = hidden;
+        self
+    }
+
+    /// Add include and exclude globs, as `--include` and `--exclude` do.
+    pub fn with_globs(mut self, include: &[Glob], exclude: &[Glob]) -> Self {
+        self.include.extend_from_slice(include);
+        self.exclude.extend_from_slice(exclude);
+        self
+    }
+
+    /// Whether to point HEAD at the built root, true by default.
+    pub fn with_write_head(mut self, write_head: bool) -> Self {
+        self.write_head = write_head;
+        self
+    }
+
+    /// Report the progress of builds to `progress`, e.g. a
+    /// [`JsonProgress`](crate::progress::JsonProgress).
+    pub fn with_progress(mut self, progress: Arc<dyn ProgressSink>) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+}
+
+/// The outcome of [`Repository::build`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Build {
+    pub root: ObjectID,
+    pub counts: BuildCounts,
+}
+
+/// How a file differs from HEAD, see [`Repository::status`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Modified,
+    Deleted,
+}
+
+impl Change {
+    /// The letter `local status` prints for the change.
+    pub fn code(&self) -> char {
+        match self {
+            Change::Added => 'A',
+            Change::Modified => 'M',
+            Change::Deleted => 'D',
+        }
+    }
+}
+
+impl From<PatchOp> for Change {
+    fn from(op: PatchOp) -> Self {
+        match op {
+            PatchOp::Add => Change::Added,
+            PatchOp::Set => Change::Modified,
+            PatchOp::Remove => Change::Deleted,
+        }
+    }
+}
+
+/// One changed entry. A deleted directory is a single entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusEntry {
+    pub change: Change,
+    pub object_type: ObjectType,
+    pub path: PathBuf,
+}
+
+impl From<PatchEntry> for StatusEntry {
+    fn from(entry: PatchEntry) -> Self {
+        StatusEntry {
+            change: entry.op.into(),
+            object_type: entry.object_type,
+            path: entry.path,
+        }
+    }
+}
+
+/// A ref and the object it points at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ref {
+    pub name: String,
+    pub object_id: ObjectID,
+}
+
+pub struct Repository {
+    ctx: Context,
+}
+
+impl Repository {
+    /// Open the repository in `root_dir`, creating its object store if
+    /// needed.
+    pub fn open<P: Into<PathBuf>>(root_dir: P) -> Result<Self, MtlError> {
+        Ok(Self::from_context(Context::new(root_dir)?))
+    }
+
+    pub fn from_context(ctx: Context) -> Self {
+        Self { ctx }
+    }
+
+    pub fn context(&self) -> &Context {
+        &self.ctx
+    }
+
+    /// Hash the working tree, like `local build`.
+    pub fn build(&self, options: &BuildOptions) -> Result<Build, MtlError> {
+        let _lock = self.ctx.lock_shared()?;
+        let build = self.build_unlocked(options)?;
+        let new = build.root.to_string();
+        if options.write_head {
+            let previous = self.ctx.read_head().ok();
+            self.ctx.write_head(&build.root)?;
+            self.ctx.audit(
+                &AuditEntry::new("build")
+                    .object_id(&build.root)
+                    .reference("HEAD")
+                    .previous(previous),
+            )?;
+            let old = previous.map(|id| id.to_string());
+            self.ctx
+                .hooks()
+                .notify_update(Hook::PostBuild, "HEAD", old.as_deref(), Some(&new));
+        } else {
+            self.ctx
+                .hooks()
+                .notify_update(Hook::PostBuild, "", None, Some(&new));
+        }
+        Ok(build)
+    }
+
+    fn build_unlocked(&self, options: &BuildOptions) -> Result<Build, MtlError> {
+        let filter = FilterBuilder::new(self.ctx.root_dir())
+            .with_globs(&options.include, &options.exclude)
+            .build()?;
+        let generator = ScanTargetGenerator::new(filter, options.hidden);
+        let mut builder = Builder::new(Box::new(generator), false);
+        if let Some(progress) = &options.progress {
+            builder = builder.with_progress_sink(progress.clone());
+        }
+        let (root, counts) = builder.build_counted(&self.ctx)?;
+        Ok(Build {
+            root: root.object_id,
+            counts,
+        })
+    }
+
+    /// The files changed in the working tree since HEAD, like `local status`.
+    /// HEAD is left alone, whatever the options say.
+    pub fn status(&self, options: &BuildOptions) -> Result<Vec<StatusEntry>, MtlError> {
+        let _lock = self.ctx.lock_shared()?;
+        let head = self.ctx.read_head()?;
+        let build = self.build_unlocked(options)?;
+        Ok(self
+            .diff(&head, &build.root)?
+            .into_iter()
+            .map(StatusEntry::from)
+            .collect())
+    }
+
+    /// The patch turning tree `a` into tree `b`, see [`patch::diff`].
+    pub fn diff(&self, a: &ObjectID, b: &ObjectID) -> Result<Vec<PatchEntry>, MtlError> {
+        Ok(patch::diff(&self.ctx, a, b)?)
+    }
+
+    pub fn head(&self) -> Result<ObjectID, MtlError> {
+        Ok(self.ctx.read_head()?)
+    }
+
+    /// The refs, sorted by name.
+    pub fn refs(&self) -> Result<Vec<Ref>, MtlError> {
+        self.ctx
+            .list_object_refs()?
+            .into_iter()
+            .map(|object_ref| {
+                Ok(Ref {
+                    object_id: self.ctx.deref_object_ref(&object_ref)?,
+                    name: object_ref.to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// Walk a tree lazily, each directory before its entries.
+    pub fn read_tree_iter(&self, object_id: &ObjectID) -> TreeIter<'_> {
+        self.ctx.tree_iter(object_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{Change, Repository};
+    use crate::{Context, MtlError, Object, ObjectID, ObjectType};
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(
+            Repository::open("/nonexistent"),
+            Err(MtlError::NotARepository(_))
+        ));
+        let repo = Repository::from_context(Context::in_memory());
+        assert!(matches!(repo.head(), Err(MtlError::RefNotFound { name }) if name == "HEAD"));
+        let root = repo.context().write_tree_contents::<Object>(&[]).unwrap();
+        let missing = ObjectID::from_contents("missing");
+        assert!(matches!(
+            repo.diff(&root, &missing),
+            Err(MtlError::ObjectNotFound)
+        ));
+    }
+
+    #[test]
+    fn test_read_tree_iter() {
+        let repo = Repository::from_context(Context::in_memory());
+        let ctx = repo.context();
+        let a = ObjectID::from_contents("a");
+        let b = ObjectID::from_contents("b");
+        let sub = ctx
+            .write_tree_contents(&[Object::new_file(b, "b")])
+            .unwrap();
+        let root = ctx
+            .write_tree_contents(&[Object::new_file(a, "a"), Object::new_tree(sub, "d")])
+            .unwrap();
+
+        let entries = repo
+            .read_tree_iter(&root)
+            .map(|entry| {
+                let entry = entry.unwrap();
+                (entry.object_type, entry.path)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            [
+                (ObjectType::File, PathBuf::from("a")),
+                (ObjectType::Tree, PathBuf::from("d")),
+                (ObjectType::File, PathBuf::from("d/b")),
+            ]
+        );
+
+        let diff = repo.diff(&root, &sub).unwrap();
+        let changes = diff.iter().map(|e| Change::from(e.op)).collect::<Vec<_>>();
+        assert_eq!(changes, [Change::Deleted, Change::Added, Change::Deleted]);
+
+        let missing = ObjectID::from_contents("missing");
+        let mut iter = repo.read_tree_iter(&missing);
+        assert!(iter.next().unwrap().is_err());
+        assert!(iter.next().is_none());
+    }
+}
@@ -0,0 +1,135 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::datetime;
+
+/// Backup-style retention policy for snapshots.
+///
+/// Each `keep_*` rule keeps the newest snapshot of that many distinct
+/// periods, counted from the newest snapshot; a snapshot is kept if any rule
+/// keeps it. Periods are UTC days, Monday-based weeks and calendar months.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    pub keep_last: usize,
+    pub keep_daily: usize,
+    pub keep_weekly: usize,
+    pub keep_monthly: usize,
+}
+
+impl RetentionPolicy {
+    pub fn is_empty(&self) -> bool {
+        self.keep_last == 0
+            && self.keep_daily == 0
+            && self.keep_weekly == 0
+            && self.keep_monthly == 0
+    }
+
+    /// Decide which snapshots to keep. `times` must be sorted newest first.
+    pub fn retain(&self, times: &[SystemTime]) -> Vec<bool> {
+        let mut keep = vec![false; times.len()];
+        keep.iter_mut().take(self.keep_last).for_each(|k| *k = true);
+
+        let days = times
+            .iter()
+            .map(|time| days_since_epoch(*time))
+            .collect::<Vec<_>>();
+        let rules: [(usize, Period); 3] = [
+            (self.keep_daily, |day| day),
+            // 1970-01-01 was a Thursday
+            (self.keep_weekly, |day| (day + 3).div_euclid(7)),
+            (self.keep_monthly, |day| {
+                let (year, month, _) = datetime::civil_from_days(day);
+                year * 12 + i64::from(month)
+            }),
+        ];
+        for (count, period) in rules {
+            let mut last_period = None;
+            let mut kept = 0;
+            for (i, day) in days.iter().enumerate() {
+                if kept >= count {
+                    break;
+                }
+                let period = period(*day);
+                if last_period != Some(period) {
+                    keep[i] = true;
+                    kept += 1;
+                    last_period = Some(period);
+                }
+            }
+        }
+        keep
+    }
+}
+
+// maps days since the epoch to the period containing them
+type Period = fn(i64) -> i64;
+
+fn days_since_epoch(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => (d.as_secs() / 86400) as i64,
+        Err(e) => -(e.duration().as_secs().div_ceil(86400) as i64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RetentionPolicy;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    // hours after 2024-01-01T00:00:00Z, a Monday
+    fn at(hours: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1704067200 + hours * 3600)
+    }
+
+    #[test]
+    fn test_retain() {
+        // newest first: two on Jan 15, one on Jan 14, Jan 8, Jan 1, Dec 31
+        let times = [
+            at(14 * 24 + 12),
+            at(14 * 24 + 1),
+            at(13 * 24),
+            at(7 * 24),
+            at(0),
+            at(0) - Duration::from_secs(3600),
+        ];
+
+        let policy = RetentionPolicy {
+            keep_last: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            policy.retain(&times),
+            [true, false, false, false, false, false]
+        );
+
+        let policy = RetentionPolicy {
+            keep_daily: 3,
+            ..Default::default()
+        };
+        assert_eq!(
+            policy.retain(&times),
+            [true, false, true, true, false, false]
+        );
+
+        let policy = RetentionPolicy {
+            keep_weekly: 3,
+            ..Default::default()
+        };
+        // Jan 14 is a Sunday, so it closes the week of Jan 8
+        assert_eq!(
+            policy.retain(&times),
+            [true, false, true, false, true, false]
+        );
+
+        let policy = RetentionPolicy {
+            keep_monthly: 12,
+            ..Default::default()
+        };
+        assert_eq!(
+            policy.retain(&times),
+            [true, false, false, false, false, true]
+        );
+
+        assert!(RetentionPolicy::default().is_empty());
+        assert!(!policy.is_empty());
+    }
+}
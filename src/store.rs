@@ -0,0 +1,702 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+use redb::{
+    DatabaseError, ReadOnlyTable, ReadTransaction, ReadableTable, StorageError, TableError,
+};
+use serde::Serialize;
+
+use crate::{
+    delta, filesystem, header, HeaderError, ObjectID, ObjectType, ParseError, ReadContentError,
+    PACKED_DELTAS_TABLE, PACKED_OBJECTS_TABLE,
+};
+
+/// Storage backend for objects.
+///
+/// Objects are immutable and addressed by their [`ObjectID`], so `put` of an
+/// existing object is expected to be a no-op in effect. The ID is the hash of
+/// the contents alone, not of the kind they are stored with.
+pub trait ObjectStore: Send + Sync {
+    /// Read the contents of an object along with its kind, which is `None`
+    /// for objects stored before kinds were recorded.
+    fn get_typed(
+        &self,
+        object_id: &ObjectID,
+    ) -> Result<(Option<ObjectType>, Vec<u8>), ReadContentError>;
+
+    /// Read the contents of an object.
+    fn get(&self, object_id: &ObjectID) -> Result<Vec<u8>, ReadContentError> {
+        Ok(self.get_typed(object_id)?.1)
+    }
+
+    /// Store the contents of an object of the given kind.
+    fn put(&self, object_id: &ObjectID, kind: ObjectType, contents: &[u8]) -> io::Result<()>;
+
+    /// List the IDs of all objects in the store.
+    fn list(&self) -> Result<Vec<ObjectID>, ReadContentError>;
+
+    /// Check whether the store has an object.
+    fn contains(&self, object_id: &ObjectID) -> Result<bool, ReadContentError>;
+
+    /// Store many objects of one kind at once, making them durable before
+    /// returning if `sync` is true. Backends override this to amortize
+    /// per-object costs.
+    fn put_batch(
+        &self,
+        kind: ObjectType,
+        objects: &[(ObjectID, Vec<u8>)],
+        _sync: bool,
+    ) -> io::Result<()> {
+        for (object_id, contents) in objects {
+            self.put(object_id, kind.clone(), contents)?;
+        }
+        Ok(())
+    }
+}
+
+/// Object store backed by loose object files and the packed redb database.
+///
+/// New objects are written as loose files unless they already exist loose or
+/// packed; the packed database is only read from here and is maintained by
+/// `mtl pack`. Both hold objects with a [`header`] in front, or without one
+/// if they were written by an older mtl.
+pub struct LocalObjectStore {
+    objects_dir: PathBuf,
+    // where loose objects are written before being renamed in place
+    tmp_dir: PathBuf,
+    packed_db: Option<redb::Database>,
+    // why the pack could not be opened, if it is damaged
+    pack_error: Option<String>,
+    counters: Arc<StoreCounters>,
+}
+
+/// Running counts of the reads and writes of a store, shared by the threads
+/// using it.
+#[derive(Debug, Default)]
+pub struct StoreCounters {
+    loose_reads: AtomicU64,
+    loose_bytes: AtomicU64,
+    packed_reads: AtomicU64,
+    packed_bytes: AtomicU64,
+    delta_reads: AtomicU64,
+    writes: AtomicU64,
+    write_bytes: AtomicU64,
+}
+
+/// A snapshot of [`StoreCounters`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct StoreStats {
+    pub loose_reads: u64,
+    pub loose_bytes: u64,
+    /// Objects read from the pack, those stored as deltas included
+    pub packed_reads: u64,
+    /// Bytes read from the pack, the bases of deltas included
+    pub packed_bytes: u64,
+    pub delta_reads: u64,
+    pub writes: u64,
+    pub write_bytes: u64,
+}
+
+impl StoreCounters {
+    pub fn snapshot(&self) -> StoreStats {
+        StoreStats {
+            loose_reads: self.loose_reads.load(Ordering::Relaxed),
+            loose_bytes: self.loose_bytes.load(Ordering::Relaxed),
+            packed_reads: self.packed_reads.load(Ordering::Relaxed),
+            packed_bytes: self.packed_bytes.load(Ordering::Relaxed),
+            delta_reads: self.delta_reads.load(Ordering::Relaxed),
+            writes: self.writes.load(Ordering::Relaxed),
+            write_bytes: self.write_bytes.load(Ordering::Relaxed),
+        }
+    }
+
+    fn add(count: &AtomicU64, bytes: &AtomicU64, len: usize) {
+        count.fetch_add(1, Ordering::Relaxed);
+        bytes.fetch_add(len as u64, Ordering::Relaxed);
+    }
+}
+
+impl LocalObjectStore {
+    /// Open the store. A damaged pack, or one written by a newer redb, is
+    /// left unopened so that the loose objects can still be read; see
+    /// [`LocalObjectStore::pack_error`].
+    pub fn open<P: Into<PathBuf>, Q: AsRef<Path>>(
+        objects_dir: P,
+        pack_file: Q,
+    ) -> anyhow::Result<Self> {
+        let pack_file = pack_file.as_ref();
+        let (packed_db, pack_error) = match pack_file.exists() {
+            true => match open_pack(pack_file)? {
+                Ok(db) => (Some(db), None),
+                Err(reason) => {
+                    log::warn!("cannot open {}: {}", pack_file.display(), reason);
+                    (None, Some(reason))
+                }
+            },
+            false => (None, None),
+        };
+
+        let objects_dir = objects_dir.into();
+        Ok(LocalObjectStore {
+            tmp_dir: objects_dir.with_file_name("tmp"),
+            objects_dir,
+            packed_db,
+            pack_error,
+            counters: Arc::default(),
+        })
+    }
+
+    /// Why the pack could not be opened, if it exists but is unreadable.
+    pub fn pack_error(&self) -> Option<&str> {
+        self.pack_error.as_deref()
+    }
+
+    pub fn counters(&self) -> Arc<StoreCounters> {
+        self.counters.clone()
+    }
+
+    /// Count reads and writes into `counters`, e.g. those of the store this
+    /// one replaces.
+    pub fn with_counters(mut self, counters: Arc<StoreCounters>) -> Self {
+        self.counters = counters;
+        self
+    }
+
+    fn object_file(&self, object_id: &ObjectID) -> PathBuf {
+        loose_object_file(&self.objects_dir, object_id)
+    }
+
+    fn begin_pack_read(&self) -> Option<ReadTransaction<'_>> {
+        self.packed_db.as_ref()?.begin_read().ok()
+    }
+
+    // Write the stored form of an object unless it is there already, and
+    // return whether it was written. An existing loose object gets a new
+    // mtime, so that gc --grace counts it as just written; one of another
+    // size, e.g. a truncated one, is replaced. The contents are not read
+    // back, which would cost as much as writing them.
+    fn write_loose(
+        &self,
+        object_id: &ObjectID,
+        stored: &[u8],
+        pack: &PackLookup,
+        dirs: &mut HashSet<PathBuf>,
+    ) -> io::Result<bool> {
+        let file_name = self.object_file(object_id);
+        match fs::metadata(&file_name) {
+            Ok(metadata) if metadata.len() == stored.len() as u64 => {
+                File::open(&file_name)?.set_modified(SystemTime::now())?;
+                return Ok(false);
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound && pack.contains(object_id) => {
+                return Ok(false);
+            }
+            _ => {}
+        }
+
+        if let Some(dir_name) = file_name.parent() {
+            if !dirs.contains(dir_name) {
+                fs::create_dir_all(dir_name)?;
+                dirs.insert(dir_name.to_path_buf());
+            }
+        }
+        filesystem::write_atomic(&file_name, &self.tmp_dir, stored)?;
+        StoreCounters::add(
+            &self.counters.writes,
+            &self.counters.write_bytes,
+            stored.len(),
+        );
+        Ok(true)
+    }
+}
+
+// The tables of the pack, opened once for a batch of lookups
+struct PackLookup<'txn> {
+    tables: Option<(PackedTable<'txn>, Option<DeltasTable<'txn>>)>,
+}
+
+impl<'txn> PackLookup<'txn> {
+    // A pack that cannot be read only costs loose copies of its objects.
+    fn new(read_txn: &'txn Option<ReadTransaction>) -> Self {
+        let tables = read_txn.as_ref().and_then(|read_txn| {
+            let objects = read_txn.open_table(PACKED_OBJECTS_TABLE).ok()?;
+            Some((objects, open_deltas(read_txn).ok()?))
+        });
+        PackLookup { tables }
+    }
+
+    fn contains(&self, object_id: &ObjectID) -> bool {
+        let Some((objects, deltas)) = &self.tables else {
+            return false;
+        };
+        matches!(objects.get(object_id), Ok(Some(_)))
+            || deltas
+                .as_ref()
+                .is_some_and(|deltas| matches!(deltas.get(object_id), Ok(Some(_))))
+    }
+}
+
+impl ObjectStore for LocalObjectStore {
+    fn get_typed(
+        &self,
+        object_id: &ObjectID,
+    ) -> Result<(Option<ObjectType>, Vec<u8>), ReadContentError> {
+        let counters = &self.counters;
+        if let Ok(contents) = fs::read(self.object_file(object_id)) {
+            StoreCounters::add(&counters.loose_reads, &counters.loose_bytes, contents.len());
+            return header::decode_vec(contents).map_err(corrupt(object_id));
+        }
+
+        let Some(packed_db) = &self.packed_db else {
+            return Err(ReadContentError::ObjectNotFound);
+        };
+
+        let read_txn = packed_db.begin_read()?;
+        let table = read_txn.open_table(PACKED_OBJECTS_TABLE)?;
+        if let Some(v) = table.get(object_id)? {
+            let contents = v.value();
+            StoreCounters::add(
+                &counters.packed_reads,
+                &counters.packed_bytes,
+                contents.len(),
+            );
+            return header::decode_vec(contents).map_err(corrupt(object_id));
+        }
+
+        let Some(deltas) = open_deltas(&read_txn)? else {
+            return Err(ReadContentError::ObjectNotFound);
+        };
+        let Some(v) = deltas.get(object_id)? else {
+            return Err(ReadContentError::ObjectNotFound);
+        };
+        let (base_id, delta) = v.value();
+        // bases are always stored whole, so there are no chains to follow
+        let base = table
+            .get(&base_id)?
+            .ok_or(ReadContentError::ObjectNotFound)?
+            .value();
+        StoreCounters::add(
+            &counters.packed_reads,
+            &counters.packed_bytes,
+            base.len() + delta.len(),
+        );
+        counters.delta_reads.fetch_add(1, Ordering::Relaxed);
+        // deltas are taken between payloads, and share the kind of their base
+        let (kind, base) = header::decode(&base).map_err(corrupt(&base_id))?;
+        Ok((kind, delta::apply(base, &delta)?))
+    }
+
+    fn put(&self, object_id: &ObjectID, kind: ObjectType, contents: &[u8]) -> io::Result<()> {
+        let read_txn = self.begin_pack_read();
+        let pack = PackLookup::new(&read_txn);
+        let stored = header::encode(kind, contents);
+        self.write_loose(object_id, &stored, &pack, &mut HashSet::new())?;
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<ObjectID>, ReadContentError> {
+        let mut object_ids = HashSet::new();
+        for entry in loose_object_files(&self.objects_dir)? {
+            object_ids.insert(loose_object_id(&entry)?);
+        }
+
+        if let Some(packed_db) = &self.packed_db {
+            let read_txn = packed_db.begin_read()?;
+
+            let table = read_txn.open_table(PACKED_OBJECTS_TABLE)?;
+            for range in table.iter()? {
+                let (object_id, _) = range?;
+                object_ids.insert(object_id.value());
+            }
+
+            let deltas = open_deltas(&read_txn)?;
+            if let Some(deltas) = &deltas {
+                for range in deltas.iter()? {
+                    let (object_id, _) = range?;
+                    object_ids.insert(object_id.value());
+                }
+            }
+        }
+
+        Ok(object_ids.into_iter().collect())
+    }
+
+    fn contains(&self, object_id: &ObjectID) -> Result<bool, ReadContentError> {
+        if self.object_file(object_id).exists() {
+            return Ok(true);
+        }
+
+        let Some(packed_db) = &self.packed_db else {
+            return Ok(false);
+        };
+
+        let read_txn = packed_db.begin_read()?;
+        let table = read_txn.open_table(PACKED_OBJECTS_TABLE)?;
+        if table.get(object_id)?.is_some() {
+            return Ok(true);
+        }
+
+        let found = match open_deltas(&read_txn)? {
+            Some(deltas) => deltas.get(object_id)?.is_some(),
+            None => false,
+        };
+        Ok(found)
+    }
+
+    fn put_batch(
+        &self,
+        kind: ObjectType,
+        objects: &[(ObjectID, Vec<u8>)],
+        sync: bool,
+    ) -> io::Result<()> {
+        // one read transaction of the pack for the whole batch
+        let read_txn = self.begin_pack_read();
+        let pack = PackLookup::new(&read_txn);
+        let mut dirs = HashSet::new();
+        let mut written = 0;
+        for (object_id, contents) in objects {
+            let stored = header::encode(kind.clone(), contents);
+            if self.write_loose(object_id, &stored, &pack, &mut dirs)? {
+                written += 1;
+            }
+        }
+
+        // one sync for the whole batch instead of one per object
+        if sync && written > 0 {
+            filesystem::syncfs(&self.objects_dir)?;
+        }
+        Ok(())
+    }
+}
+
+// the start of every redb file
+const REDB_MAGIC: &[u8] = b"redb\x1a\x0a\xa9\x0d\x0a";
+
+/// Ok(Err(reason)) for a file that does not start with the redb magic
+/// number. redb would turn such a file into a new empty database, so files
+/// that are not known to be databases are checked before they are opened.
+pub fn check_redb_magic(path: &Path) -> io::Result<Result<(), &'static str>> {
+    let mut magic = [0; REDB_MAGIC.len()];
+    match File::open(path).and_then(|mut file| file.read_exact(&mut magic)) {
+        Ok(()) if magic == REDB_MAGIC => Ok(Ok(())),
+        Ok(()) => Ok(Err("not a redb database")),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(Err("truncated")),
+        Err(e) => Err(e),
+    }
+}
+
+// Ok(Err(reason)) for a pack that is damaged or from another redb version.
+// redb makes a new empty database of a file without its magic number, so
+// that is checked first to leave a damaged pack as it is.
+fn open_pack(pack_file: &Path) -> Result<Result<redb::Database, String>, DatabaseError> {
+    match check_redb_magic(pack_file) {
+        Ok(Ok(())) => {}
+        Ok(Err(reason)) => return Ok(Err(reason.to_string())),
+        Err(e) => return Err(StorageError::Io(e).into()),
+    }
+
+    match redb::Database::open(pack_file) {
+        Ok(db) => Ok(Ok(db)),
+        Err(e @ DatabaseError::UpgradeRequired(_))
+        | Err(e @ DatabaseError::Storage(StorageError::Corrupted(_))) => Ok(Err(e.to_string())),
+        Err(e) => Err(e),
+    }
+}
+
+type PackedTable<'txn> = ReadOnlyTable<'txn, ObjectID, Vec<u8>>;
+type DeltasTable<'txn> = ReadOnlyTable<'txn, ObjectID, (ObjectID, Vec<u8>)>;
+
+// packs written without deltas have no deltas table
+fn open_deltas<'txn>(
+    read_txn: &'txn ReadTransaction,
+) -> Result<Option<DeltasTable<'txn>>, ReadContentError> {
+    match read_txn.open_table(PACKED_DELTAS_TABLE) {
+        Ok(table) => Ok(Some(table)),
+        Err(TableError::TableDoesNotExist(_)) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// name the object whose stored header is broken
+fn corrupt(object_id: &ObjectID) -> impl FnOnce(HeaderError) -> ReadContentError + '_ {
+    move |error| ReadContentError::CorruptObject {
+        id: object_id.to_string(),
+        error,
+    }
+}
+
+/// Path of the loose object file for `object_id` under `objects_dir`.
+pub fn loose_object_file(objects_dir: &Path, object_id: &ObjectID) -> PathBuf {
+    let object_string = object_id.to_string();
+    objects_dir
+        .join(&object_string[0..2])
+        .join(&object_string[2..])
+}
+
+/// List all loose object files under `objects_dir`.
+pub fn loose_object_files(objects_dir: &Path) -> Result<Vec<PathBuf>, ReadContentError> {
+    let mut object_files = Vec::new();
+    if !objects_dir.exists() {
+        return Ok(object_files);
+    }
+
+    for entry in fs::read_dir(objects_dir)? {
+        let entry = entry?;
+        let path = entry.path();
+
+        if path.is_file() {
+            log::warn!("Unexpected file in object directory: {}", path.display());
+        }
+        if path.is_dir() {
+            for entry in fs::read_dir(path)? {
+                let entry = entry?;
+                let path = entry.path();
+
+                if path.is_dir() {
+                    log::warn!(
+                        "Unexpected directory in object directory: {}",
+                        path.display()
+                    );
+                }
+                if path.is_file() {
+                    object_files.push(path);
+                }
+            }
+        }
+    }
+
+    Ok(object_files)
+}
+
+/// Recover the object ID from a loose object file path.
+pub fn loose_object_id(path: &Path) -> Result<ObjectID, ReadContentError> {
+    let dir_name = path
+        .parent()
+        .and_then(|f| f.file_name())
+        .and_then(|f| f.to_str())
+        .ok_or(ParseError::EmptyToken)?;
+    let file_name = path
+        .file_name()
+        .and_then(|f| f.to_str())
+        .ok_or(ParseError::EmptyToken)?;
+
+    let mut buf = String::with_capacity(dir_name.len() + file_name.len());
+    buf.push_str(dir_name);
+    buf.push_str(file_name);
+
+    let object_id: ObjectID = buf.parse()?;
+    assert_eq!(object_id.to_string(), buf);
+    Ok(object_id)
+}
+
+/// Object store that discards all writes.
+///
+/// Used to compute object IDs without touching the repository.
+pub struct NullObjectStore;
+
+impl ObjectStore for NullObjectStore {
+    fn get_typed(
+        &self,
+        _object_id: &ObjectID,
+    ) -> Result<(Option<ObjectType>, Vec<u8>), ReadContentError> {
+        Err(ReadContentError::ObjectNotFound)
+    }
+
+    fn put(&self, _object_id: &ObjectID, _kind: ObjectType, _contents: &[u8]) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<ObjectID>, ReadContentError> {
+        Ok(Vec::new())
+    }
+
+    fn contains(&self, _object_id: &ObjectID) -> Result<bool, ReadContentError> {
+        Ok(false)
+    }
+}
+
+/// Object store that keeps all objects in memory.
+#[derive(Default)]
+pub struct MemoryObjectStore {
+    objects: RwLock<HashMap<ObjectID, (ObjectType, Vec<u8>)>>,
+}
+
+impl MemoryObjectStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ObjectStore for MemoryObjectStore {
+    fn get_typed(
+        &self,
+        object_id: &ObjectID,
+    ) -> Result<(Option<ObjectType>, Vec<u8>), ReadContentError> {
+        let objects = self.objects.read().unwrap();
+        let (kind, contents) = objects
+            .get(object_id)
+            .ok_or(ReadContentError::ObjectNotFound)?;
+        Ok((Some(kind.clone()), contents.clone()))
+    }
+
+    fn put(&self, object_id: &ObjectID, kind: ObjectType, contents: &[u8]) -> io::Result<()> {
+        let mut objects = self.objects.write().unwrap();
+        objects
+            .entry(*object_id)
+            .or_insert_with(|| (kind, contents.to_vec()));
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<ObjectID>, ReadContentError> {
+        let objects = self.objects.read().unwrap();
+        Ok(objects.keys().copied().collect())
+    }
+
+    fn contains(&self, object_id: &ObjectID) -> Result<bool, ReadContentError> {
+        let objects = self.objects.read().unwrap();
+        Ok(objects.contains_key(object_id))
+    }
+}
+
+/// Object store that keeps the objects written to it in memory, and reads
+/// the others from another store.
+///
+/// Used to build trees and compare them with the repository without writing
+/// them.
+pub struct OverlayObjectStore {
+    base: Arc<dyn ObjectStore>,
+    written: MemoryObjectStore,
+}
+
+impl OverlayObjectStore {
+    pub fn new(base: Arc<dyn ObjectStore>) -> Self {
+        OverlayObjectStore {
+            base,
+            written: MemoryObjectStore::new(),
+        }
+    }
+}
+
+impl ObjectStore for OverlayObjectStore {
+    fn get_typed(
+        &self,
+        object_id: &ObjectID,
+    ) -> Result<(Option<ObjectType>, Vec<u8>), ReadContentError> {
+        match self.written.get_typed(object_id) {
+            Err(ReadContentError::ObjectNotFound) => self.base.get_typed(object_id),
+            result => result,
+        }
+    }
+
+    fn put(&self, object_id: &ObjectID, kind: ObjectType, contents: &[u8]) -> io::Result<()> {
+        self.written.put(object_id, kind, contents)
+    }
+
+    fn list(&self) -> Result<Vec<ObjectID>, ReadContentError> {
+        let mut object_ids = self.base.list()?;
+        object_ids.extend(self.written.list()?);
+        object_ids.sort();
+        object_ids.dedup();
+        Ok(object_ids)
+    }
+
+    fn contains(&self, object_id: &ObjectID) -> Result<bool, ReadContentError> {
+        Ok(self.written.contains(object_id)? || self.base.contains(object_id)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+    use std::time::{Duration, SystemTime};
+
+    use std::sync::Arc;
+
+    use super::{
+        loose_object_file, LocalObjectStore, MemoryObjectStore, ObjectStore, OverlayObjectStore,
+    };
+    use crate::{ObjectID, ObjectType, ReadContentError};
+
+    #[test]
+    fn local_object_store_rewrite() {
+        let dir = std::env::temp_dir().join(format!("mtl-store-{}", std::process::id()));
+        let objects_dir = dir.join("objects");
+        let store = LocalObjectStore::open(&objects_dir, dir.join("pack")).unwrap();
+        let object_id = ObjectID::from_contents("hello world");
+        let object_file = loose_object_file(&objects_dir, &object_id);
+
+        // writing an existing object again makes it new for gc --grace
+        store
+            .put(&object_id, ObjectType::File, b"hello world")
+            .unwrap();
+        let old = SystemTime::now() - Duration::from_secs(86400);
+        File::open(&object_file).unwrap().set_modified(old).unwrap();
+        store
+            .put_batch(
+                ObjectType::File,
+                &[(object_id, b"hello world".to_vec())],
+                false,
+            )
+            .unwrap();
+        let mtime = fs::metadata(&object_file).unwrap().modified().unwrap();
+        assert!(mtime > old + Duration::from_secs(3600));
+        assert_eq!(store.counters().snapshot().writes, 1);
+
+        // a truncated object is replaced
+        fs::write(&object_file, b"hello").unwrap();
+        store
+            .put(&object_id, ObjectType::File, b"hello world")
+            .unwrap();
+        assert_eq!(store.get(&object_id).unwrap(), b"hello world");
+        assert_eq!(store.counters().snapshot().writes, 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn memory_object_store() {
+        let store = MemoryObjectStore::new();
+        let object_id = ObjectID::from_contents("hello world");
+
+        assert!(!store.contains(&object_id).unwrap());
+        assert!(matches!(
+            store.get(&object_id),
+            Err(ReadContentError::ObjectNotFound)
+        ));
+
+        store
+            .put(&object_id, ObjectType::File, b"hello world")
+            .unwrap();
+        assert!(store.contains(&object_id).unwrap());
+        assert_eq!(store.get(&object_id).unwrap(), b"hello world");
+        assert_eq!(
+            store.get_typed(&object_id).unwrap().0,
+            Some(ObjectType::File)
+        );
+        assert_eq!(store.list().unwrap(), vec![object_id]);
+    }
+
+    #[test]
+    fn overlay_object_store() {
+        let base = Arc::new(MemoryObjectStore::new());
+        let old = ObjectID::from_contents("old");
+        base.put(&old, ObjectType::File, b"old").unwrap();
+
+        let store = OverlayObjectStore::new(base.clone());
+        let new = ObjectID::from_contents("new");
+        store.put(&new, ObjectType::File, b"new").unwrap();
+
+        // both are read, and only the base holds what it had
+        assert_eq!(store.get(&old).unwrap(), b"old");
+        assert_eq!(store.get(&new).unwrap(), b"new");
+        let mut expected = vec![old, new];
+        expected.sort();
+        assert_eq!(store.list().unwrap(), expected);
+        assert!(!base.contains(&new).unwrap());
+    }
+}
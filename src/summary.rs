@@ -0,0 +1,228 @@
+//! Summaries of builds, kept as refs.
+//!
+//! `local build --summary` writes a tree with two entries, the root tree of
+//! the build as `root` and a JSON file `build.json` describing who built it,
+//! where and how long it took, and points a `build-<time>` ref at it. The
+//! ref keeps both reachable, so `mtl print-tree -r build-<time>:root` and
+//! `mtl cat-object build-<time>:build.json` work for as long as it exists.
+//!
+//! `local build --report <path>` writes a [`BuildReport`] to a file instead,
+//! for CI jobs to archive next to what they built.
+
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::builder::BuildCounts;
+use crate::filter::SkipReport;
+use crate::{audit, datetime, Context, Object, ObjectID, ObjectType};
+
+/// Prefix of the refs of build summaries.
+pub const REF_PREFIX: &str = "build-";
+/// Name of the metadata file in a summary tree.
+pub const METADATA_NAME: &str = "build.json";
+/// Name of the built root in a summary tree.
+pub const ROOT_NAME: &str = "root";
+
+/// Metadata of one build, stored as `build.json`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BuildSummary {
+    /// Seconds since the Unix epoch when the build finished
+    pub time: u64,
+    pub host: String,
+    pub user: String,
+    pub duration_secs: f64,
+    pub files: u64,
+    pub dirs: u64,
+    pub root: String,
+    pub mtl_version: String,
+}
+
+impl BuildSummary {
+    pub fn new(root: &ObjectID, counts: BuildCounts, duration_secs: f64) -> Self {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        BuildSummary {
+            time,
+            host: hostname(),
+            user: audit::current_user(),
+            duration_secs,
+            files: counts.files,
+            dirs: counts.dirs,
+            root: root.to_string(),
+            mtl_version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+
+    /// Name of the ref for this summary, like "build-20240102T030405Z".
+    /// ':' would read as a path in an object expression, so it is left out.
+    pub fn ref_name(&self) -> String {
+        format!("{}{}", REF_PREFIX, datetime::format_compact(self.time))
+    }
+
+    /// The names to try in turn for the ref of this summary:
+    /// [`BuildSummary::ref_name`], then with "-2", "-3" and so on for the
+    /// builds that finished within the same second.
+    pub fn ref_names(&self) -> impl Iterator<Item = String> {
+        let name = self.ref_name();
+        std::iter::once(name.clone()).chain((2..).map(move |n| format!("{}-{}", name, n)))
+    }
+
+    /// Point a new ref at the summary tree `tree_id`, never replacing the
+    /// ref of another build, and return its name.
+    pub fn write_ref(&self, ctx: &Context, tree_id: ObjectID) -> io::Result<String> {
+        for ref_name in self.ref_names() {
+            match ctx.create_object_ref(&ref_name, tree_id) {
+                Ok(()) => return Ok(ref_name),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        unreachable!("the ref names never run out")
+    }
+
+    /// Store the metadata and the summary tree, returning the tree ID for
+    /// the ref of this summary to point at.
+    pub fn write(&self, ctx: &Context) -> anyhow::Result<ObjectID> {
+        let mut metadata = serde_json::to_vec_pretty(self)?;
+        metadata.push(b'\n');
+        let metadata_id = ObjectID::from_contents(&metadata);
+        ctx.store().put(&metadata_id, ObjectType::File, &metadata)?;
+
+        let root: ObjectID = self.root.parse()?;
+        let tree_id = ctx.write_tree_contents(&[
+            Object::new_file(metadata_id, METADATA_NAME),
+            Object::new_tree(root, ROOT_NAME),
+        ])?;
+        Ok(tree_id)
+    }
+}
+
+/// What one build took in and left out, written by `local build --report`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BuildReport {
+    pub root: String,
+    /// The entries of the root tree
+    pub entries: Vec<ReportEntry>,
+    pub files: u64,
+    pub dirs: u64,
+    pub bytes_hashed: u64,
+    pub duration_secs: f64,
+    /// The entries skipped by the filters. A skipped directory stands for
+    /// everything below it.
+    pub skipped: Vec<ReportSkip>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReportEntry {
+    pub name: String,
+    pub kind: String,
+    pub id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReportSkip {
+    /// Relative path, with a trailing '/' for directories
+    pub path: String,
+    pub reason: String,
+}
+
+impl BuildReport {
+    pub fn new(
+        ctx: &Context,
+        root: &ObjectID,
+        counts: BuildCounts,
+        duration_secs: f64,
+        skipped: &SkipReport,
+    ) -> anyhow::Result<Self> {
+        let entries = ctx
+            .read_tree_contents(root)?
+            .into_iter()
+            .map(|object| ReportEntry {
+                name: object.file_path.to_string(),
+                kind: object.object_type.to_string(),
+                id: object.object_id.to_string(),
+            })
+            .collect();
+        let skipped = skipped
+            .entries()
+            .into_iter()
+            .map(|entry| ReportSkip {
+                path: match entry.is_dir {
+                    true => format!("{}/", entry.path),
+                    false => entry.path.to_string(),
+                },
+                reason: entry.reason.to_string(),
+            })
+            .collect();
+        Ok(BuildReport {
+            root: root.to_string(),
+            entries,
+            files: counts.files,
+            dirs: counts.dirs,
+            bytes_hashed: counts.bytes,
+            duration_secs,
+            skipped,
+        })
+    }
+}
+
+#[cfg(unix)]
+fn hostname() -> String {
+    let mut buf = [0u8; 256];
+    let ret = unsafe { libc::gethostname(buf.as_mut_ptr() as _, buf.len()) };
+    if ret != 0 {
+        return "unknown".to_string();
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+#[cfg(not(unix))]
+fn hostname() -> String {
+    std::env::var("COMPUTERNAME").unwrap_or_else(|_| "unknown".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BuildSummary;
+    use crate::builder::BuildCounts;
+    use crate::{Context, ObjectID};
+
+    #[test]
+    fn test_write_summary() {
+        let ctx = Context::in_memory();
+        let root = ctx.write_tree_contents::<crate::Object>(&[]).unwrap();
+
+        let mut summary = BuildSummary::new(
+            &root,
+            BuildCounts {
+                files: 3,
+                dirs: 1,
+                bytes: 10,
+            },
+            0.5,
+        );
+        summary.time = 1704164645;
+        assert_eq!(summary.ref_name(), "build-20240102T030405Z");
+        assert_eq!(
+            summary.ref_names().take(3).collect::<Vec<_>>(),
+            [
+                "build-20240102T030405Z",
+                "build-20240102T030405Z-2",
+                "build-20240102T030405Z-3"
+            ]
+        );
+
+        let tree_id = summary.write(&ctx).unwrap();
+        let entries = ctx.read_tree_contents(&tree_id).unwrap();
+        assert_eq!(entries[1].object_id, root);
+        let metadata = ctx.read_object(&entries[0].object_id).unwrap();
+        let read: BuildSummary = serde_json::from_slice(&metadata).unwrap();
+        assert_eq!(read, summary);
+        assert_eq!(ObjectID::from_contents(&metadata), entries[0].object_id);
+    }
+}
@@ -0,0 +1,757 @@
+//! Building and editing trees by path, walking and flattening them.
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Component, Path, PathBuf};
+use std::vec;
+
+use crate::{
+    tree_object, Context, Object, ObjectID, ObjectType, ParseError, ReadContentError, TreeError,
+};
+
+/// An entry of a flattened tree, with its path from the root.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Entry {
+    pub object_type: ObjectType,
+    pub object_id: ObjectID,
+    pub path: PathBuf,
+}
+
+enum Node {
+    File(ObjectID),
+    Tree {
+        // an existing tree to reuse when no children are given
+        object_id: Option<ObjectID>,
+        children: BTreeMap<OsString, Node>,
+    },
+}
+
+impl Node {
+    fn empty_tree() -> Self {
+        Node::Tree {
+            object_id: None,
+            children: BTreeMap::new(),
+        }
+    }
+
+    fn is_empty_tree(&self) -> bool {
+        matches!(self, Node::Tree { object_id: None, children } if children.is_empty())
+    }
+}
+
+/// Builds a tree hierarchy from entries given by their full path.
+///
+/// Intermediate trees are created as needed. A tree entry that has no
+/// entries of its own below it refers to an existing tree object; once
+/// anything is inserted below it, the tree is rebuilt from those entries.
+pub struct TreeBuilder {
+    root: Node,
+    strict: bool,
+}
+
+impl Default for TreeBuilder {
+    fn default() -> Self {
+        TreeBuilder {
+            root: Node::empty_tree(),
+            strict: false,
+        }
+    }
+}
+
+impl TreeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A builder where entries must not overlap: nothing can be inserted
+    /// below a tree given by its ID, in either order.
+    pub fn strict() -> Self {
+        TreeBuilder {
+            strict: true,
+            ..Self::default()
+        }
+    }
+
+    /// Add an entry. `"."` (or an empty path) is the root itself, which can
+    /// only be a tree.
+    pub fn insert<P: AsRef<Path>>(
+        &mut self,
+        object_type: ObjectType,
+        object_id: ObjectID,
+        path: P,
+    ) -> Result<(), TreeError> {
+        let path = path.as_ref();
+        let names = split_path(path)?;
+
+        let mut node = &mut self.root;
+        for name in names.iter() {
+            let Node::Tree {
+                object_id,
+                children,
+            } = node
+            else {
+                return Err(TreeError::Conflict(path.to_path_buf()));
+            };
+            if self.strict && object_id.is_some() {
+                return Err(TreeError::Conflict(path.to_path_buf()));
+            }
+            node = children
+                .entry(name.clone())
+                .or_insert_with(Node::empty_tree);
+        }
+
+        if object_type == ObjectType::File && !names.is_empty() && node.is_empty_tree() {
+            *node = Node::File(object_id);
+            return Ok(());
+        }
+        match (object_type, node) {
+            (
+                ObjectType::Tree,
+                Node::Tree {
+                    object_id: id @ None,
+                    children,
+                },
+            ) if !self.strict || children.is_empty() => {
+                *id = Some(object_id);
+                Ok(())
+            }
+            _ => Err(TreeError::Conflict(path.to_path_buf())),
+        }
+    }
+
+    /// Write all new trees and return the ID of the root tree.
+    pub fn write(&self, ctx: &Context) -> Result<ObjectID, TreeError> {
+        let mut objects = Vec::new();
+        let root_id = build_tree(ctx, &self.root, &mut objects)?;
+        ctx.write_objects(&objects)?;
+        Ok(root_id)
+    }
+}
+
+fn build_tree(
+    ctx: &Context,
+    node: &Node,
+    objects: &mut Vec<(ObjectID, Vec<u8>)>,
+) -> Result<ObjectID, TreeError> {
+    let Node::Tree {
+        object_id,
+        children,
+    } = node
+    else {
+        unreachable!("only trees are built");
+    };
+    if let (Some(object_id), true) = (object_id, children.is_empty()) {
+        if !ctx.store().contains(object_id)? {
+            return Err(TreeError::MissingTree(object_id.to_string()));
+        }
+        return Ok(*object_id);
+    }
+
+    let mut entries = Vec::with_capacity(children.len());
+    for (name, child) in children {
+        let entry = match child {
+            Node::File(object_id) => Object::new_file(*object_id, name),
+            Node::Tree { .. } => Object::new_tree(build_tree(ctx, child, objects)?, name),
+        };
+        entries.push(entry);
+    }
+    entries.sort();
+
+    let (object_id, contents) = tree_object(&entries)?;
+    objects.push((object_id, contents));
+    Ok(object_id)
+}
+
+enum EditNode {
+    File(ObjectID),
+    // a tree that is read only when something below it changes
+    Tree(ObjectID),
+    Edited(BTreeMap<OsString, EditNode>),
+}
+
+impl EditNode {
+    fn object_type(&self) -> ObjectType {
+        match self {
+            EditNode::File(_) => ObjectType::File,
+            _ => ObjectType::Tree,
+        }
+    }
+}
+
+/// Changes entries of an existing tree by path.
+///
+/// Only the trees along the changed paths are read and rewritten; every
+/// other subtree keeps its ID.
+pub struct TreeEditor<'a> {
+    ctx: &'a Context,
+    root: EditNode,
+}
+
+impl<'a> TreeEditor<'a> {
+    pub fn new(ctx: &'a Context, base: ObjectID) -> Self {
+        TreeEditor {
+            ctx,
+            root: EditNode::Tree(base),
+        }
+    }
+
+    /// Add an entry that must not exist yet, creating parent trees as needed.
+    pub fn add<P: AsRef<Path>>(
+        &mut self,
+        object_type: ObjectType,
+        object_id: ObjectID,
+        path: P,
+    ) -> Result<(), TreeError> {
+        let path = path.as_ref();
+        let (children, name) = self.parent(path, true)?;
+        if children.contains_key(&name) {
+            return Err(TreeError::Conflict(path.to_path_buf()));
+        }
+        children.insert(name, new_edit_node(object_type, object_id));
+        Ok(())
+    }
+
+    /// Replace an existing entry of the same type.
+    pub fn set<P: AsRef<Path>>(
+        &mut self,
+        object_type: ObjectType,
+        object_id: ObjectID,
+        path: P,
+    ) -> Result<(), TreeError> {
+        let path = path.as_ref();
+        let (children, name) = self.parent(path, false)?;
+        match children.get_mut(&name) {
+            Some(node) if node.object_type() == object_type => {
+                *node = new_edit_node(object_type, object_id);
+                Ok(())
+            }
+            Some(_) => Err(TreeError::Conflict(path.to_path_buf())),
+            None => Err(TreeError::NotFound(path.to_path_buf())),
+        }
+    }
+
+    /// Remove an existing entry, which must be of the given type.
+    pub fn remove<P: AsRef<Path>>(
+        &mut self,
+        object_type: ObjectType,
+        path: P,
+    ) -> Result<(), TreeError> {
+        let path = path.as_ref();
+        let (children, name) = self.parent(path, false)?;
+        match children.get(&name) {
+            Some(node) if node.object_type() == object_type => {
+                children.remove(&name);
+                Ok(())
+            }
+            Some(_) => Err(TreeError::Conflict(path.to_path_buf())),
+            None => Err(TreeError::NotFound(path.to_path_buf())),
+        }
+    }
+
+    /// Write the changed trees and return the ID of the root tree.
+    pub fn write(&self) -> Result<ObjectID, TreeError> {
+        let mut objects = Vec::new();
+        let root_id = write_edited(self.ctx, &self.root, &mut objects)?;
+        self.ctx.write_objects(&objects)?;
+        Ok(root_id)
+    }
+
+    // entries of the tree containing `path`, and the name of `path` in it
+    fn parent(
+        &mut self,
+        path: &Path,
+        create: bool,
+    ) -> Result<(&mut BTreeMap<OsString, EditNode>, OsString), TreeError> {
+        let mut names = split_path(path)?;
+        let Some(name) = names.pop() else {
+            return Err(TreeError::InvalidPath(path.to_path_buf()));
+        };
+
+        let mut node = &mut self.root;
+        for dir in names {
+            let children = load(self.ctx, node, path)?;
+            node = match create {
+                true => children
+                    .entry(dir)
+                    .or_insert_with(|| EditNode::Edited(BTreeMap::new())),
+                false => children
+                    .get_mut(&dir)
+                    .ok_or_else(|| TreeError::NotFound(path.to_path_buf()))?,
+            };
+        }
+        Ok((load(self.ctx, node, path)?, name))
+    }
+}
+
+fn new_edit_node(object_type: ObjectType, object_id: ObjectID) -> EditNode {
+    match object_type {
+        ObjectType::Tree => EditNode::Tree(object_id),
+        ObjectType::File => EditNode::File(object_id),
+    }
+}
+
+// read the entries of a tree so that they can be changed
+fn load<'n>(
+    ctx: &Context,
+    node: &'n mut EditNode,
+    path: &Path,
+) -> Result<&'n mut BTreeMap<OsString, EditNode>, TreeError> {
+    if let EditNode::Tree(object_id) = node {
+        let mut children = BTreeMap::new();
+        for object in ctx.read_tree_contents(object_id)? {
+            let name = object.file_path.as_os_str().to_os_string();
+            children.insert(name, new_edit_node(object.object_type, object.object_id));
+        }
+        *node = EditNode::Edited(children);
+    }
+    match node {
+        EditNode::Edited(children) => Ok(children),
+        _ => Err(TreeError::Conflict(path.to_path_buf())),
+    }
+}
+
+fn write_edited(
+    ctx: &Context,
+    node: &EditNode,
+    objects: &mut Vec<(ObjectID, Vec<u8>)>,
+) -> Result<ObjectID, TreeError> {
+    let children = match node {
+        EditNode::File(_) => unreachable!("only trees are written"),
+        EditNode::Tree(object_id) => {
+            if ctx.store().contains(object_id)? {
+                return Ok(*object_id);
+            }
+            // empty trees can always be recreated
+            let (empty_id, empty) = tree_object::<Object>(&[])?;
+            if *object_id != empty_id {
+                return Err(TreeError::MissingTree(object_id.to_string()));
+            }
+            objects.push((empty_id, empty));
+            return Ok(empty_id);
+        }
+        EditNode::Edited(children) => children,
+    };
+
+    let mut entries = Vec::with_capacity(children.len());
+    for (name, child) in children {
+        let entry = match child {
+            EditNode::File(object_id) => Object::new_file(*object_id, name),
+            _ => Object::new_tree(write_edited(ctx, child, objects)?, name),
+        };
+        entries.push(entry);
+    }
+    entries.sort();
+
+    let (object_id, contents) = tree_object(&entries)?;
+    objects.push((object_id, contents));
+    Ok(object_id)
+}
+
+// split a relative path into names, rejecting anything that can't be stored
+// in a tree
+fn split_path(path: &Path) -> Result<Vec<OsString>, TreeError> {
+    let invalid = || TreeError::InvalidPath(path.to_path_buf());
+
+    let mut names = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir if names.is_empty() => continue,
+            Component::Normal(name) => {
+                let bytes = name.as_encoded_bytes();
+                if bytes.contains(&b'\t') || bytes.contains(&b'\n') {
+                    return Err(invalid());
+                }
+                names.push(name.to_os_string());
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(names)
+}
+
+/// List the leaves of a tree: its files and its empty trees.
+///
+/// Inserting the entries into a [`TreeBuilder`] gives back the same tree.
+pub fn flatten(ctx: &Context, object_id: &ObjectID) -> Result<Vec<Entry>, ReadContentError> {
+    let mut entries = Vec::new();
+    inner_flatten(ctx, Path::new(""), object_id, &mut entries)?;
+    Ok(entries)
+}
+
+fn inner_flatten(
+    ctx: &Context,
+    parent: &Path,
+    object_id: &ObjectID,
+    entries: &mut Vec<Entry>,
+) -> Result<(), ReadContentError> {
+    let objects = ctx.read_tree_contents(object_id)?;
+    if objects.is_empty() && !parent.as_os_str().is_empty() {
+        entries.push(Entry {
+            object_type: ObjectType::Tree,
+            object_id: *object_id,
+            path: parent.to_path_buf(),
+        });
+    }
+
+    for object in objects {
+        let path = parent.join(&object.file_path);
+        match object.object_type {
+            ObjectType::Tree => inner_flatten(ctx, &path, &object.object_id, entries)?,
+            ObjectType::File => entries.push(Entry {
+                object_type: ObjectType::File,
+                object_id: object.object_id,
+                path,
+            }),
+        }
+    }
+    Ok(())
+}
+
+/// What [`Context::walk_tree`] does after visiting an entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalkControl {
+    Continue,
+    /// Don't descend into this tree; the same as `Continue` for a file
+    SkipChildren,
+    /// End the walk
+    Stop,
+}
+
+/// Depth-first iterator over the entries below a tree, each tree before its
+/// entries, in tree order. See [`Context::tree_iter`].
+///
+/// A tree is read when the walk descends into it, so trees skipped with
+/// [`TreeIter::skip_children`] or left after stopping early aren't read at
+/// all. An error ends the walk.
+pub struct TreeIter<'a> {
+    ctx: &'a Context,
+    // the last tree returned, to be read unless its children are skipped
+    pending: Option<(PathBuf, ObjectID)>,
+    // the entries left of each tree being walked, with its path
+    stack: Vec<(PathBuf, vec::IntoIter<Object>)>,
+}
+
+impl<'a> TreeIter<'a> {
+    pub fn new(ctx: &'a Context, object_id: &ObjectID) -> Self {
+        TreeIter {
+            ctx,
+            pending: Some((PathBuf::new(), *object_id)),
+            stack: Vec::new(),
+        }
+    }
+
+    /// Don't descend into the tree returned last.
+    pub fn skip_children(&mut self) {
+        self.pending = None;
+    }
+
+    /// Depth of the entry returned last; the entries of the root are at 1.
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+}
+
+impl Iterator for TreeIter<'_> {
+    type Item = Result<Entry, ReadContentError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some((path, object_id)) = self.pending.take() {
+            match self.ctx.read_tree_contents(&object_id) {
+                Ok(objects) => self.stack.push((path, objects.into_iter())),
+                Err(e) => {
+                    self.stack.clear();
+                    return Some(Err(e));
+                }
+            }
+        }
+        loop {
+            let (parent, objects) = self.stack.last_mut()?;
+            let Some(object) = objects.next() else {
+                self.stack.pop();
+                continue;
+            };
+            let path = parent.join(&object.file_path);
+            if object.is_tree() {
+                self.pending = Some((path.clone(), object.object_id));
+            }
+            return Some(Ok(Entry {
+                object_type: object.object_type,
+                object_id: object.object_id,
+                path,
+            }));
+        }
+    }
+}
+
+/// Escape a path for a line-based format: `\`, tab and newline become
+/// `\\`, `\t` and `\n`.
+pub fn escape_path(path: &str) -> String {
+    let mut escaped = String::with_capacity(path.len());
+    for c in path.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Reverse of [`escape_path`].
+pub fn unescape_path(escaped: &str) -> Result<String, ParseError> {
+    let mut path = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            path.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => path.push('\\'),
+            Some('t') => path.push('\t'),
+            Some('n') => path.push('\n'),
+            _ => return Err(ParseError::InvalidToken(escaped.to_string())),
+        }
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{escape_path, flatten, unescape_path, TreeBuilder, TreeEditor, WalkControl};
+    use crate::{Context, Object, ObjectID, ObjectType, TreeError};
+
+    #[test]
+    fn test_tree_builder() {
+        let ctx = Context::in_memory();
+        let file = ObjectID::from_contents("hello world");
+
+        let sub = ctx
+            .write_tree_contents(&[Object::new_file(file, "c")])
+            .unwrap();
+        let expected = ctx
+            .write_tree_contents(&[Object::new_file(file, "a"), Object::new_tree(sub, "b")])
+            .unwrap();
+
+        let mut builder = TreeBuilder::new();
+        builder.insert(ObjectType::File, file, "b/c").unwrap();
+        builder.insert(ObjectType::File, file, "a").unwrap();
+        assert_eq!(builder.write(&ctx).unwrap(), expected);
+
+        // an existing tree is reused as is
+        let mut builder = TreeBuilder::new();
+        builder.insert(ObjectType::Tree, sub, "b/").unwrap();
+        builder.insert(ObjectType::File, file, "./a").unwrap();
+        assert_eq!(builder.write(&ctx).unwrap(), expected);
+
+        let mut builder = TreeBuilder::new();
+        builder.insert(ObjectType::Tree, expected, ".").unwrap();
+        assert_eq!(builder.write(&ctx).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_tree_builder_errors() {
+        let ctx = Context::in_memory();
+        let file = ObjectID::from_contents("hello world");
+
+        let mut builder = TreeBuilder::new();
+        builder.insert(ObjectType::File, file, "a").unwrap();
+        assert!(matches!(
+            builder.insert(ObjectType::File, file, "a"),
+            Err(TreeError::Conflict(_))
+        ));
+        assert!(matches!(
+            builder.insert(ObjectType::File, file, "a/b"),
+            Err(TreeError::Conflict(_))
+        ));
+        assert!(matches!(
+            builder.insert(ObjectType::File, file, "."),
+            Err(TreeError::Conflict(_))
+        ));
+        for path in ["../a", "/a", "a/../b", "a\tb"] {
+            assert!(matches!(
+                builder.insert(ObjectType::File, file, path),
+                Err(TreeError::InvalidPath(_))
+            ));
+        }
+
+        // overlapping entries
+        let mut builder = TreeBuilder::strict();
+        builder.insert(ObjectType::Tree, file, "a").unwrap();
+        assert!(matches!(
+            builder.insert(ObjectType::File, file, "a/b"),
+            Err(TreeError::Conflict(_))
+        ));
+        builder.insert(ObjectType::File, file, "b/c").unwrap();
+        assert!(matches!(
+            builder.insert(ObjectType::Tree, file, "b"),
+            Err(TreeError::Conflict(_))
+        ));
+
+        let mut builder = TreeBuilder::new();
+        builder.insert(ObjectType::Tree, file, "a").unwrap();
+        assert!(matches!(
+            builder.write(&ctx),
+            Err(TreeError::MissingTree(_))
+        ));
+    }
+
+    #[test]
+    fn test_flatten() {
+        let ctx = Context::in_memory();
+        let file = ObjectID::from_contents("hello world");
+
+        let empty = ctx.write_tree_contents::<Object>(&[]).unwrap();
+        let sub = ctx
+            .write_tree_contents(&[Object::new_file(file, "c"), Object::new_tree(empty, "d")])
+            .unwrap();
+        let root = ctx
+            .write_tree_contents(&[Object::new_file(file, "a"), Object::new_tree(sub, "b")])
+            .unwrap();
+
+        let entries = flatten(&ctx, &root).unwrap();
+        let paths: Vec<_> = entries.iter().map(|e| e.path.clone()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("a"),
+                PathBuf::from("b/c"),
+                PathBuf::from("b/d")
+            ]
+        );
+        assert_eq!(entries[2].object_type, ObjectType::Tree);
+
+        let mut builder = TreeBuilder::new();
+        for entry in entries {
+            builder
+                .insert(entry.object_type, entry.object_id, entry.path)
+                .unwrap();
+        }
+        assert_eq!(builder.write(&ctx).unwrap(), root);
+    }
+
+    #[test]
+    fn test_escape_path() {
+        for path in ["a/b", "a\tb", "a\\b\nc\\", ""] {
+            assert_eq!(unescape_path(&escape_path(path)).unwrap(), path);
+        }
+        assert_eq!(escape_path("a\tb\\"), "a\\tb\\\\");
+        assert!(unescape_path("a\\").is_err());
+        assert!(unescape_path("a\\x").is_err());
+    }
+
+    #[test]
+    fn test_tree_editor() {
+        let ctx = Context::in_memory();
+        let file = ObjectID::from_contents("hello world");
+        let other = ObjectID::from_contents("other");
+
+        let sub = ctx
+            .write_tree_contents(&[Object::new_file(file, "c")])
+            .unwrap();
+        let untouched = ctx
+            .write_tree_contents(&[Object::new_file(file, "e")])
+            .unwrap();
+        let base = ctx
+            .write_tree_contents(&[
+                Object::new_file(file, "a"),
+                Object::new_tree(sub, "b"),
+                Object::new_tree(untouched, "d"),
+            ])
+            .unwrap();
+
+        let mut editor = TreeEditor::new(&ctx, base);
+        editor.set(ObjectType::File, other, "b/c").unwrap();
+        editor.remove(ObjectType::File, "a").unwrap();
+        editor.add(ObjectType::File, file, "new/deep/f").unwrap();
+        let edited = editor.write().unwrap();
+
+        let mut builder = TreeBuilder::new();
+        builder.insert(ObjectType::File, other, "b/c").unwrap();
+        builder.insert(ObjectType::Tree, untouched, "d").unwrap();
+        builder
+            .insert(ObjectType::File, file, "new/deep/f")
+            .unwrap();
+        assert_eq!(edited, builder.write(&ctx).unwrap());
+
+        let mut editor = TreeEditor::new(&ctx, base);
+        assert!(matches!(
+            editor.add(ObjectType::File, file, "a"),
+            Err(TreeError::Conflict(_))
+        ));
+        assert!(matches!(
+            editor.add(ObjectType::File, file, "a/x"),
+            Err(TreeError::Conflict(_))
+        ));
+        assert!(matches!(
+            editor.set(ObjectType::Tree, sub, "a"),
+            Err(TreeError::Conflict(_))
+        ));
+        assert!(matches!(
+            editor.remove(ObjectType::File, "x/y"),
+            Err(TreeError::NotFound(_))
+        ));
+        assert!(matches!(
+            editor.remove(ObjectType::Tree, "."),
+            Err(TreeError::InvalidPath(_))
+        ));
+        assert_eq!(editor.write().unwrap(), base);
+    }
+
+    #[test]
+    fn test_walk_tree() {
+        let ctx = Context::in_memory();
+        let file = ObjectID::from_contents("file");
+        let inner = ctx
+            .write_tree_contents(&[Object::new_file(file, "f")])
+            .unwrap();
+        let skipped = ctx
+            .write_tree_contents(&[Object::new_tree(inner, "c")])
+            .unwrap();
+        let root = ctx
+            .write_tree_contents(&[
+                Object::new_tree(inner, "a"),
+                Object::new_tree(skipped, "b"),
+                Object::new_file(file, "z"),
+            ])
+            .unwrap();
+
+        let mut visited = Vec::new();
+        ctx.walk_tree(&root, |path, object| {
+            visited.push(path.to_string());
+            match object.file_path.as_os_str().to_str() {
+                Some("b") => WalkControl::SkipChildren,
+                Some("z") => WalkControl::Stop,
+                _ => WalkControl::Continue,
+            }
+        })
+        .unwrap();
+        assert_eq!(visited, ["a", "a/f", "b", "z"]);
+
+        let mut iter = ctx.tree_iter(&root);
+        let mut depths = Vec::new();
+        while let Some(entry) = iter.next() {
+            depths.push((entry.unwrap().path, iter.depth()));
+        }
+        assert_eq!(
+            depths,
+            [
+                (PathBuf::from("a"), 1),
+                (PathBuf::from("a/f"), 2),
+                (PathBuf::from("b"), 1),
+                (PathBuf::from("b/c"), 2),
+                (PathBuf::from("b/c/f"), 3),
+                (PathBuf::from("z"), 1),
+            ]
+        );
+
+        // the root is read lazily, and its error ends the walk
+        let missing = ObjectID::from_contents("missing");
+        assert!(ctx
+            .walk_tree(&missing, |_, _| WalkControl::Continue)
+            .is_err());
+    }
+}
@@ -0,0 +1,196 @@
+//! Extended attributes folded into file IDs.
+//!
+//! With `local build --xattr`, the selected attributes of a file, such as
+//! `security.selinux` or the POSIX ACLs in `system.posix_acl_access`, count
+//! towards its ID, so that replicas whose permissions or labels drifted
+//! apart differ even when their contents do not. A file without any of the
+//! selected attributes keeps the ID of its contents.
+//!
+//! Only files are fingerprinted; the attributes of directories are not
+//! read. A tree built this way no longer holds the IDs of the contents of
+//! its files, so `export-git`, which checks files against them, cannot
+//! export it.
+
+use std::io;
+use std::path::Path;
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+
+use crate::{FilterError, ObjectID};
+
+/// Attribute holding the POSIX access ACL of a file. The default ACL only
+/// exists on directories, which are not fingerprinted.
+pub const ACL_XATTR: &str = "system.posix_acl_access";
+
+/// Names of the extended attributes to include, as glob patterns.
+#[derive(Debug, Clone)]
+pub struct XattrSelector {
+    patterns: GlobSet,
+}
+
+impl XattrSelector {
+    pub fn new<S: AsRef<str>>(patterns: &[S]) -> Result<Self, FilterError> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            builder.add(Glob::new(pattern.as_ref())?);
+        }
+        Ok(XattrSelector {
+            patterns: builder.build()?,
+        })
+    }
+
+    pub fn matches(&self, name: &[u8]) -> bool {
+        self.patterns
+            .is_match(String::from_utf8_lossy(name).as_ref())
+    }
+
+    /// The selected attributes of `path` as (name, value) pairs, sorted by
+    /// name. Symbolic links are not followed.
+    pub fn read(&self, path: &Path) -> io::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut attrs = Vec::new();
+        for name in list(path)? {
+            if !self.matches(&name) {
+                continue;
+            }
+            // the attribute may be gone since it was listed
+            if let Some(value) = get(path, &name)? {
+                attrs.push((name, value));
+            }
+        }
+        attrs.sort();
+        Ok(attrs)
+    }
+}
+
+/// ID of a file with the ID `object_id` of its contents and the attributes
+/// `attrs`.
+pub fn fingerprint(object_id: ObjectID, attrs: &[(Vec<u8>, Vec<u8>)]) -> ObjectID {
+    if attrs.is_empty() {
+        return object_id;
+    }
+
+    let mut buf = object_id.to_string().into_bytes();
+    for (name, value) in attrs {
+        buf.push(b'\n');
+        buf.extend_from_slice(name);
+        buf.push(0);
+        buf.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        buf.extend_from_slice(value);
+    }
+    ObjectID::from_contents(buf)
+}
+
+#[cfg(target_os = "linux")]
+fn list(path: &Path) -> io::Result<Vec<Vec<u8>>> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    loop {
+        let len = unsafe { libc::llistxattr(path.as_ptr(), std::ptr::null_mut(), 0) };
+        if len < 0 {
+            let e = io::Error::last_os_error();
+            // filesystems without extended attributes
+            if e.raw_os_error() == Some(libc::ENOTSUP) {
+                return Ok(Vec::new());
+            }
+            return Err(e);
+        }
+
+        let mut buf = vec![0u8; len as usize];
+        let len = unsafe { libc::llistxattr(path.as_ptr(), buf.as_mut_ptr() as _, buf.len()) };
+        if len < 0 {
+            let e = io::Error::last_os_error();
+            // an attribute was added since the size was taken
+            if e.raw_os_error() == Some(libc::ERANGE) {
+                continue;
+            }
+            return Err(e);
+        }
+        buf.truncate(len as usize);
+        return Ok(buf
+            .split(|&b| b == 0)
+            .filter(|name| !name.is_empty())
+            .map(<[u8]>::to_vec)
+            .collect());
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn get(path: &Path, name: &[u8]) -> io::Result<Option<Vec<u8>>> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let name = CString::new(name)?;
+    loop {
+        let len = unsafe { libc::lgetxattr(path.as_ptr(), name.as_ptr(), std::ptr::null_mut(), 0) };
+        if len < 0 {
+            let e = io::Error::last_os_error();
+            if e.raw_os_error() == Some(libc::ENODATA) {
+                return Ok(None);
+            }
+            return Err(e);
+        }
+
+        let mut buf = vec![0u8; len as usize];
+        let len = unsafe {
+            libc::lgetxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                buf.as_mut_ptr() as _,
+                buf.len(),
+            )
+        };
+        if len < 0 {
+            let e = io::Error::last_os_error();
+            match e.raw_os_error() {
+                Some(libc::ERANGE) => continue,
+                Some(libc::ENODATA) => return Ok(None),
+                _ => return Err(e),
+            }
+        }
+        buf.truncate(len as usize);
+        return Ok(Some(buf));
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn list(_path: &Path) -> io::Result<Vec<Vec<u8>>> {
+    Ok(Vec::new())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn get(_path: &Path, _name: &[u8]) -> io::Result<Option<Vec<u8>>> {
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fingerprint, XattrSelector};
+    use crate::ObjectID;
+
+    #[test]
+    fn test_xattr_selector() {
+        let selector = XattrSelector::new(&["user.*", "security.selinux"]).unwrap();
+        assert!(selector.matches(b"user.checksum"));
+        assert!(selector.matches(b"security.selinux"));
+        assert!(!selector.matches(b"security.capability"));
+        assert!(!selector.matches(b"trusted.user.x"));
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let object_id = ObjectID::from_contents("hello");
+        assert_eq!(fingerprint(object_id, &[]), object_id);
+
+        let attrs = [(b"user.a".to_vec(), b"1".to_vec())];
+        let with_attrs = fingerprint(object_id, &attrs);
+        assert_ne!(with_attrs, object_id);
+        assert_eq!(fingerprint(object_id, &attrs), with_attrs);
+        assert_ne!(
+            fingerprint(object_id, &[(b"user.a".to_vec(), b"2".to_vec())]),
+            with_attrs
+        );
+    }
+}
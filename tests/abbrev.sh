@@ -0,0 +1,32 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+$MTL local build > /dev/null
+$MTL ref save old > /dev/null
+echo changed > file1
+$MTL local build > /dev/null
+
+full_tree=$($MTL print-tree -r old)
+full_diff=$($MTL diff old HEAD || true)
+
+$MTL config set core.abbrev 7
+$MTL print-tree -r old | head -1 | grep -q "^tree 99f9d65	\.$"
+diff -u <($MTL print-tree -r old --full-ids) <(echo "$full_tree")
+$MTL diff old HEAD | grep -q "99f9d65/[0-9a-f]\{7\}	\.$"
+diff -u <($MTL diff --full-ids old HEAD) <(echo "$full_diff")
+$MTL audit show | tail -1 | grep -q " build HEAD 99f9d65 -> [0-9a-f]\{7\}$"
+
+# JSON output keeps the IDs whole for scripts
+$MTL --format json print-tree -r old | head -1 | grep -q '"id":"99f9d6592fc5edec"'
+
+# the abbreviations still resolve
+$MTL rev-parse 99f9d65 | grep -q "^99f9d6592fc5edec$"
+
+for width in 3 17; do
+  if $MTL config set core.abbrev $width 2>/dev/null; then
+    false
+  fi
+done
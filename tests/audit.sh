@@ -0,0 +1,34 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+export USER=tester
+
+$MTL local build >/dev/null
+$MTL local build --hidden >/dev/null
+$MTL ref save root HEAD@{1} >/dev/null
+$MTL ref delete root >/dev/null
+$MTL gc >/dev/null
+$MTL gc --dry >/dev/null
+$MTL pack >/dev/null
+
+diff -u <($MTL audit show | cut -d' ' -f2-) <(cat <<EOF
+tester build HEAD 99f9d6592fc5edec
+tester build HEAD 99f9d6592fc5edec -> 6b1d722afb0c117d
+tester ref-save root 99f9d6592fc5edec
+tester ref-delete root 99f9d6592fc5edec -> (deleted)
+tester gc (2 objects)
+tester pack (4 objects)
+EOF
+)
+
+diff -u <($MTL audit show -n 1 --json | sed 's/"time":[0-9]*,//') <(echo '{"user":"tester","operation":"pack","objects":4}')
+$MTL audit show | head -1 | grep -Eq "^[0-9]{4}-[0-9]{2}-[0-9]{2}T[0-9]{2}:[0-9]{2}:[0-9]{2}Z "
+
+time=$($MTL audit show -n 1 --json | sed 's/.*"time":\([0-9]*\),.*/\1/')
+$MTL audit show -n 1 --date unix | grep -q "^$time tester pack "
+$MTL audit show -n 1 --date relative | grep -Eq "^[0-9]+ seconds? ago tester pack "
+TZ=UTC $MTL audit show -n 1 --date local | grep -Eq "^[0-9-]{10}T[0-9:]{8}Z tester pack "
+TZ=JST-9 $MTL audit show -n 1 --date local | grep -Eq "^[0-9-]{10}T[0-9:]{8}\+09:00 tester pack "
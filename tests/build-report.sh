@@ -0,0 +1,30 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+report=$(mktemp)
+echo $report >> $DROP_LIST
+
+$MTL local build --exclude 'dir2/**' --max-file-size 10 --report $report > /dev/null
+root=$($MTL rev-parse HEAD)
+python3 - $report $root $($MTL rev-parse HEAD:z1) <<'EOF'
+import json, sys
+
+report = json.load(open(sys.argv[1]))
+assert report["root"] == sys.argv[2]
+entries = {entry["name"]: entry for entry in report["entries"]}
+assert entries["z1"] == {"name": "z1", "kind": "tree", "id": sys.argv[3]}
+assert "dir2" not in entries
+assert report["files"] > 0 and report["dirs"] > 0
+assert report["bytes_hashed"] > 0
+assert report["duration_secs"] >= 0
+skipped = {entry["path"]: entry["reason"] for entry in report["skipped"]}
+assert skipped["dir2/"] == "excluded"
+assert skipped["README"] == "too-large"
+EOF
+
+if $MTL local build --root . --report $report 2>/dev/null; then
+  false
+fi
@@ -0,0 +1,41 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+# no summary unless asked for
+$MTL local build > /dev/null
+diff -u <($MTL ref list) /dev/null
+
+output=$($MTL local build --summary)
+echo "$output" | head -1 | grep -q "^Written HEAD: 99f9d6592fc5edec$"
+name=$(echo "$output" | tail -1 | sed -n 's/^Written \(build-[0-9]\{8\}T[0-9]\{6\}Z\): [0-9a-f]\{16\}$/\1/p')
+[ -n "$name" ]
+diff -u <($MTL ref list | awk '{print $1}') <(echo $name)
+
+# the summary tree holds the root and its metadata
+diff -u <($MTL rev-parse $name:root) <(echo 99f9d6592fc5edec)
+metadata=$($MTL cat-object $name:build.json)
+echo "$metadata" | grep -q '"root": "99f9d6592fc5edec"'
+echo "$metadata" | grep -q "\"host\": \"$(hostname)\""
+echo "$metadata" | grep -q '"files": [0-9]*,'
+echo "$metadata" | grep -q '"mtl_version": "'
+$MTL audit show | tail -1 | grep -q " build-summary $name "
+
+# builds within the same second do not replace each other's summary
+for i in 1 2 3; do
+  $MTL local build --summary --no-write-head > /dev/null &
+done
+wait
+[ $($MTL ref list | grep -c "^build-") -eq 4 ]
+$MTL ref list | awk '{print $1}' | grep -v "^$name$" | while read other; do
+  $MTL ref delete $other > /dev/null
+done
+
+# the ref keeps the root alive once HEAD moves on
+echo changed > README
+$MTL local build > /dev/null
+$MTL gc > /dev/null
+$MTL print-tree -r $name:root > /dev/null
+diff -u <($MTL cat-object $name:build.json) <(echo "$metadata")
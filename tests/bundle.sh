@@ -0,0 +1,75 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+top=$(pwd)
+cd $(setup_new case1)
+
+$MTL local build >/dev/null
+$MTL ref save snap >/dev/null
+$MTL local build --hidden >/dev/null
+$MTL plumbing hash-object -w README >/dev/null
+bundle=$(pwd)/snap.bundle
+$MTL bundle create $bundle snap HEAD >/dev/null
+if $MTL bundle create $bundle snap 2>/dev/null; then
+  false
+fi
+$MTL print-tree > head.tree
+$MTL print-tree -r snap > snap.tree
+origin=$(pwd)
+
+cd $top
+cd $(setup_new case1)
+
+# objects and named refs are imported, HEAD is not
+diff -u <($MTL bundle unbundle $bundle) <(echo "snap	99f9d6592fc5edec")
+diff -u <($MTL print-tree -r snap) $origin/snap.tree
+diff -u <($MTL print-tree -r 6b1d722afb0c117d) $origin/head.tree
+[ ! -e .mtl/HEAD ]
+diff -u <($MTL cat-object d447b1ea40e6988b) README
+# with the kind they were stored with
+diff -u <($MTL cat-object -t d447b1ea40e6988b) <(echo file)
+diff -u <($MTL cat-object -t 99f9d6592fc5edec) <(echo tree)
+
+# again is a no-op, a diverged ref needs --force
+diff -u <($MTL bundle unbundle $bundle) /dev/null
+$MTL ref save snap 6b1d722afb0c117d >/dev/null
+if $MTL bundle unbundle $bundle 2>/dev/null; then
+  false
+fi
+$MTL bundle unbundle --force $bundle >/dev/null
+diff -u <($MTL rev-parse snap) <(echo 99f9d6592fc5edec)
+
+# a file that is not a bundle is left as it is
+echo "precious" > precious.txt
+if $MTL bundle unbundle precious.txt 2>/dev/null; then
+  false
+fi
+diff -u precious.txt <(echo "precious")
+: > empty.txt
+if $MTL bundle unbundle empty.txt 2>/dev/null; then
+  false
+fi
+[ ! -s empty.txt ]
+
+# a ref name that would escape the refs directory is rejected before
+# anything is written
+cd $origin
+$MTL ref save xxescape >/dev/null
+$MTL bundle create $origin/escape.bundle xxescape >/dev/null
+python3 -c "
+import sys
+data = open(sys.argv[1], 'rb').read()
+open(sys.argv[2], 'wb').write(data.replace(b'xxescape', b'../escap'))
+" $origin/escape.bundle $origin/crafted.bundle
+cd $top
+cd $(setup_new case1)
+if $MTL bundle unbundle $origin/crafted.bundle 2>/dev/null; then
+  false
+fi
+[ ! -e .mtl/escap ]
+[ ! -e .mtl/refs ]
+if $MTL ref save ../escap 2>/dev/null; then
+  false
+fi
+[ ! -e .mtl/escap ]
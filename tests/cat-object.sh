@@ -6,4 +6,29 @@ cd $(setup_new case1)
 
 $MTL local build >/dev/null
 
-diff -u <($MTL cat-object 99f9d6592fc5edec) <(cat .mtl/objects/99/f9d6592fc5edec)
\ No newline at end of file
+diff -u <($MTL cat-object 99f9d6592fc5edec) <(loose_object .mtl/objects/99/f9d6592fc5edec)
+
+# objects are stored with a header naming their kind
+head -c 4 .mtl/objects/99/f9d6592fc5edec | cmp - <(printf '\0mtl')
+diff -u <($MTL cat-object -t 99f9d6592fc5edec) <(echo tree)
+diff -u <($MTL cat-object --type $($MTL plumbing hash-object -w README)) <(echo file)
+
+# objects written without a header are still read, with an unknown kind
+object=.mtl/objects/99/f9d6592fc5edec
+loose_object $object > legacy
+mv legacy $object
+diff -u <($MTL cat-object -t HEAD) <(echo unknown)
+$MTL print-tree > /dev/null
+$MTL pack
+diff -u <($MTL cat-object -t HEAD) <(echo unknown)
+diff -u <($MTL cat-object -t HEAD:dir1) <(echo tree)
+$MTL print-tree > /dev/null
+
+# a header from a newer format is an error, not a legacy object
+$MTL local build > /dev/null
+printf 'dummy' > dummy
+$MTL local build > /dev/null
+object=$(find .mtl/objects -type f | head -1)
+printf '\0mtl\001\011' | dd of=$object conv=notrunc status=none
+id=$(echo $object | sed 's|.mtl/objects/||; s|/||')
+$MTL cat-object $id 2>&1 | grep -q "object $id is corrupt: object format version 9 is not supported"
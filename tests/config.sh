@@ -0,0 +1,66 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+export XDG_CONFIG_HOME=$(mktemp -d)
+echo $XDG_CONFIG_HOME >> $DROP_LIST
+
+# the user's file, with the repository's over it
+$MTL config set --user gc.grace 7d
+$MTL config set --user core.wait true
+diff -u <($MTL config get gc.grace) <(echo 7d)
+$MTL config set gc.grace 1h
+diff -u <($MTL config get gc.grace) <(echo 1h)
+diff -u <($MTL config get core.wait) <(echo true)
+diff -u .mtl/config <(printf '[gc]\ngrace = "1h"\n')
+diff -u $XDG_CONFIG_HOME/mtl/config <(printf '[core]\nwait = true\n\n[gc]\ngrace = "7d"\n')
+
+for args in "core.jobs 0" "core.jobs many" "gc.grace soon" "core.color true"; do
+  if $MTL config set $args 2>/dev/null; then
+    false
+  fi
+done
+if $MTL config get core.jobs 2>/dev/null; then
+  false
+fi
+
+# build.hidden changes the default, and the command line still wins
+$MTL config set build.hidden true
+if $MTL local build | grep -q "99f9d6592fc5edec"; then
+  false
+fi
+$MTL local build --no-hidden | grep -q "99f9d6592fc5edec"
+$MTL config set build.hidden false
+$MTL local build | grep -q "99f9d6592fc5edec"
+if $MTL local build --hidden | grep -q "99f9d6592fc5edec"; then
+  false
+fi
+
+# build.ignore-file is read from the scanned root
+echo file1 > .extraignore
+$MTL config set build.ignore-file .extraignore
+if $MTL local list | grep -q "file1$"; then
+  false
+fi
+$MTL local list | grep -q "file2$"
+
+# gc.grace keeps the objects it covers
+$MTL plumbing hash-object -w README > /dev/null
+$MTL local build > /dev/null
+echo "unreachable" > new
+$MTL plumbing hash-object -w new > /dev/null
+$MTL gc -n | grep -q "within the grace period"
+
+$MTL config set pack.delta true
+$MTL pack | grep -q "as deltas"
+
+# a bad value stops the other commands, but not the config ones
+echo "[core]" > .mtl/config
+echo 'jobs = "4"' >> .mtl/config
+if $MTL rev-parse HEAD 2>/dev/null; then
+  false
+fi
+$MTL config set core.jobs 4
+$MTL rev-parse HEAD > /dev/null
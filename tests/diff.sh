@@ -38,4 +38,30 @@ diff -u <($MTL diff 6b1d722afb0c117d 99f9d6592fc5edec) <(cat <<EOF
 -/+ tree/tree	32dbd98251e9a916/f015d1f89f0287bf	z1
 -/  file/    	7f20afdd73eeb0a3/                	z1/.ignore
 EOF
-)
\ No newline at end of file
+)
+# exit status: 0 when identical, 1 when different
+$MTL diff --quiet 99f9d6592fc5edec 99f9d6592fc5edec
+test -z "$($MTL diff --quiet 99f9d6592fc5edec 6b1d722afb0c117d || true)"
+if $MTL diff --quiet 99f9d6592fc5edec 6b1d722afb0c117d; then false; fi
+status=0
+$MTL diff 99f9d6592fc5edec 6b1d722afb0c117d >/dev/null || status=$?
+test "$status" -eq 1
+
+# only the directories, without the files inside them
+diff -u <($MTL diff --dirs-only 99f9d6592fc5edec 6b1d722afb0c117d) <(cat <<EOF
+-/+ tree/tree	99f9d6592fc5edec/6b1d722afb0c117d	.
+-/+ tree/tree	f015d1f89f0287bf/32dbd98251e9a916	z1
+EOF
+)
+
+# paging through the lines
+diff -u <($MTL diff --skip 1 --limit 2 99f9d6592fc5edec 6b1d722afb0c117d) <(cat <<EOF
+ /+     /file	                /f3c610f214152e9f	.ignore
+-/+ tree/tree	f015d1f89f0287bf/32dbd98251e9a916	z1
+EOF
+)
+diff -u <($MTL diff --skip 3 99f9d6592fc5edec 6b1d722afb0c117d) <(cat <<EOF
+ /+     /file	                /7f20afdd73eeb0a3	z1/.ignore
+EOF
+)
+test -z "$($MTL diff --skip 4 99f9d6592fc5edec 6b1d722afb0c117d || true)"
@@ -0,0 +1,28 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+repo=$(setup_new case1)
+cd $repo
+
+MTL_JOBS=1 $MTL local build | grep -q "99f9d6592fc5edec"
+$MTL --jobs 2 local build | grep -q "99f9d6592fc5edec"
+if MTL_JOBS=0 $MTL local build 2>/dev/null; then
+  false
+fi
+
+# the command line wins over the environment
+mkdir sub
+diff -u <(cd sub && MTL_DIR=$repo .$MTL rev-parse HEAD) <(echo 99f9d6592fc5edec)
+diff -u <(cd sub && MTL_DIR=/nonexistent .$MTL --dir $repo rev-parse HEAD) <(echo 99f9d6592fc5edec)
+rmdir sub
+
+MTL_STATS=json $MTL rev-parse HEAD 2>&1 >/dev/null | grep -q '^{"wall_time_secs":'
+MTL_STATS=json $MTL --stats rev-parse HEAD 2>&1 >/dev/null | grep -q '^wall time'
+if MTL_STATS=xml $MTL rev-parse HEAD 2>/dev/null; then
+  false
+fi
+
+# --threads is another name for --jobs, also after the subcommand
+$MTL local build --threads 1 | grep -q "99f9d6592fc5edec"
+diff -u <($MTL local list --threads 2 | sort) <($MTL local list | sort)
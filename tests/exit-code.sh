@@ -0,0 +1,29 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+exit_code() {
+  "$@" >/dev/null 2>&1 && echo 0 || echo $?
+}
+
+$MTL local build >/dev/null
+diff <(exit_code $MTL rev-parse HEAD) <(echo 0)
+diff <(exit_code $MTL diff --quiet 99f9d6592fc5edec f015d1f89f0287bf) <(echo 1)
+diff <(exit_code $MTL --no-such-option) <(echo 2)
+diff <(exit_code $MTL --dir /nonexistent rev-parse HEAD) <(echo 3)
+diff <(exit_code $MTL rev-parse no-such-ref) <(echo 6)
+diff <(exit_code $MTL diff HEAD no-such-ref:dir1) <(echo 6)
+diff <(exit_code $MTL cat-object ffffffffffffffff) <(echo 6)
+diff <(exit_code $MTL local build --report /nonexistent/report.json) <(echo 7)
+
+flock .mtl/lock sleep 2 &
+sleep 0.5
+diff <(exit_code $MTL gc) <(echo 4)
+wait
+
+object=$(find .mtl/objects -type f | head -1)
+printf '\0mtl\001\011' | dd of=$object conv=notrunc status=none
+diff <(exit_code $MTL cat-object $(echo $object | sed 's|.mtl/objects/||; s|/||')) <(echo 5)
+diff <(exit_code $MTL stats --sample 100) <(echo 5)
@@ -0,0 +1,30 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+$MTL local build >/dev/null
+git init -q --bare export.git
+
+# same tree as git computes from the worktree
+git init -q work
+for f in README dir1/file1 dir2/file1 file1 file2 main.c z1/file; do
+  mkdir -p work/$(dirname $f)
+  cp $f work/$f
+done
+expected=$(git -C work add -A && git -C work write-tree)
+
+sha=$($MTL export-git HEAD --git-dir export.git)
+diff -u <(echo $sha) <(echo $expected)
+diff -u <(git --git-dir export.git ls-tree -r --name-only $sha) <(git -C work ls-files)
+diff -u <(git --git-dir export.git cat-file -p $sha:dir1/file1) dir1/file1
+
+# subtrees
+diff -u <($MTL export-git HEAD:dir1 --git-dir export.git) <(git --git-dir export.git rev-parse $sha:dir1)
+
+# files must still match the snapshot
+echo changed >> file2
+if $MTL export-git HEAD --git-dir export.git 2>/dev/null; then
+  false
+fi
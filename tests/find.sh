@@ -0,0 +1,30 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+$MTL local build >/dev/null
+
+diff -u <($MTL find --name 'file*') <(cat <<EOF
+dir1/file1
+dir2/file1
+file1
+file2
+z1/file
+EOF
+)
+
+diff -u <($MTL find --name 'file*' --max-depth 1) <(printf "file1\nfile2\n")
+diff -u <($MTL find --name 'file*' --min-depth 2) <(printf "dir1/file1\ndir2/file1\nz1/file\n")
+diff -u <($MTL find --type tree) <(printf "dir1/\ndir2/\nz1/\n")
+diff -u <($MTL find --regex '^dir[0-9]/') <(printf "dir1/file1\ndir2/file1\n")
+diff -u <($MTL find HEAD:z1) <(echo file)
+
+# reverse lookup by object ID
+diff -u <($MTL find --id e8ec1f907115a249) <(echo dir2/file1)
+diff -u <($MTL find --id F015D1F8) <(echo z1/)
+diff -u <($MTL find --id 2f31af8ed6c71ce5 HEAD:z1) <(echo file)
+if $MTL find --id 2f3 2>/dev/null; then
+  false
+fi
@@ -0,0 +1,19 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+$MTL local build >/dev/null
+$MTL pack
+$MTL local build --hidden >/dev/null
+$MTL pack
+
+# root and z1 of the first build are unreachable
+diff <($MTL gc --dry | grep -c "from pack$") <(echo 2)
+diff <($MTL tool redb | wc -l | awk '{print $1}') <(echo 6)
+
+$MTL gc >/dev/null
+diff <($MTL tool redb | wc -l | awk '{print $1}') <(echo 4)
+diff <($MTL gc --dry | wc -l | awk '{print $1}') <(echo 1)
+$MTL print-tree >/dev/null
@@ -17,3 +17,64 @@ diff <($MTL gc --dry | wc -l | awk '{print $1}') <(echo 3)
 
 $MTL gc >/dev/null
 diff <(find .mtl/objects -type f | wc -l) <(find . -type d -not -path "*.mtl*" | wc -l)
+
+# grace period keeps recently written unreachable objects
+$MTL local build >/dev/null
+$MTL local build --hidden >/dev/null
+diff <($MTL gc --dry --grace 1h | grep -c "^\[dry-run\] Removing") <(echo 0)
+diff <($MTL gc --dry --grace 0s | grep -c "^\[dry-run\] Removing") <(echo 2)
+
+# a build writing them again makes old objects new
+find .mtl/objects -type f -exec touch -d "2 days ago" {} +
+diff <($MTL gc --dry --grace 1d | grep -c "^\[dry-run\] Removing") <(echo 2)
+$MTL local build -n >/dev/null
+diff <($MTL gc --dry --grace 1d | grep -c "^\[dry-run\] Removing") <(echo 0)
+
+# objects listed in the keep file are reachable
+echo 99f9d6592fc5edec > .mtl/keep
+diff <($MTL gc --dry | wc -l | awk '{print $1}') <(echo 1)
+rm .mtl/keep
+
+# a reviewed plan is carried out later, as long as its objects stay unreachable
+plan=$(mktemp)
+echo $plan >> $DROP_LIST
+$MTL gc --plan $plan | grep -q "^\[dry-run\] Deleted 2 objects"
+python3 -c "import json, sys; assert len(json.load(open(sys.argv[1]))['objects']) == 2" $plan
+diff <($MTL gc --dry | grep -c "Removing") <(echo 2)
+
+$MTL ref save old 99f9d6592fc5edec >/dev/null
+diff <($MTL gc --from-plan $plan | grep -c "^Keeping [0-9a-f]*: reachable again$") <(echo 2)
+$MTL ref delete old >/dev/null
+$MTL gc --from-plan $plan | grep -q "^Deleted 2 objects"
+diff <($MTL gc --dry | grep -c "Removing") <(echo 0)
+diff <($MTL gc --from-plan $plan | grep -c "already deleted") <(echo 2)
+
+# trashed objects can be copied back until the trash is emptied
+$MTL local build >/dev/null
+$MTL local build --hidden >/dev/null
+diff <($MTL gc --trash | grep -c "^Moving [0-9a-f]* to .*/.mtl/trash/") <(echo 2)
+diff <(find .mtl/trash -type f | wc -l) <(echo 2)
+if $MTL print-tree -r 99f9d6592fc5edec >/dev/null 2>&1; then
+  false
+fi
+cp -r .mtl/trash/*/* .mtl/objects/
+$MTL print-tree -r 99f9d6592fc5edec >/dev/null
+$MTL gc --empty-trash | grep -q "^Emptied trash (2 objects)$"
+[ ! -e .mtl/trash ]
+$MTL gc --empty-trash | grep -q "^Trash is empty$"
+if $MTL gc --empty-trash --dry 2>/dev/null; then
+  false
+fi
+
+# files left in .mtl/tmp by killed writers go once they are old
+mkdir -p .mtl/tmp
+echo stale > .mtl/tmp/1-0
+touch -d "2 hours ago" .mtl/tmp/1-0
+echo fresh > .mtl/tmp/1-1
+$MTL gc --dry | grep -q "^\[dry-run\] Removing .*/tmp/1-0$"
+[ -e .mtl/tmp/1-0 ]
+$MTL gc | grep -q "^Removing .*/tmp/1-0$"
+[ ! -e .mtl/tmp/1-0 ]
+[ -e .mtl/tmp/1-1 ]
+$MTL gc --grace 0s >/dev/null
+[ ! -e .mtl/tmp/1-1 ]
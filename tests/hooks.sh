@@ -0,0 +1,51 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+log=$(mktemp)
+echo $log >> $DROP_LIST
+
+mkdir -p .mtl/hooks
+for hook in post-build post-gc ref-update; do
+  cat > .mtl/hooks/$hook <<HOOK
+#!/bin/bash
+echo "\$MTL_HOOK \$* \$MTL_DIR" >> $log
+HOOK
+  chmod +x .mtl/hooks/$hook
+done
+
+$MTL local build > /dev/null
+grep -q "^post-build 0000000000000000 99f9d6592fc5edec HEAD $(pwd)$" $log
+grep -q "^ref-update 0000000000000000 99f9d6592fc5edec HEAD $(pwd)$" $log
+
+echo changed > file1
+$MTL local build > /dev/null
+new=$($MTL rev-parse HEAD)
+grep -q "^post-build 99f9d6592fc5edec $new HEAD " $log
+
+: > $log
+$MTL ref save old 99f9d6592fc5edec > /dev/null
+$MTL ref delete old > /dev/null
+diff -u <(awk '{print $1, $2, $3, $4}' $log) <(printf "ref-update 0000000000000000 99f9d6592fc5edec old\nref-update 99f9d6592fc5edec 0000000000000000 old\n")
+
+: > $log
+$MTL gc > /dev/null
+grep -q "^post-gc [1-9][0-9]* " $log
+$MTL gc -n > /dev/null
+[ $(grep -c "^post-gc" $log) -eq 1 ]
+
+# a failing pre-pack hook stops the pack
+printf '#!/bin/sh\nexit 3\n' > .mtl/hooks/pre-pack
+chmod +x .mtl/hooks/pre-pack
+if $MTL pack 2> /dev/null; then
+  false
+fi
+[ ! -e .mtl/pack/packed.redb ]
+chmod -x .mtl/hooks/pre-pack
+$MTL pack
+
+# a failing post hook only warns
+printf '#!/bin/sh\nexit 1\n' > .mtl/hooks/post-build
+$MTL local build 2>&1 >/dev/null | grep -q "^warning: post-build hook failed"
@@ -0,0 +1,32 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+$MTL local build >/dev/null
+head=$($MTL rev-parse HEAD)
+git init -q --bare export.git
+sha=$($MTL export-git HEAD --git-dir export.git)
+
+# a round trip gives back the same tree, saved as a ref
+diff -u <($MTL import-git $sha --git-dir export.git) <(echo $head)
+diff -u <($MTL ref list) <(echo -e "git\t$head")
+diff -u <($MTL import-git $sha:dir1 --git-dir export.git --save dir1) <($MTL rev-parse HEAD:dir1)
+diff -u <($MTL rev-parse dir1) <($MTL rev-parse HEAD:dir1)
+
+# commits are read through their tree, links are skipped
+git init -q work
+for f in README dir1/file1 dir2/file1 file1 file2 main.c z1/file; do
+  mkdir -p work/$(dirname $f)
+  cp $f work/$f
+done
+ln -s file1 work/link
+git -C work add -A
+git -C work -c user.name=mtl -c user.email=mtl@example.com commit -qm import
+diff -u <($MTL import-git HEAD --git-dir work/.git --save work 2>/dev/null) <(echo $head)
+
+# unknown revisions fail
+if $MTL import-git no-such-rev --git-dir work/.git 2>/dev/null; then
+  false
+fi
@@ -17,6 +17,23 @@ cat .mtl/HEAD | grep -Eq "^${hash}$"
 echo 'README' | $MTL local build -i - | grep -Eq "\s${hash}$"
 cat .mtl/HEAD | grep -Eq "^${hash}$"
 
+# listed paths are normalized
+printf './/README\n.\n' | $MTL local build -i - | grep -Eq "\s${hash}$"
+if echo 'dir1/../README' | $MTL local build -i - 2>/dev/null; then
+  false
+fi
+if echo '/etc/passwd' | $MTL local build -i - 2>/dev/null; then
+  false
+fi
+cat .mtl/HEAD | grep -Eq "^${hash}$"
+
+# parents of listed files are added, and kinds can be given explicitly
+hash="99f9d6592fc5edec"
+$MTL local list | awk '$1 == "file" {print $2}' | $MTL local build --files-from - | grep -Eq "\s${hash}$"
+$MTL local list | sed 's/ /\t/' | $MTL local build --files-from - | grep -Eq "\s${hash}$"
+printf 'file\tdir1/file1\ndir\tdir1\nREADME\n' | $MTL local build -i - >/dev/null
+diff -u <($MTL print-tree | cut -f2) <(printf '.\nREADME\ndir1/\ndir1/file1\n')
+
 hash="ae65013be93d648e"
 $MTL local build -i <(echo '.ignore') | grep -Eq "\s${hash}$"
 cat .mtl/HEAD | grep -Eq "^${hash}$"
@@ -25,4 +42,27 @@ cat .mtl/HEAD | grep -Eq "^${hash}$"
 # hidden file:  "--hidden" option
 hash="6b1d722afb0c117d"
 $MTL local build --hidden | grep -Eq "\s${hash}$"
-cat .mtl/HEAD | grep -Eq "^${hash}$"
\ No newline at end of file
+cat .mtl/HEAD | grep -Eq "^${hash}$"
+# size and file type filters, with a report of what they skipped
+head -c 100000 /dev/zero > scratch.bin
+mkfifo pipe
+report=$(mktemp)
+$MTL local build --max-file-size 1000 --skip-special --skip-report $report | grep -Eq "\s99f9d6592fc5edec$"
+# ./mtl is a symlink to the binary
+diff -u $report <(printf 'special\tmtl\nspecial\tpipe\ntoo-large\tscratch.bin\n')
+# a listed FIFO would block the build if it were read
+$MTL local build -i <(printf 'README\npipe\n') --skip-special --skip-report $report >/dev/null
+diff -u $report <(printf 'special\tpipe\n')
+$MTL local build --min-file-size 10 --max-file-size 11 --no-write-head --skip-report $report >/dev/null
+diff -u $report <(printf 'too-small\tfile1\ntoo-small\tfile2\ntoo-large\tmain.c\ntoo-large\tscratch.bin\ntoo-small\tz1/file\n')
+rm $report pipe scratch.bin
+
+# hashing through a memory map gives the same trees
+$MTL local build --mmap --mmap-threshold 0 | grep -Eq "\s99f9d6592fc5edec$"
+head -c 3000000 /dev/urandom > scratch.bin
+hash=$($MTL local build --no-write-head | awk '{print $NF}')
+$MTL local build --mmap --no-write-head | grep -Eq "\s${hash}$"
+if $MTL local build --mmap-threshold 10 2>/dev/null; then
+  false
+fi
+rm scratch.bin
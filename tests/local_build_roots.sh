@@ -0,0 +1,38 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+repo=$(setup_new case1)
+a=$(setup_new case1)
+b=$(setup_new case1)
+cd $repo
+echo changed > $b/README
+rm $a/mtl $b/mtl
+
+expected_a=$(cd $a && $repo/mtl tool hash --tree . | awk '{print $1}')
+expected_b=$(cd $b && $repo/mtl tool hash --tree . | awk '{print $1}')
+[ $expected_a != $expected_b ]
+
+# each root goes to its own ref, and HEAD is left alone
+output=$($MTL local build --root $a --root $b=volume-b)
+diff -u <(echo "$output") <(printf "Written $(basename $a): $expected_a\nWritten volume-b: $expected_b\n")
+[ ! -e .mtl/HEAD ]
+diff -u <($MTL rev-parse volume-b) <(echo $expected_b)
+$MTL print-tree -r volume-b:dir1 > /dev/null
+[ ! -e $a/.mtl ]
+$MTL audit show | tail -1 | grep -q " build volume-b $expected_b$"
+
+# nothing is written when a root fails
+if $MTL local build --root $a=x --root /nonexistent=y 2>/dev/null; then
+  false
+fi
+if $MTL rev-parse x 2>/dev/null; then
+  false
+fi
+
+if $MTL local build --root $a=same --root $b=same 2>/dev/null; then
+  false
+fi
+if $MTL local build --root $a=HEAD 2>/dev/null; then
+  false
+fi
@@ -46,4 +46,122 @@ tree z1
 file z1/.ignore
 file z1/file
 EOF
-)
\ No newline at end of file
+)
+# globs
+diff <($MTL local list --include '**/file1' --exclude 'dir2/**' | sort -k2) <(cat <<EOF
+tree .
+tree dir1
+file dir1/file1
+file file1
+tree z1
+EOF
+)
+
+diff <($MTL local list -i <(printf 'README\nmain.c\n') --exclude '*.c' | sort -k2) <(cat <<EOF
+tree .
+file README
+EOF
+)
+
+# build takes the same filters
+hash=$($MTL local build --no-write-head -i <(printf 'dir1/file1\nfile1\nz1/\n') | awk '{print $NF}')
+diff <($MTL local build --no-write-head --include '**/file1' --exclude 'dir2/**' | awk '{print $NF}') <(echo $hash)
+
+# .mtlignore files, at the root and nested, in gitignore syntax
+echo 'file2' > .mtlignore
+echo '/file1' > dir1/.mtlignore
+diff <($MTL local list | sort -k2) <(cat <<LIST
+tree .
+file README
+tree dir1
+tree dir2
+file dir2/file1
+file file1
+file main.c
+tree z1
+file z1/file
+LIST
+)
+rm .mtlignore dir1/.mtlignore
+
+# size bounds and ignore files, the latter also for listed paths
+diff <($MTL local list --min-size 8 --max-size 10 | sort -k2) <(cat <<LIST
+tree .
+tree dir1
+file dir1/file1
+tree dir2
+file dir2/file1
+tree z1
+file z1/file
+LIST
+)
+ignore_file=$(mktemp)
+printf 'dir2/\n*.c\n' > $ignore_file
+diff <($MTL local list --ignore-file $ignore_file | sort -k2) <(cat <<LIST
+tree .
+file README
+tree dir1
+file dir1/file1
+file file1
+file file2
+tree z1
+file z1/file
+LIST
+)
+diff <($MTL local list --ignore-file $ignore_file -i <(printf 'main.c\ndir2/file1\nREADME\n') | sort -k2) <(cat <<LIST
+tree .
+file README
+LIST
+)
+rm $ignore_file
+
+# --explain tells why entries were left out, on stderr
+ignore_file=$(mktemp)
+printf 'z1/\n' > $ignore_file
+diff -u <($MTL local list --explain --include '**/file1' --exclude 'dir2/**' --ignore-file $ignore_file 2>&1 >/dev/null) <(cat <<LIST
+pruned	excluded	dir2/
+pruned	ignored	z1/
+skipped	excluded	1
+skipped	not-included	4
+skipped	ignored	1
+LIST
+)
+rm $ignore_file
+
+# hidden entries can be taken or left out one by one
+mkdir -p .well-known/.secret
+echo a > .well-known/key
+echo b > .well-known/.secret/key
+diff <($MTL local list --include-hidden '**/.well-known' | sort -k2) <(cat <<LIST
+tree .
+tree .well-known
+file .well-known/key
+file README
+tree dir1
+file dir1/file1
+tree dir2
+file dir2/file1
+file file1
+file file2
+file main.c
+tree z1
+file z1/file
+LIST
+)
+diff <($MTL local list --hidden --exclude-hidden '**/.ignore' --exclude-hidden '.well-known/.secret' | sort -k2) <(cat <<LIST
+tree .
+tree .well-known
+file .well-known/key
+file README
+tree dir1
+file dir1/file1
+tree dir2
+file dir2/file1
+file file1
+file file2
+file main.c
+tree z1
+file z1/file
+LIST
+)
+rm -r .well-known
@@ -0,0 +1,43 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+# no snapshot to compare with yet
+if $MTL local status 2>/dev/null; then
+  false
+fi
+
+$MTL local build > /dev/null
+diff -u <($MTL local status) /dev/null
+
+echo changed >> README
+echo new > "dir1/new file"
+rm -r dir2
+diff -u <($MTL local status) <(cat <<EOF
+M README
+A dir1/new file
+D dir2
+EOF
+)
+diff -u <($MTL local status --porcelain | tr '\0' '\n') <(cat <<EOF
+M README
+A dir1/new file
+D dir2
+EOF
+)
+
+# HEAD is left alone
+diff -u <($MTL rev-parse HEAD) <(echo 99f9d6592fc5edec)
+
+# and nothing is written to the objects
+objects=$(find .mtl/objects -type f | sort)
+echo changed again >> README
+diff -u <($MTL local status) <(cat <<EOF
+M README
+A dir1/new file
+D dir2
+EOF
+)
+diff -u <(find .mtl/objects -type f | sort) <(echo "$objects")
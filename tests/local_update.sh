@@ -20,7 +20,7 @@ diff -u <($MTL rev-parse HEAD:z1) <($MTL rev-parse update-root:z1)
 echo "hello" >> z1/file2
 
 $MTL local update --hidden z1 >/dev/null
-diff -u <($MTL cat-object HEAD:z1) <(cat .mtl/objects/31/32b2cc5f6bcb59)
+diff -u <($MTL cat-object HEAD:z1) <(loose_object .mtl/objects/31/32b2cc5f6bcb59)
 
 cd - >/dev/null
 cd $(setup_new case2)
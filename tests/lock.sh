@@ -0,0 +1,38 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+$MTL local build >/dev/null
+
+# hold the repository lock from another process
+flock .mtl/lock sleep 2 &
+sleep 0.5
+
+status=0
+$MTL gc 2>/dev/null || status=$?
+[ $status -eq 4 ]
+$MTL gc 2>&1 | grep -q "repository is busy"
+# read-only commands do not take the lock
+$MTL print-tree >/dev/null
+
+# --wait blocks until the lock is released
+$MTL gc --wait >/dev/null
+wait
+
+# builds share the lock with each other, but not with gc
+flock -s .mtl/lock sleep 2 &
+sleep 0.5
+$MTL local build --root dir1=one >/dev/null
+$MTL gc 2>&1 | grep -q "is held by another process (shared by builds)"
+wait
+
+# builds of different subtrees run side by side, each into its own ref
+$MTL local build --root dir1=one >/dev/null &
+pid=$!
+$MTL local build --root z1=two >/dev/null
+wait $pid
+diff <($MTL rev-parse one) <($MTL rev-parse HEAD:dir1)
+diff <($MTL rev-parse two) <($MTL rev-parse HEAD:z1)
+$MTL gc --dry >/dev/null
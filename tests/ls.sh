@@ -0,0 +1,36 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+$MTL local build >/dev/null
+
+diff -u <($MTL ls) <(cat <<EOF
+README
+dir1/
+dir2/
+file1
+file2
+main.c
+z1/
+EOF
+)
+
+diff -u <($MTL ls HEAD:z1) <(echo file)
+
+diff -u <($MTL ls --long | tr -s ' \t' ' ') <(cat <<EOF
+file d447b1ea40e6988b - README
+tree 188acf4cce004363 28 dir1/
+tree ba35f09b9bff44c1 28 dir2/
+file ec3cea290f9e42e8 - file1
+file 56c2402c3bf24293 - file2
+file e8383ee34f1c57f5 - main.c
+tree f015d1f89f0287bf 27 z1/
+EOF
+)
+
+# not a tree
+if $MTL ls HEAD:README 2>/dev/null; then
+  false
+fi
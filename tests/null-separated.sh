@@ -0,0 +1,37 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+echo spaces > "a b.txt"
+echo " padded" > " padded "
+
+# -z only changes the terminator
+diff -u <($MTL local list -z | tr '\0' '\n') <($MTL local list)
+
+$MTL local build >/dev/null
+head=$($MTL rev-parse HEAD)
+diff -u <($MTL print-tree -z | tr '\0' '\n') <($MTL print-tree)
+diff -u <($MTL find -z --name '* *' | tr '\0' '\n') <($MTL find --name '* *')
+
+# paths are read as is, spaces included; directories are listed with a "/"
+list=$(mktemp)
+$MTL local list -z | perl -0 -pe 's/^file // or s/^tree (.*)\0/$1\/\0/' > $list
+diff -u <($MTL local build -z --input $list | awk '{print $NF}') <(echo $head)
+diff -u <($MTL local list -z --input $list | tr '\0' '\n' | grep padded) <(echo "file  padded ")
+
+# find output can be fed to xargs -0
+diff -u <($MTL find -z --name '*.txt' | xargs -0 cat) <(echo spaces)
+
+# trees cannot hold newlines in names
+if printf 'new\nline\0' | $MTL local build -z --input - 2>/dev/null; then
+  false
+fi
+
+# a tab is part of the path, not a kind as in line mode
+echo tab > tab
+diff -u <(printf 'file\ttab\n' | $MTL local list --input - | grep tab) <(echo "file tab")
+if printf 'file\ttab\0' | $MTL local build -z --input - 2>/dev/null; then
+  false
+fi
@@ -0,0 +1,37 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+$MTL local build >/dev/null
+$MTL ref save first >/dev/null
+
+# one JSON object per text line
+diff <($MTL --format json print-tree | wc -l) <($MTL print-tree | wc -l)
+$MTL print-tree --format json | head -1 | grep -qx '{"id":"99f9d6592fc5edec","path":".","type":"tree"}'
+diff <($MTL ref list --format json) <(echo '{"id":"99f9d6592fc5edec","name":"first"}')
+
+$MTL local build --hidden >/dev/null
+$MTL diff --format json first HEAD | grep -qx '{"a":null,"b":{"id":"7f20afdd73eeb0a3","type":"file"},"path":"z1/.ignore"}'
+$MTL audit show --format json | tail -1 | grep -q '"operation":"build"'
+
+$MTL ref delete first >/dev/null
+$MTL --format json gc --dry | tail -1 | grep -qx '{"deleted_bytes":[0-9]*,"deleted_objects":2,"dry_run":true}'
+diff <($MTL --format json gc --dry | grep -c '"action":"remove"') <(echo 2)
+$MTL ref save first 99f9d6592fc5edec >/dev/null
+echo new > new
+$MTL local status --format json | grep -qx '{"change":"A","path":"new","type":"file"}'
+
+# colors only when asked for, as the output is not a terminal
+if $MTL diff first HEAD | grep -q $'\e\['; then
+  false
+fi
+$MTL diff --color always first HEAD | grep -q $'\e\['
+if $MTL diff --color never first HEAD | grep -q $'\e\['; then
+  false
+fi
+
+if $MTL --format yaml print-tree 2>/dev/null; then
+  false
+fi
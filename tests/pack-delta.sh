@@ -0,0 +1,42 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+$MTL local build >/dev/null
+$MTL ref save old >/dev/null
+old=$($MTL rev-parse old)
+echo changed > file1
+$MTL local build >/dev/null
+new=$($MTL rev-parse HEAD)
+
+cat_old=$($MTL cat-object old)
+tree_old=$($MTL print-tree -r old)
+tree_new=$($MTL print-tree)
+
+# only the root changed, and the old one is stored against HEAD's
+diff <($MTL pack --delta) <(echo "Packed 5 objects, 1 as deltas")
+diff <(find .mtl/objects -type f | wc -l | awk '{print $1}') <(echo 0)
+diff <($MTL tool redb $old) <(echo "delta against $new")
+diff <($MTL cat-object old) <(echo "$cat_old")
+diff <($MTL print-tree -r old) <(echo "$tree_old")
+diff <($MTL print-tree) <(echo "$tree_new")
+$MTL diff old HEAD >/dev/null || true
+
+# a plain pack stores everything whole again
+$MTL pack
+diff <($MTL tool redb $old) <(echo "$cat_old"; echo)
+$MTL pack --delta >/dev/null
+
+# removing the base keeps the delta readable
+echo changed again > file1
+$MTL local build >/dev/null
+diff <($MTL gc | grep -c "from pack$") <(echo 1)
+diff <($MTL cat-object old) <(echo "$cat_old")
+diff <($MTL tool redb $old) <(echo "$cat_old"; echo)
+
+# refs are delta bases without HEAD too
+rm .mtl/HEAD
+$MTL pack --delta >/dev/null
+diff <($MTL cat-object old) <(echo "$cat_old")
@@ -0,0 +1,52 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+$MTL local build > /dev/null
+$MTL pack
+echo changed > README
+root=$($MTL local build | awk '{print $NF}')
+
+# a pack that is not a redb database is kept as it is
+head -c 4096 /dev/urandom > .mtl/pack/packed.redb
+cp .mtl/pack/packed.redb damaged.redb
+
+# loose objects can still be read, with a warning
+$MTL cat-object $root 2>&1 >/dev/null | grep -q '^warning: the pack is unreadable (not a redb database)'
+$MTL cat-object $root 2>/dev/null | grep -q "README"
+if $MTL print-tree >/dev/null 2>&1; then
+  false
+fi
+
+# commands that would drop the packed objects refuse to run
+for command in "pack" "pack --incremental" "gc"; do
+  if $MTL $command 2>/dev/null; then
+    false
+  fi
+  $MTL $command 2>&1 | grep -q 'run "mtl pack rebuild" to replace it'
+done
+
+output=$($MTL pack rebuild 2>/dev/null)
+echo "$output" | grep -q "^Moved the unreadable pack (not a redb database) to .*/packed.redb.broken-[0-9]*$"
+echo "$output" | grep -q "^Packed 1 objects$"
+diff -u <(echo "$output" | sed -n '/^Missing/,$p' | sort) <(cat <<EOF
+  188acf4cce004363
+  ba35f09b9bff44c1
+  f015d1f89f0287bf
+Missing trees reachable from HEAD and the refs: 3
+EOF
+)
+cmp .mtl/pack/packed.redb.broken-* damaged.redb
+rm damaged.redb
+
+# the pack is usable again, and a build brings back the missing trees
+diff <($MTL cat-object $root 2>&1 >/dev/null) /dev/null
+$MTL local build > /dev/null
+$MTL print-tree > /dev/null
+$MTL pack rebuild | grep -q "^Missing trees reachable from HEAD and the refs: 0$"
+$MTL audit show | tail -1 | grep -q " pack-rebuild (4 objects)$"
+# the trees are looked for in the new pack, with the same counters: four
+# objects are packed, and read again from the new pack
+$MTL pack rebuild --stats=json 2>&1 >/dev/null | grep -q '"packed_reads":8,'
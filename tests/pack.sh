@@ -57,4 +57,18 @@ $MTL local build --hidden >/dev/null
 $MTL pack
 $MTL gc >/dev/null
 
-diff <(find .mtl/objects -type f | wc -l | awk '{print $1}') <(echo 0)
\ No newline at end of file
+diff <(find .mtl/objects -type f | wc -l | awk '{print $1}') <(echo 0)
+# incremental pack keeps packed objects and adds new loose ones
+$MTL local build >/dev/null
+$MTL pack --incremental
+diff <(find .mtl/objects -type f | wc -l | awk '{print $1}') <(echo 0)
+diff <($MTL tool redb | wc -l | awk '{print $1}') <(echo 6)
+
+$MTL ref save root >/dev/null
+$MTL pack --incremental
+diff <($MTL tool redb | wc -l | awk '{print $1}') <(echo 6)
+$MTL print-tree >/dev/null
+
+# rebuilding unchanged trees does not write loose copies of packed objects
+$MTL local build >/dev/null
+diff <(find .mtl/objects -type f | wc -l | awk '{print $1}') <(echo 0)
@@ -0,0 +1,34 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+top=$(pwd)
+cd $(setup_new case1)
+
+$MTL local build >/dev/null
+$MTL local build --hidden >/dev/null
+
+diff -u <($MTL diff --emit-patch 99f9d6592fc5edec 6b1d722afb0c117d) <(cat <<EOF
+add	file	f3c610f214152e9f	.ignore
+add	file	7f20afdd73eeb0a3	z1/.ignore
+EOF
+)
+diff -u <($MTL diff --emit-patch 6b1d722afb0c117d 6b1d722afb0c117d) /dev/null
+
+# round trip in both directions
+$MTL diff --emit-patch 99f9d6592fc5edec 6b1d722afb0c117d > forward.patch || true
+$MTL diff --emit-patch 6b1d722afb0c117d 99f9d6592fc5edec > backward.patch || true
+diff -u <($MTL apply forward.patch 99f9d6592fc5edec) <(echo 6b1d722afb0c117d)
+diff -u <($MTL apply - 6b1d722afb0c117d < backward.patch) <(echo 99f9d6592fc5edec)
+
+# the patch does not apply twice
+if $MTL apply forward.patch 6b1d722afb0c117d 2>/dev/null; then
+  false
+fi
+
+# replay on another repository that only has the old tree
+patch=$(pwd)/forward.patch
+cd $top
+cd $(setup_new case1)
+$MTL local build >/dev/null
+diff -u <($MTL apply $patch HEAD) <(echo 6b1d722afb0c117d)
@@ -0,0 +1,32 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+repo=$(setup_new case1)
+cd $repo
+
+$MTL local build > /dev/null
+
+# paths are relative to the root by default
+$MTL find --name file1 | grep -q "^file1$"
+$MTL --absolute find --name file1 | grep -q "^$repo/file1$"
+$MTL find --name file1 --absolute | grep -q "^$repo/file1$"
+$MTL --relative-to dir1 find --name file1 | grep -q "^\.\./file1$"
+$MTL --relative-to . find --name file1 | grep -q "^file1$"
+
+$MTL --absolute print-tree | head -1 | grep -q "^tree 99f9d6592fc5edec	$repo$"
+$MTL --absolute --format json print-tree | grep -q "\"path\":\"$repo/README\""
+$MTL --relative-to dir2 local list | grep -q " \.\./README$"
+
+$MTL ref save old > /dev/null
+echo changed > file1
+$MTL --relative-to dir1 local status | grep -q "^M \.\./file1$"
+$MTL local build > /dev/null
+$MTL --absolute diff old HEAD | grep -q "	$repo/file1$"
+
+if $MTL --absolute --relative-to . find 2>/dev/null; then
+  false
+fi
+if $MTL --relative-to /nonexistent find 2>/dev/null; then
+  false
+fi
@@ -0,0 +1,24 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+$MTL local build >/dev/null
+
+# same IDs as the build
+diff -u <($MTL plumbing hash-object README dir1/file1) <(printf "d447b1ea40e6988b\n83e38dfac6ad32cd\n")
+diff -u <($MTL plumbing hash-object < README) <(echo d447b1ea40e6988b)
+[ ! -e .mtl/objects/d4/47b1ea40e6988b ]
+
+# -w stores the contents
+$MTL plumbing hash-object -w README >/dev/null
+diff -u <($MTL cat-object d447b1ea40e6988b) README
+garbage=$(echo "not in any tree" | $MTL plumbing hash-object -w)
+
+# gc keeps blobs referenced from reachable trees only
+$MTL gc >/dev/null
+diff -u <($MTL cat-object d447b1ea40e6988b) README
+if $MTL cat-object $garbage 2>/dev/null; then
+  false
+fi
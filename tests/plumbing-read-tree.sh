@@ -0,0 +1,35 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+echo backslash > 'dir1/back\slash'
+$MTL local build >/dev/null
+head=$($MTL rev-parse HEAD)
+
+# files only, paths from the root, backslashes escaped
+$MTL plumbing read-tree HEAD > entries
+diff -u <(cut -f3 entries | sort) <($MTL print-tree -t file | tail -n +2 | cut -f2 | sort)
+grep -F 'dir1/back\\slash' entries >/dev/null
+if grep -q "^tree" entries; then
+  false
+fi
+
+# round trip
+diff -u <($MTL plumbing write-tree < entries) <(echo $head)
+diff -u <($MTL plumbing read-tree HEAD:dir1 | $MTL plumbing write-tree) <($MTL rev-parse HEAD:dir1)
+
+# dropping entries rebuilds the trees above them
+tree=$(grep -v 'dir1/' entries | $MTL plumbing write-tree)
+if $MTL rev-parse $tree:dir1 2>/dev/null; then
+  false
+fi
+diff -u <($MTL plumbing read-tree $tree) <(grep -v 'dir1/' entries)
+
+# empty trees are listed so that they survive the round trip
+empty=$($MTL plumbing write-tree < /dev/null)
+tree=$(printf "tree\t$empty\tempty\nfile\td447b1ea40e6988b\tREADME\n" | $MTL plumbing write-tree)
+diff -u <($MTL plumbing read-tree $tree) <(printf "file\td447b1ea40e6988b\tREADME\ntree\t$empty\tempty\n")
+diff -u <($MTL plumbing read-tree $tree | $MTL plumbing write-tree) <(echo $tree)
+diff -u <($MTL plumbing read-tree $empty) /dev/null
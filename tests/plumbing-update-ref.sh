@@ -0,0 +1,33 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+$MTL local build >/dev/null
+
+# create only if missing
+diff -u <($MTL plumbing update-ref root 99f9d6592fc5edec --old 0000000000000000) <(echo -n "")
+diff -u <($MTL rev-parse root) <(echo 99f9d6592fc5edec)
+if $MTL plumbing update-ref root 6b1d722afb0c117d --old 0000000000000000 2>/dev/null; then
+  false
+fi
+
+# compare and swap
+if $MTL plumbing update-ref root 6b1d722afb0c117d --old f015d1f89f0287bf 2>/dev/null; then
+  false
+fi
+diff -u <($MTL rev-parse root) <(echo 99f9d6592fc5edec)
+$MTL plumbing update-ref root 6b1d722afb0c117d --old 99f9d6592fc5edec
+diff -u <($MTL rev-parse root) <(echo 6b1d722afb0c117d)
+
+# HEAD
+$MTL plumbing update-ref HEAD f015d1f89f0287bf
+diff -u <($MTL rev-parse HEAD) <(echo f015d1f89f0287bf)
+
+# delete
+if $MTL plumbing update-ref -d root --old 99f9d6592fc5edec 2>/dev/null; then
+  false
+fi
+$MTL plumbing update-ref -d root --old 6b1d722afb0c117d
+diff -u <($MTL ref list) <(echo -n "")
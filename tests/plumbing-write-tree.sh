@@ -0,0 +1,45 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+$MTL local build >/dev/null
+head=$($MTL rev-parse HEAD)
+
+# print-tree output round-trips, with or without the tree lines
+diff -u <($MTL print-tree | $MTL plumbing write-tree) <(echo $head)
+diff -u <($MTL print-tree -t file | $MTL plumbing write-tree) <(echo $head)
+diff -u <($MTL print-tree -t file | sed 's/ /\t/' | $MTL plumbing write-tree) <(echo $head)
+
+# an existing tree is reused without listing its contents
+diff -u <($MTL print-tree --max-depth 1 | $MTL plumbing write-tree) <(echo $head)
+
+# synthesized tree
+tree=$(printf "file\td447b1ea40e6988b\tnew/deep/README\n" | $MTL plumbing write-tree)
+diff -u <($MTL print-tree -r $tree) <(cat <<EOT
+tree $tree	.
+tree $($MTL rev-parse $tree:new)	new/
+tree $($MTL rev-parse $tree:new/deep)	new/deep/
+file d447b1ea40e6988b	new/deep/README
+EOT
+)
+
+# conflicting, invalid and missing entries
+if printf "file\td447b1ea40e6988b\ta\nfile\td447b1ea40e6988b\ta/b\n" | $MTL plumbing write-tree 2>/dev/null; then
+  false
+fi
+if printf "file\td447b1ea40e6988b\t../a\n" | $MTL plumbing write-tree 2>/dev/null; then
+  false
+fi
+if printf "tree\t0123456789abcdef\ta\n" | $MTL plumbing write-tree 2>/dev/null; then
+  false
+fi
+
+# backslashes in names are escaped on the way, so that they survive it
+mkdir 'odd\dir'
+echo a > 'odd\dir/a\nb'
+head=$($MTL local build | awk '{print $NF}')
+diff -u <($MTL print-tree | $MTL plumbing write-tree) <(echo $head)
+$MTL print-tree | grep -qF 'odd\\dir/a\\nb'
+$MTL print-tree -z | tr '\0' '\n' | grep -qF 'odd\dir/a\nb'
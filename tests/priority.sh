@@ -0,0 +1,35 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+# the build blocks on its input, so its priority can be looked at meanwhile
+fifo=$(mktemp -u)
+mkfifo $fifo
+echo $fifo >> $DROP_LIST
+
+$MTL --nice 7 --ionice best-effort:6 local build -i $fifo > /dev/null &
+pid=$!
+sleep 0.5
+[ $(ps -o ni= -p $pid) -eq 7 ]
+ionice -p $pid | grep -q "^best-effort: prio 6$"
+echo README > $fifo
+wait $pid
+
+$MTL --background local build -i $fifo > /dev/null &
+pid=$!
+sleep 0.5
+[ $(ps -o ni= -p $pid) -eq 19 ]
+ionice -p $pid | grep -q "^idle$"
+echo README > $fifo
+wait $pid
+
+# the option goes after the subcommand too
+$MTL local build --background | grep -q "99f9d6592fc5edec"
+
+for args in "--nice 20" "--ionice idle:1" "--ionice low" "--background --nice 1"; do
+  if $MTL $args rev-parse HEAD 2>/dev/null; then
+    false
+  fi
+done
@@ -0,0 +1,20 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+# JSON lines go to stderr, the root to stdout as usual
+progress=$(mktemp)
+echo $progress >> $DROP_LIST
+$MTL local build --progress=json 2> $progress | grep -q "99f9d6592fc5edec"
+head -1 $progress | grep -q '^{"event":"phase","phase":"scan"}$'
+grep -q '^{"dirs":[0-9]*,"event":"phase","files":7,"phase":"hash"}$' $progress
+tail -1 $progress | grep -q '^{"dirs":[0-9]*,"event":"finish","files":7}$'
+
+# a bare --progress still means bars
+$MTL local build --progress 2> /dev/null | grep -q "99f9d6592fc5edec"
+
+if $MTL local build --progress=dots 2>/dev/null; then
+  false
+fi
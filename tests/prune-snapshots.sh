@@ -0,0 +1,61 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+$MTL local build >/dev/null
+for name in snap-1 snap-2 snap-3 snap-4; do
+  $MTL ref save $name >/dev/null
+done
+$MTL ref save keep >/dev/null
+
+# snapshots are as old as the last entry of their reflog
+written_at() {
+  printf "%s\t%s\n" $($MTL rev-parse $1) $(date -d "$2" +%s) >> .mtl/logs/refs/$1
+}
+written_at snap-1 "2024-01-15 12:00:00Z"
+written_at snap-2 "2024-01-15 01:00:00Z"
+written_at snap-3 "2024-01-14 00:00:00Z"
+written_at snap-4 "2023-12-31 00:00:00Z"
+# whatever the mtime of the ref says
+touch -d "2000-01-01 00:00:00Z" .mtl/refs/snap-1
+
+# a policy is required
+if $MTL prune-snapshots 2>/dev/null; then
+  false
+fi
+
+# only refs starting with "snapshot-" unless told otherwise
+diff -u <($MTL prune-snapshots --keep-last 1 --dry) /dev/null
+if $MTL prune-snapshots --prefix "" --keep-last 1 2>/dev/null; then
+  false
+fi
+
+diff -u <($MTL prune-snapshots --prefix snap- --keep-daily 2 --dry) <(cat <<EOF
+[dry-run] Deleting ref "snap-2"
+[dry-run] Deleting ref "snap-4"
+EOF
+)
+diff -u <($MTL ref list | cut -f1) <(printf "keep\nsnap-1\nsnap-2\nsnap-3\nsnap-4\n")
+
+$MTL prune-snapshots --prefix snap- --keep-daily 2 --keep-monthly 2 >/dev/null
+diff -u <($MTL ref list | cut -f1) <(printf "keep\nsnap-1\nsnap-3\nsnap-4\n")
+
+# unreferenced trees are collected with --gc
+$MTL local build --hidden >/dev/null
+$MTL ref save snap-5 >/dev/null
+$MTL local build >/dev/null
+written_at snap-5 "2000-01-01 00:00:00Z"
+
+# and packed with the settings of the repository
+$MTL config set pack.delta true
+tmpfile=$(mktemp)
+$MTL prune-snapshots --prefix snap- --keep-last 3 --gc --pack > $tmpfile
+grep -q '^Deleting ref "snap-5"$' $tmpfile
+grep -q "as deltas$" $tmpfile
+[ -z "$(find .mtl/objects -type f 2>/dev/null)" ]
+if $MTL cat-object 6b1d722afb0c117d 2>/dev/null; then
+  false
+fi
+$MTL rev-parse HEAD:z1 >/dev/null
@@ -30,4 +30,5 @@ tmpfile=$(mktemp)
 echo -e "z1\tf015d1f89f0287bf" > $tmpfile
 diff -u <($MTL ref list) $tmpfile
 
-$MTL ref delete z1 >/dev/null
\ No newline at end of file
+$MTL ref delete z1 >/dev/null
+$MTL rev-parse z1 2>&1 | grep -q 'ref "z1" not found'
@@ -0,0 +1,35 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+$MTL local build >/dev/null
+$MTL local build --hidden >/dev/null
+
+# HEAD@{0} is the current HEAD, HEAD@{1} the previous one
+diff -u <($MTL rev-parse HEAD@{0}) <(echo 6b1d722afb0c117d)
+diff -u <($MTL rev-parse HEAD@{1}) <(echo 99f9d6592fc5edec)
+diff -u <($MTL rev-parse @{1}) <(echo 99f9d6592fc5edec)
+
+# with a path
+diff -u <($MTL rev-parse HEAD@{1}:z1) <(echo f015d1f89f0287bf)
+diff -u <($MTL diff HEAD@{1}:z1 HEAD:z1) <(cat <<EOF
+-/+ tree/tree	f015d1f89f0287bf/32dbd98251e9a916	.
+ /+     /file	                /7f20afdd73eeb0a3	.ignore
+EOF
+)
+
+# refs keep their own reflog
+$MTL ref save root HEAD@{1} >/dev/null
+$MTL ref save root HEAD >/dev/null
+diff -u <($MTL rev-parse root@{1}) <(echo 99f9d6592fc5edec)
+
+# out of range
+if $MTL rev-parse HEAD@{5} 2>/dev/null; then
+  false
+fi
+
+# the reflog goes away with the ref
+$MTL ref delete root >/dev/null
+[ ! -e .mtl/logs/refs/root ]
@@ -0,0 +1,31 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+export XDG_CONFIG_HOME=$(mktemp -d)
+echo $XDG_CONFIG_HOME >> $DROP_LIST
+
+$MTL remote add origin ssh://backup.example.com/data
+$MTL remote add archive s3://bucket/mtl
+diff -u <($MTL remote list) <(printf 'archive\ts3://bucket/mtl\norigin\tssh://backup.example.com/data\n')
+$MTL --format json remote list | grep -q '^{"name":"origin","url":"ssh://backup.example.com/data"}$'
+diff -u <($MTL config get remote.origin.url) <(echo ssh://backup.example.com/data)
+grep -q '^\[remote.origin\]$' .mtl/config
+
+for args in "origin https://example.com/other" "bad.name ssh://host/x" "local /data" "ftp ftp://host/x"; do
+  if $MTL remote add $args 2>/dev/null; then
+    false
+  fi
+done
+
+# a remote of the user's config file is listed too
+$MTL config set --user remote.home.url https://home.example.com/data
+$MTL remote list | grep -q "^home	https://home.example.com/data$"
+
+$MTL remote remove archive
+diff -u <($MTL remote list) <(printf 'home\thttps://home.example.com/data\norigin\tssh://backup.example.com/data\n')
+if $MTL remote remove archive 2>/dev/null; then
+  false
+fi
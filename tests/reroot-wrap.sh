@@ -0,0 +1,29 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+$MTL local build >/dev/null
+dir1=$($MTL rev-parse HEAD:dir1)
+
+# reroot prints the subtree and can save it
+diff -u <($MTL reroot HEAD:dir1) <(echo $dir1)
+$MTL reroot HEAD:dir1 --save sub >/dev/null
+diff -u <($MTL rev-parse sub) <(echo $dir1)
+if $MTL reroot HEAD:README 2>/dev/null; then
+  false
+fi
+
+# wrap nests a tree under new directories
+tree=$($MTL wrap HEAD --under new/prefix/)
+diff -u <($MTL rev-parse $tree:new/prefix) <($MTL rev-parse HEAD)
+diff -u <($MTL plumbing read-tree $tree) <($MTL plumbing read-tree HEAD | sed 's,\t,\tnew/prefix/,2')
+
+# and is undone by reroot
+diff -u <($MTL reroot $tree:new/prefix) <($MTL rev-parse HEAD)
+$MTL reroot $tree:new/prefix --save unwrapped >/dev/null
+diff -u <($MTL rev-parse unwrapped) <($MTL rev-parse HEAD)
+wrapped=$($MTL wrap sub --under dir1 --save wrapped)
+diff -u <($MTL rev-parse wrapped) <(echo $wrapped)
+diff -u <($MTL rev-parse wrapped:dir1) <(echo $dir1)
@@ -0,0 +1,47 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+$MTL local build >/dev/null
+head=$($MTL rev-parse HEAD)
+
+# no rules gives the same tree
+diff -u <($MTL rewrite HEAD) <(echo $head)
+
+# exclude
+tree=$($MTL rewrite HEAD --exclude 'dir1/**' --exclude '*.c')
+diff -u <($MTL plumbing read-tree $tree | cut -f3) <(cat <<EOT
+README
+dir2/file1
+file1
+file2
+z1/file
+EOT
+)
+# untouched subtrees keep their IDs
+diff -u <($MTL rev-parse $tree:dir2) <($MTL rev-parse HEAD:dir2)
+
+# rename directories and files
+tree=$($MTL rewrite HEAD --rename 'dir1/:moved/deep/' --rename 'file2:dir2/file2')
+diff -u <($MTL plumbing read-tree $tree | cut -f3) <(cat <<EOT
+README
+dir2/file1
+dir2/file2
+file1
+main.c
+moved/deep/file1
+z1/file
+EOT
+)
+diff -u <($MTL rev-parse $tree:moved/deep) <($MTL rev-parse HEAD:dir1)
+
+# both, excludes match the old paths
+tree=$($MTL rewrite HEAD --exclude 'dir1/file1' --rename 'dir2/:dir1/')
+diff -u <($MTL plumbing read-tree $tree | grep dir) <($MTL plumbing read-tree HEAD | grep dir2 | sed 's/dir2/dir1/')
+
+# renaming onto an existing path fails
+if $MTL rewrite HEAD --rename 'dir1:dir2' 2>/dev/null; then
+  false
+fi
@@ -0,0 +1,23 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+mkdir scratch
+diff -u <($MTL selftest --dir scratch --files 20) <(cat <<EOF
+generate  ok
+build     ok
+diff      ok
+pack      ok
+gc        ok
+fsck      ok
+PASS
+EOF
+)
+diff -u <(ls scratch) /dev/null
+
+# --keep leaves the repository for inspection
+$MTL selftest --dir scratch --files 5 --keep >/dev/null
+diff -u <(ls scratch | wc -l | awk '{print $1}') <(echo 1)
+diff -u <(ls scratch/*/.mtl | grep -c pack) <(echo 1)
@@ -9,12 +9,12 @@ $MTL ref save z1 f015d1f89f0287bf >/dev/null
 
 # cat-object
 ## HEAD
-diff -u <($MTL cat-object HEAD) <(cat .mtl/objects/99/f9d6592fc5edec)
+diff -u <($MTL cat-object HEAD) <(loose_object .mtl/objects/99/f9d6592fc5edec)
 ## ref-name
-diff -u <($MTL cat-object z1) <(cat .mtl/objects/f0/15d1f89f0287bf)
+diff -u <($MTL cat-object z1) <(loose_object .mtl/objects/f0/15d1f89f0287bf)
 
 # object-expression
-diff -u <($MTL cat-object HEAD:z1) <(cat .mtl/objects/f0/15d1f89f0287bf)
+diff -u <($MTL cat-object HEAD:z1) <(loose_object .mtl/objects/f0/15d1f89f0287bf)
 
 # ref
 $MTL ref save root HEAD >/dev/null
@@ -51,4 +51,9 @@ cd $(setup_new case2)
 $MTL local build >/dev/null
 
 diff -u <($MTL rev-parse HEAD:a1/b1/c2) <(echo 8232f35a21d5b43c)
-diff -u <($MTL cat-object HEAD:a1/b1/c2) <(cat .mtl/objects/82/32f35a21d5b43c)
\ No newline at end of file
+diff -u <($MTL cat-object HEAD:a1/b1/c2) <(loose_object .mtl/objects/82/32f35a21d5b43c)
+# abbreviated object ID
+diff -u <($MTL rev-parse 8232f3) <(echo 8232f35a21d5b43c)
+if $MTL rev-parse 0000 2>/dev/null; then
+  false
+fi
@@ -0,0 +1,21 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+# the footer goes to stderr and leaves the output alone
+diff -u <($MTL --stats local build 2>/dev/null) <(echo "Written HEAD: 99f9d6592fc5edec")
+$MTL local build --stats=json 2>&1 >/dev/null | grep -q '"writes":0,'
+
+diff -u <($MTL --stats print-tree 2>&1 >/dev/null | cut -c1-16 | sed 's/ *$//') <(cat <<EOF
+wall time
+loose reads
+packed reads
+writes
+EOF
+)
+$MTL print-tree --stats 2>&1 >/dev/null | grep -q "^loose reads     4 (332 bytes)$"
+
+$MTL pack >/dev/null
+$MTL print-tree --stats=json 2>&1 >/dev/null | grep -q '"loose_reads":0,"loose_bytes":0,"packed_reads":4,"packed_bytes":332,'
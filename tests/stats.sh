@@ -0,0 +1,55 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+$MTL local build >/dev/null
+
+diff -u <($MTL stats) <(cat <<EOF
+trees           4
+files           7
+distinct trees  4
+distinct files  7
+tree bytes      276
+max depth       2
+EOF
+)
+
+diff -u <($MTL stats --objects | sed -n '/^entries per directory/,/^$/p') <(cat <<EOF
+entries per directory
+  1             3
+  4-7           1
+
+EOF
+)
+
+# the same numbers as JSON, histograms only with --objects
+diff -u <($MTL stats --json) <(echo '{"trees":4,"files":7,"distinct_trees":4,"distinct_files":7,"tree_bytes":276,"max_depth":2}')
+diff -u <($MTL stats --json --objects | perl -ne 'print "$1\n" if /"depth":(\[.*?\])/') \
+  <(echo '[{"min":1,"max":1,"count":7},{"min":2,"max":2,"count":3}]')
+
+# subtrees are measured on their own
+diff -u <($MTL stats HEAD:dir1 | head -2) <(printf "trees           1\nfiles           1\n")
+
+# sampling reads objects back, loose and packed, and checks their IDs
+$MTL pack
+echo changed > README
+$MTL local build >/dev/null
+diff -u <($MTL stats --sample 100 | sed -n '/^sampled/,$p') <(cat <<EOF
+sampled         5 (1 loose, 4 packed)
+corrupt         0
+corruption      0.0% (95% CI 0.0%-43.4%)
+EOF
+)
+$MTL stats --sample 2 --json | grep -q '"sampled":2,'
+
+object=$(find .mtl/objects -type f)
+# a name in the root tree, so that it still parses
+printf 'X' | dd of=$object bs=1 seek=36 conv=notrunc status=none
+diff -u <($MTL stats --sample 100 --json | perl -ne 'print "$1\n" if /"corrupt":(\[.*?\])/') \
+  <(echo "[\"$(echo $object | sed 's|.mtl/objects/||; s|/||')\"]")
+$MTL stats --sample 100 2>/dev/null | grep -q "^corruption      20.0% (95% CI "
+status=0
+$MTL stats --sample 100 >/dev/null 2>&1 || status=$?
+test "$status" -eq 5
@@ -0,0 +1,19 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+mkdir scratch
+output=$($MTL tool bench-compare --baseline $MTL --dir scratch --files 20 --runs 1)
+diff -u <(echo "$output" | awk '{print $1}') <(printf "step\nbuild\nrebuild\ndiff\npack\n")
+if echo "$output" | tail -4 | grep -qvE "^[a-z]+ +[0-9.]+s +[0-9.]+s +[-+][0-9.]+%$"; then
+  false
+fi
+diff -u <(ls scratch) /dev/null
+
+# a baseline that fails is reported, and the workload is still removed
+if $MTL tool bench-compare --baseline /bin/false --dir scratch --files 5 2>/dev/null; then
+  false
+fi
+diff -u <(ls scratch) /dev/null
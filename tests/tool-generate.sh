@@ -0,0 +1,33 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+manifest1=$(mktemp)
+manifest2=$(mktemp)
+echo $manifest1 >> $DROP_LIST
+echo $manifest2 >> $DROP_LIST
+
+# the same seed generates the same files
+$MTL tool generate gen1 20 --num-kilobytes 1 --num-kilobytes-stddev 0 -p 1 --seed 42 --manifest $manifest1
+$MTL tool generate gen2 20 --num-kilobytes 1 --num-kilobytes-stddev 0 -p 1 --seed 42 --manifest $manifest2
+diff -u <(sed 's/gen1/gen/' $manifest1) <(sed 's/gen2/gen/' $manifest2)
+[ $(wc -l < $manifest1) -eq 21 ]
+
+# the manifest lists every file with its hash and size
+tail -n +2 $manifest1 | while read -r hash size path; do
+  [ $($MTL tool hash $path | awk '{print $1}') = $hash ]
+  [ $(stat -c %s $path) = $size ]
+done
+[ $(find gen1 -type f | wc -l) -eq 20 ]
+
+# and starts with the root tree of the directory
+diff -u <(head -1 $manifest1 | awk '{print $1}') <($MTL tool hash --tree gen1 | awk '{print $1}')
+diff -u <(head -1 $manifest1 | awk '{print $1}') <(head -1 $manifest2 | awk '{print $1}')
+
+# another seed generates other files
+$MTL tool generate gen3 20 --num-kilobytes 1 --num-kilobytes-stddev 0 -p 1 --seed 43 --manifest $manifest2
+if diff -q <(head -1 $manifest1 | awk '{print $1}') <(head -1 $manifest2 | awk '{print $1}') >/dev/null; then
+  false
+fi
@@ -0,0 +1,13 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+# tree mode matches local build without writing to the repository
+diff -u <($MTL tool hash --tree . | awk '{print $1}') <(echo 99f9d6592fc5edec)
+diff -u <($MTL tool hash --tree --hidden . | awk '{print $1}') <(echo 6b1d722afb0c117d)
+[ ! -e .mtl ]
+
+$MTL local build >/dev/null
+diff -u <($MTL tool hash --tree . | awk '{print $1}') <(echo $(cat .mtl/HEAD))
@@ -0,0 +1,18 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+version=$($MTL --version | awk '{print $2}')
+$MTL version | grep -q "^mtl $version$"
+$MTL version | grep -q "^hash algorithms: xxh3-64$"
+
+json=$($MTL version --format json)
+echo "$json" | grep -q "^{\"version\":\"$version\",\"features\":\[[^]]*\],"
+echo "$json" | grep -q '"object_format_versions":\[0,1\]'
+echo "$json" | grep -q '"hash_algorithms":\["xxh3-64"\]'
+echo "$json" | grep -q '"commands":\[.*"local build".*"version"'
+
+# no repository is needed
+MTL_DIR=/nonexistent $MTL version > /dev/null
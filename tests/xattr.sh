@@ -0,0 +1,39 @@
+#!/bin/bash
+
+. $(dirname $0)/common.inc
+
+cd $(setup_new case1)
+
+setxattr() {
+  python3 -c 'import os, sys; os.setxattr(sys.argv[1], sys.argv[2], sys.argv[3].encode())' "$@"
+}
+
+# without any of the selected attributes, the IDs are those of the contents
+$MTL local build --xattr 'user.*' --acl | grep -q "99f9d6592fc5edec"
+
+setxattr README user.label blue
+$MTL local build | grep -q "99f9d6592fc5edec"
+labelled=$($MTL local build --xattr 'user.label' | awk '{print $NF}')
+[ $labelled != 99f9d6592fc5edec ]
+diff -u <($MTL diff 99f9d6592fc5edec $labelled | awk '{print $NF}') <(printf '.\nREADME\n')
+
+# the IDs are not those of the contents, which export-git checks
+git_dir=$(mktemp -d)
+echo $git_dir >> $DROP_LIST
+if $MTL export-git --git-dir $git_dir $labelled 2>/dev/null; then
+  false
+fi
+
+# a changed value is drift, even with the same contents
+setxattr README user.label red
+relabelled=$($MTL local build --xattr 'user.*' | awk '{print $NF}')
+[ $relabelled != $labelled ]
+[ $($MTL local build --xattr 'user.*' | awk '{print $NF}') = $relabelled ]
+
+# unselected attributes do not count
+$MTL local build --xattr 'security.*' | grep -q "99f9d6592fc5edec"
+
+# status compares with the attributes HEAD was built with
+$MTL local build --xattr 'user.*' >/dev/null
+diff -u <($MTL local status --xattr 'user.*') /dev/null
+diff -u <($MTL local status) <(echo "M README")
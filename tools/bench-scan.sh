@@ -0,0 +1,38 @@
+#!/bin/bash
+
+set -euo pipefail
+
+cd "$(dirname "$0")/../"
+
+usage() {
+  echo "Usage: $0 <dir> <nfile> [queue_size...]"
+  echo
+  echo "Generates <nfile> empty files under <dir> (if it does not exist yet)"
+  echo "and compares scan throughput of \"local list\" for each queue size."
+  exit 1
+}
+
+trap "echo 'An error occurred. Exiting.'" ERR
+
+[ $# -lt 2 ] && usage
+
+readonly dir=$1
+readonly nfile=$2
+shift 2
+queue_sizes=${*:-"1 100 1000"}
+
+cargo build --release --bin mtl
+readonly mtl=$(pwd)/target/release/mtl
+
+if [ ! -d "$dir" ]; then
+  echo "Generating $nfile files into $dir"
+  seq 0 9999 | awk -v dir="$dir" '{ printf "%s/%02d/%02d\n", dir, $1 % 100, int($1 / 100) }' | xargs mkdir -p
+  seq 1 "$nfile" | awk -v dir="$dir" '{ printf "%s/%02d/%02d/%d\n", dir, $1 % 100, int($1 / 100) % 100, $1 }' | xargs touch
+fi
+
+commands=()
+for queue_size in $queue_sizes; do
+  commands+=("$mtl -d $dir local list --scan-queue-size $queue_size")
+done
+
+hyperfine --warmup 1 --output=null "${commands[@]}"